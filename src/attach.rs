@@ -0,0 +1,206 @@
+//! File attachments for prompts.
+//!
+//! Files inside the working directory are passed with the Gemini CLI's `@path`
+//! file-reference syntax, so the CLI reads them itself. Files elsewhere in
+//! `GEMINI_INCLUDE_ROOT` are outside the CLI's workspace and can't be referenced, so
+//! their content is inlined in a fenced block instead, which only works for text files.
+//! Anything outside both is refused, so a client can't have the server read arbitrary
+//! files and send them to the model.
+
+use crate::messages::{catalog, render};
+use crate::stream::decode_text;
+use std::path::{Component, Path, PathBuf};
+
+/// Most files one call may attach
+pub const MAX_ATTACHMENTS: usize = 20;
+/// Largest file that may be attached
+pub const MAX_ATTACHMENT_BYTES: u64 = 1024 * 1024;
+
+/// How an attached file reaches the CLI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Attachment {
+    /// `@path` reference, relative to the working directory
+    Reference(PathBuf),
+    /// Content inlined in the prompt
+    Inline { path: PathBuf, content: String },
+}
+
+/// Check and load the attachments named in `paths`, relative to `base` unless absolute.
+/// Each must resolve, after following symlinks, to a file inside `base` or `root`
+pub fn resolve(paths: &[String], base: &Path, root: &Path) -> Result<Vec<Attachment>, String> {
+    let paths: Vec<&str> = paths
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .collect();
+    if paths.len() > MAX_ATTACHMENTS {
+        return Err(render(catalog().too_many_attachments, &[&MAX_ATTACHMENTS]));
+    }
+    let base = base.canonicalize().unwrap_or_else(|_| base.to_path_buf());
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+
+    let mut attachments = Vec::with_capacity(paths.len());
+    for name in paths {
+        let path = base
+            .join(name)
+            .canonicalize()
+            .ok()
+            .filter(|p| p.is_file())
+            .ok_or_else(|| render(catalog().attachment_not_found, &[&name]))?;
+        if !is_within(&path, &[&base, &root]) {
+            return Err(render(catalog().path_outside_workspace, &[&name]));
+        }
+        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if size > MAX_ATTACHMENT_BYTES {
+            return Err(render(
+                catalog().attachment_too_large,
+                &[&name, &size, &MAX_ATTACHMENT_BYTES],
+            ));
+        }
+
+        let attachment = match path.strip_prefix(&base) {
+            Ok(relative) if is_plain_relative(relative) => {
+                Attachment::Reference(relative.to_path_buf())
+            }
+            _ => {
+                let bytes = std::fs::read(&path)
+                    .map_err(|_| render(catalog().attachment_not_found, &[&name]))?;
+                if bytes.contains(&0) {
+                    return Err(render(catalog().attachment_not_text, &[&name]));
+                }
+                Attachment::Inline {
+                    path,
+                    content: decode_text(&bytes),
+                }
+            }
+        };
+        attachments.push(attachment);
+    }
+    Ok(attachments)
+}
+
+/// Whether a canonical `path` lies inside one of the canonical `roots`
+pub(crate) fn is_within(path: &Path, roots: &[&Path]) -> bool {
+    roots.iter().any(|root| path.starts_with(root))
+}
+
+pub(crate) fn is_plain_relative(path: &Path) -> bool {
+    path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// `prompt` followed by the attachments
+pub fn append_to_prompt(prompt: &str, attachments: &[Attachment]) -> String {
+    if attachments.is_empty() {
+        return prompt.to_string();
+    }
+    let mut prompt = format!("{}\n\nAttached files:", prompt);
+    for attachment in attachments {
+        match attachment {
+            Attachment::Reference(path) => {
+                prompt.push_str(&format!("\n{}", file_reference(path)));
+            }
+            Attachment::Inline { path, content } => {
+                let fence = fence_for(content);
+                prompt.push_str(&format!(
+                    "\n\n{}:\n{}\n{}\n{}",
+                    path.display(),
+                    fence,
+                    content.trim_end_matches('\n'),
+                    fence
+                ));
+            }
+        }
+    }
+    prompt
+}
+
+/// `@path` with spaces escaped the way the CLI expects, always using `/`
+pub(crate) fn file_reference(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    format!("@{}", path.replace(' ', "\\ "))
+}
+
+/// A backtick fence longer than any backtick run in `content`
+fn fence_for(content: &str) -> String {
+    let longest = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("gemini-mcp-attach-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_workspace_files_become_references() {
+        let dir = temp_dir("refs");
+        std::fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(dir.join("my notes.txt"), "hi").unwrap();
+
+        let attachments = resolve(
+            &["src/main.rs".to_string(), " my notes.txt ".to_string()],
+            &dir,
+            &dir,
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+        let attachments = attachments.unwrap();
+
+        assert_eq!(
+            append_to_prompt("Review these", &attachments),
+            "Review these\n\nAttached files:\n@src/main.rs\n@my\\ notes.txt"
+        );
+    }
+
+    #[test]
+    fn test_files_elsewhere_in_the_include_root_are_inlined() {
+        let root = temp_dir("root");
+        let workspace = root.join("src");
+        std::fs::write(root.join("doc.md"), "```rust\ncode\n```\n").unwrap();
+        std::fs::write(root.join("blob.bin"), [0u8, 1, 2]).unwrap();
+
+        let text = resolve(&["../doc.md".to_string()], &workspace, &root);
+        let binary = resolve(&["../blob.bin".to_string()], &workspace, &root);
+        let _ = std::fs::remove_dir_all(&root);
+
+        let text = text.unwrap();
+        let Attachment::Inline { content, .. } = &text[0] else {
+            panic!("expected inline attachment, got {:?}", text);
+        };
+        assert_eq!(content, "```rust\ncode\n```\n");
+        assert!(append_to_prompt("Explain", &text).ends_with(":\n````\n```rust\ncode\n```\n````"));
+        assert!(binary.is_err());
+    }
+
+    #[test]
+    fn test_rejects_files_outside_the_workspace() {
+        let dir = temp_dir("jail");
+        let workspace = dir.join("src");
+        std::fs::write(dir.join("secret"), "token").unwrap();
+
+        let parent = resolve(&["../secret".to_string()], &workspace, &workspace);
+        let absolute = resolve(&["/etc/passwd".to_string()], &workspace, &workspace);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let error = parent.unwrap_err();
+        assert!(error.contains("outside"), "error was: {}", error);
+        assert!(error.contains("../secret"), "error was: {}", error);
+        assert!(absolute.is_err());
+    }
+
+    #[test]
+    fn test_rejects_missing_and_too_many() {
+        let dir = temp_dir("errors");
+        let missing = resolve(&["nope.rs".to_string()], &dir, &dir);
+        let too_many = resolve(&vec!["a".to_string(); MAX_ATTACHMENTS + 1], &dir, &dir);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(missing.unwrap_err().contains("nope.rs"));
+        assert!(too_many.is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! Persistent audit log of tool invocations.
+//!
+//! When `GEMINI_AUDIT_LOG` names a file, the server appends one JSON line per tool
+//! call. Unlike the in-memory [`crate::history`], the log survives restarts; it is
+//! what `gemini-mcp-rs report` aggregates. Prompts and replies are never written.
+
+use crate::gemini::{FailureKind, TokenUsage};
+use crate::sessions::Attribution;
+use crate::stream::decode_text;
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const ENV_AUDIT_LOG: &str = "GEMINI_AUDIT_LOG";
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// RFC 3339 UTC timestamp of when the call started
+    pub timestamp: String,
+    pub tool: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Model reported by the CLI, or the requested one if the CLI didn't say
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<FailureKind>,
+    pub duration_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    /// Estimated USD cost from the pricing table in effect at the time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+    /// The caller's `label` and `tags` for the call
+    #[serde(flatten)]
+    pub attribution: Attribution,
+}
+
+/// Append-only JSONL audit log
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    write_lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Audit log configured through `GEMINI_AUDIT_LOG`, if any
+    pub fn from_env() -> Option<Self> {
+        configured_path().map(Self::new)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a record as a single line, creating the file and its directory if needed
+    pub fn append(&self, record: &AuditRecord) -> io::Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())
+    }
+}
+
+/// Path from `GEMINI_AUDIT_LOG`, ignoring empty values
+pub fn configured_path() -> Option<PathBuf> {
+    std::env::var_os(ENV_AUDIT_LOG)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Read every well-formed record; malformed lines (e.g. a torn final write) are skipped
+pub fn read_records(path: &Path) -> io::Result<Vec<AuditRecord>> {
+    let bytes = std::fs::read(path)?;
+    Ok(decode_text(&bytes)
+        .lines()
+        .filter_map(|line| serde_json::from_str(line.trim()).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(tool: &str, success: bool) -> AuditRecord {
+        AuditRecord {
+            timestamp: "2024-02-29T12:34:56Z".to_string(),
+            tool: tool.to_string(),
+            session_id: Some("s1".to_string()),
+            model: Some("gemini-2.5-pro".to_string()),
+            success,
+            failure: (!success).then_some(FailureKind::Timeout),
+            duration_ms: 1500,
+            usage: Some(TokenUsage {
+                input_tokens: 10,
+                output_tokens: 5,
+                total_tokens: 15,
+            }),
+            cost_usd: Some(0.5),
+            attribution: Attribution {
+                label: Some(format!("{}-agent", tool)),
+                tags: vec!["run:42".to_string()],
+            },
+        }
+    }
+
+    #[test]
+    fn test_append_and_read_round_trip() {
+        let dir = std::env::temp_dir().join(format!("gemini-mcp-audit-{}", std::process::id()));
+        let path = dir.join("nested").join("audit.jsonl");
+        let log = AuditLog::new(&path);
+
+        log.append(&record("gemini", true)).unwrap();
+        log.append(&record("gemini_agent", false)).unwrap();
+        // A torn line must not hide the records around it
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"timestamp\":\n")
+            .unwrap();
+
+        let records = read_records(&path).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(
+            records,
+            vec![record("gemini", true), record("gemini_agent", false)]
+        );
+    }
+
+    #[test]
+    fn test_failure_kind_serializes_snake_case() {
+        let line = serde_json::to_string(&record("gemini", false)).unwrap();
+        assert!(line.contains(r#""failure":"timeout""#));
+        assert!(!line.contains("prompt"));
+    }
+}
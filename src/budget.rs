@@ -0,0 +1,394 @@
+//! Wall-clock and token budgets, per call and per rolling hour.
+//!
+//! Budgets come from four environment variables, each unset (or `0`) for no limit:
+//!
+//! - `GEMINI_BUDGET_CALL_SECS`: longest one call may run; longer timeouts are cut to it
+//! - `GEMINI_BUDGET_CALL_TOKENS`: largest prompt one call may send, estimated at four
+//!   bytes per token
+//! - `GEMINI_BUDGET_HOURLY_SECS`: CLI run time all calls together may use in any hour
+//! - `GEMINI_BUDGET_HOURLY_TOKENS`: tokens (as reported by the CLI) all calls together
+//!   may use in any hour
+//!
+//! Once an hourly budget is used up, calls are rejected with [`BudgetExceeded`] until
+//! enough usage has left the window, and a call admitted while some remains has its
+//! timeout cut to the seconds left. Usage is counted as calls finish, so calls running
+//! at the same time can overshoot an hourly budget by what they use together.
+
+use crate::messages::{catalog, render};
+use crate::quota::retry_secs;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const ENV_BUDGET_CALL_SECS: &str = "GEMINI_BUDGET_CALL_SECS";
+pub const ENV_BUDGET_CALL_TOKENS: &str = "GEMINI_BUDGET_CALL_TOKENS";
+pub const ENV_BUDGET_HOURLY_SECS: &str = "GEMINI_BUDGET_HOURLY_SECS";
+pub const ENV_BUDGET_HOURLY_TOKENS: &str = "GEMINI_BUDGET_HOURLY_TOKENS";
+const WINDOW: Duration = Duration::from_secs(3600);
+const BYTES_PER_TOKEN: usize = 4;
+
+/// Configured budgets; `None` means unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub call_secs: Option<u64>,
+    pub call_tokens: Option<u64>,
+    pub hourly_secs: Option<u64>,
+    pub hourly_tokens: Option<u64>,
+}
+
+impl Limits {
+    /// Limits configured through the `GEMINI_BUDGET_*` variables
+    pub fn from_env() -> Self {
+        let limit = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .filter(|n| *n > 0)
+        };
+        Self {
+            call_secs: limit(ENV_BUDGET_CALL_SECS),
+            call_tokens: limit(ENV_BUDGET_CALL_TOKENS),
+            hourly_secs: limit(ENV_BUDGET_HOURLY_SECS),
+            hourly_tokens: limit(ENV_BUDGET_HOURLY_TOKENS),
+        }
+    }
+}
+
+/// Why a call was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BudgetExceeded {
+    CallTokens {
+        estimated: u64,
+        limit: u64,
+    },
+    HourlySecs {
+        used: u64,
+        limit: u64,
+        retry_after: Duration,
+    },
+    HourlyTokens {
+        used: u64,
+        limit: u64,
+        retry_after: Duration,
+    },
+}
+
+impl std::fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            BudgetExceeded::CallTokens { estimated, limit } => {
+                render(catalog().budget_call_tokens, &[estimated, limit])
+            }
+            BudgetExceeded::HourlySecs {
+                used,
+                limit,
+                retry_after,
+            } => render(
+                catalog().budget_hourly_secs,
+                &[used, limit, &retry_secs(*retry_after)],
+            ),
+            BudgetExceeded::HourlyTokens {
+                used,
+                limit,
+                retry_after,
+            } => render(
+                catalog().budget_hourly_tokens,
+                &[used, limit, &retry_secs(*retry_after)],
+            ),
+        };
+        f.write_str(&message)
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+impl BudgetExceeded {
+    /// Details for the error's `data`, so clients can tell budgets apart and when to retry
+    pub fn to_json(&self) -> serde_json::Value {
+        let (budget, used, limit, retry_after) = match self {
+            BudgetExceeded::CallTokens { estimated, limit } => {
+                ("call_tokens", estimated, limit, None)
+            }
+            BudgetExceeded::HourlySecs {
+                used,
+                limit,
+                retry_after,
+            } => ("hourly_secs", used, limit, Some(retry_secs(*retry_after))),
+            BudgetExceeded::HourlyTokens {
+                used,
+                limit,
+                retry_after,
+            } => ("hourly_tokens", used, limit, Some(retry_secs(*retry_after))),
+        };
+        serde_json::json!({
+            "error": "budget_exceeded",
+            "budget": budget,
+            "used": used,
+            "limit": limit,
+            "retry_after_secs": retry_after,
+        })
+    }
+}
+
+/// Budget use reported by the `budget_status` tool; limits are `null` when unlimited
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BudgetStatus {
+    pub call_secs_limit: Option<u64>,
+    pub call_tokens_limit: Option<u64>,
+    pub hourly_secs_limit: Option<u64>,
+    pub hourly_secs_used: u64,
+    pub hourly_secs_remaining: Option<u64>,
+    pub hourly_tokens_limit: Option<u64>,
+    pub hourly_tokens_used: u64,
+    pub hourly_tokens_remaining: Option<u64>,
+    /// Whether calls are being rejected now
+    pub exceeded: bool,
+    /// When calls will be admitted again, while `exceeded`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+}
+
+/// One finished call within the window
+#[derive(Debug)]
+struct Usage {
+    at: Instant,
+    duration: Duration,
+    tokens: u64,
+}
+
+impl Usage {
+    /// Run time in whole seconds, rounded up so short calls still count
+    fn secs(&self) -> u64 {
+        retry_secs(self.duration)
+    }
+}
+
+/// Tracks the last hour of usage against the configured budgets
+#[derive(Debug, Default)]
+pub struct Budget {
+    limits: Limits,
+    window: Mutex<VecDeque<Usage>>,
+}
+
+impl Budget {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            window: Mutex::default(),
+        }
+    }
+
+    /// Budget configured through the `GEMINI_BUDGET_*` variables
+    pub fn from_env() -> Self {
+        Self::new(Limits::from_env())
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Check a call with a `timeout_secs` timeout before it starts.
+    ///
+    /// Returns a shorter timeout when the budget leaves less time than that.
+    pub fn admit(
+        &self,
+        prompt: &str,
+        timeout_secs: u64,
+        now: Instant,
+    ) -> Result<Option<u64>, BudgetExceeded> {
+        if let Some(limit) = self.limits.call_tokens {
+            let estimated = estimate_tokens(prompt);
+            if estimated > limit {
+                return Err(BudgetExceeded::CallTokens { estimated, limit });
+            }
+        }
+
+        let mut window = self.window.lock().unwrap_or_else(|e| e.into_inner());
+        expire(&mut window, now);
+        if let Some(limit) = self.limits.hourly_tokens {
+            let used = window.iter().map(|u| u.tokens).sum::<u64>();
+            if used >= limit {
+                let retry_after = wait_until_below(&window, limit, now, |u| u.tokens);
+                return Err(BudgetExceeded::HourlyTokens {
+                    used,
+                    limit,
+                    retry_after,
+                });
+            }
+        }
+        let mut allowed = self.limits.call_secs.unwrap_or(u64::MAX);
+        if let Some(limit) = self.limits.hourly_secs {
+            let used = used_secs(&window);
+            if used >= limit {
+                let retry_after = wait_until_below(&window, limit, now, Usage::secs);
+                return Err(BudgetExceeded::HourlySecs {
+                    used,
+                    limit,
+                    retry_after,
+                });
+            }
+            allowed = allowed.min(limit - used);
+        }
+        Ok((allowed < timeout_secs).then_some(allowed))
+    }
+
+    /// Count a finished call that ran for `duration` and used `tokens`
+    pub fn record(&self, duration: Duration, tokens: u64, now: Instant) {
+        if self.limits.hourly_secs.is_none() && self.limits.hourly_tokens.is_none() {
+            return;
+        }
+        let mut window = self.window.lock().unwrap_or_else(|e| e.into_inner());
+        window.push_back(Usage {
+            at: now,
+            duration,
+            tokens,
+        });
+    }
+
+    /// Limits, usage within the last hour and what remains
+    pub fn status(&self, now: Instant) -> BudgetStatus {
+        let mut window = self.window.lock().unwrap_or_else(|e| e.into_inner());
+        expire(&mut window, now);
+        let secs_used = used_secs(&window);
+        let tokens_used = window.iter().map(|u| u.tokens).sum::<u64>();
+        drop(window);
+
+        let exceeded = self.admit("", u64::MAX, now).err();
+        BudgetStatus {
+            call_secs_limit: self.limits.call_secs,
+            call_tokens_limit: self.limits.call_tokens,
+            hourly_secs_limit: self.limits.hourly_secs,
+            hourly_secs_used: secs_used,
+            hourly_secs_remaining: self.limits.hourly_secs.map(|l| l.saturating_sub(secs_used)),
+            hourly_tokens_limit: self.limits.hourly_tokens,
+            hourly_tokens_used: tokens_used,
+            hourly_tokens_remaining: self
+                .limits
+                .hourly_tokens
+                .map(|l| l.saturating_sub(tokens_used)),
+            exceeded: exceeded.is_some(),
+            retry_after_secs: exceeded.and_then(|e| match e {
+                BudgetExceeded::CallTokens { .. } => None,
+                BudgetExceeded::HourlySecs { retry_after, .. }
+                | BudgetExceeded::HourlyTokens { retry_after, .. } => Some(retry_secs(retry_after)),
+            }),
+        }
+    }
+}
+
+/// Tokens a prompt is likely to use, for the per-call budget
+pub fn estimate_tokens(prompt: &str) -> u64 {
+    prompt.len().div_ceil(BYTES_PER_TOKEN) as u64
+}
+
+fn expire(window: &mut VecDeque<Usage>, now: Instant) {
+    while window
+        .front()
+        .is_some_and(|u| now.duration_since(u.at) >= WINDOW)
+    {
+        window.pop_front();
+    }
+}
+
+fn used_secs(window: &VecDeque<Usage>) -> u64 {
+    window.iter().map(Usage::secs).sum()
+}
+
+/// Time until the usage counted by `amount` drops below `limit` as calls leave the window
+fn wait_until_below(
+    window: &VecDeque<Usage>,
+    limit: u64,
+    now: Instant,
+    amount: impl Fn(&Usage) -> u64,
+) -> Duration {
+    let mut used: u64 = window.iter().map(&amount).sum();
+    let mut wait = Duration::ZERO;
+    for usage in window {
+        if used < limit {
+            break;
+        }
+        used -= amount(usage);
+        wait = (usage.at + WINDOW).saturating_duration_since(now);
+    }
+    wait
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(limits: Limits) -> Budget {
+        Budget::new(limits)
+    }
+
+    #[test]
+    fn test_call_budgets_reject_large_prompts_and_cut_timeouts() {
+        let budget = budget(Limits {
+            call_secs: Some(60),
+            call_tokens: Some(10),
+            ..Limits::default()
+        });
+        let now = Instant::now();
+        assert_eq!(budget.admit("short", 600, now), Ok(Some(60)));
+        assert_eq!(budget.admit("short", 30, now), Ok(None));
+        assert_eq!(
+            budget.admit(&"x".repeat(41), 30, now),
+            Err(BudgetExceeded::CallTokens {
+                estimated: 11,
+                limit: 10
+            })
+        );
+    }
+
+    #[test]
+    fn test_hourly_seconds_cut_timeout_then_reject_until_window_frees() {
+        let budget = budget(Limits {
+            hourly_secs: Some(100),
+            ..Limits::default()
+        });
+        let start = Instant::now();
+        budget.record(Duration::from_secs(70), 0, start);
+        assert_eq!(budget.admit("p", 600, start), Ok(Some(30)));
+
+        budget.record(Duration::from_secs(30), 0, start + Duration::from_secs(600));
+        let later = start + Duration::from_secs(900);
+        let err = budget.admit("p", 600, later).unwrap_err();
+        assert_eq!(
+            err,
+            BudgetExceeded::HourlySecs {
+                used: 100,
+                limit: 100,
+                retry_after: Duration::from_secs(2700),
+            }
+        );
+        assert_eq!(err.to_json()["retry_after_secs"], 2700);
+
+        // Once the first call leaves the window the rest of the budget is available
+        assert_eq!(
+            budget.admit("p", 600, start + Duration::from_secs(3600)),
+            Ok(Some(70))
+        );
+    }
+
+    #[test]
+    fn test_status_reports_usage_and_remaining() {
+        let budget = budget(Limits {
+            hourly_tokens: Some(1000),
+            ..Limits::default()
+        });
+        let now = Instant::now();
+        budget.record(Duration::from_secs(5), 400, now);
+        let status = budget.status(now);
+        assert_eq!(status.hourly_tokens_used, 400);
+        assert_eq!(status.hourly_tokens_remaining, Some(600));
+        assert_eq!(status.hourly_secs_remaining, None);
+        assert!(!status.exceeded);
+
+        budget.record(Duration::from_secs(5), 700, now);
+        let status = budget.status(now);
+        assert_eq!(status.hourly_tokens_remaining, Some(0));
+        assert!(status.exceeded);
+        assert_eq!(status.retry_after_secs, Some(3600));
+    }
+}
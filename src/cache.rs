@@ -0,0 +1,229 @@
+//! Response cache for the `cache_ttl_secs` parameter.
+//!
+//! A `gemini` call that sets `cache_ttl_secs` and resumes no session is looked up by
+//! a hash of everything that decides what the CLI is asked: binary, arguments (model
+//! included), working directory, environment overrides and the final prompt with its
+//! GEMINI.md instructions. A successful reply is kept for the call's TTL, and an
+//! identical call within it gets the stored reply at once instead of running Gemini.
+//!
+//! Entries live in memory (the `GEMINI_CACHE_MAX_ENTRIES` most recent, default 256)
+//! and, when `GEMINI_CACHE_DIR` is set, as one JSON file per entry in that directory,
+//! so they survive restarts.
+
+use crate::gemini::GeminiResult;
+use crate::state::write_atomic;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const ENV_CACHE_DIR: &str = "GEMINI_CACHE_DIR";
+pub const ENV_CACHE_MAX_ENTRIES: &str = "GEMINI_CACHE_MAX_ENTRIES";
+pub const DEFAULT_MAX_ENTRIES: usize = 256;
+/// Longest TTL a call may ask for
+pub const MAX_TTL_SECS: u64 = 30 * 24 * 3600; // 30 days
+
+/// Hex SHA-256 of `parts`, each length-prefixed so different splits never collide
+pub fn digest<S: AsRef<[u8]>>(parts: impl IntoIterator<Item = S>) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        let part = part.as_ref();
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// A stored reply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub session_id: String,
+    pub agent_messages: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Seconds since the Unix epoch after which the entry is stale
+    pub expires_at: u64,
+}
+
+impl CachedResponse {
+    /// The reply of a successful `result`, kept for `ttl` from `now`
+    pub fn new(result: &GeminiResult, ttl: Duration, now: SystemTime) -> Self {
+        Self {
+            session_id: result.session_id.clone(),
+            agent_messages: result.agent_messages.clone(),
+            model: result.model.clone(),
+            expires_at: unix_secs(now).saturating_add(ttl.as_secs()),
+        }
+    }
+
+    fn is_fresh(&self, now: SystemTime) -> bool {
+        unix_secs(now) < self.expires_at
+    }
+
+    /// A successful result carrying the stored reply
+    pub fn into_result(self) -> GeminiResult {
+        GeminiResult {
+            success: true,
+            session_id: self.session_id,
+            agent_messages: self.agent_messages,
+            model: self.model,
+            ..Default::default()
+        }
+    }
+}
+
+/// Stored replies by request hash
+#[derive(Debug)]
+pub struct ResponseCache {
+    entries: Mutex<HashMap<String, (CachedResponse, SystemTime)>>,
+    dir: Option<PathBuf>,
+    max_entries: usize,
+}
+
+impl ResponseCache {
+    pub fn new(dir: Option<PathBuf>, max_entries: usize) -> Self {
+        Self {
+            entries: Mutex::default(),
+            dir,
+            max_entries: max_entries.max(1),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let dir = std::env::var_os(ENV_CACHE_DIR)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+        let max_entries = std::env::var(ENV_CACHE_MAX_ENTRIES)
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_MAX_ENTRIES);
+        Self::new(dir, max_entries)
+    }
+
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.json", key)))
+    }
+
+    /// The fresh reply stored under `key`, from memory or else from disk
+    pub fn get(&self, key: &str, now: SystemTime) -> Option<CachedResponse> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((entry, _)) = entries.get(key) {
+            if entry.is_fresh(now) {
+                return Some(entry.clone());
+            }
+            entries.remove(key);
+        }
+        drop(entries);
+
+        let path = self.path(key)?;
+        let entry: CachedResponse = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())?;
+        if !entry.is_fresh(now) {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+        self.remember(key, entry.clone(), now);
+        Some(entry)
+    }
+
+    /// Store `entry` under `key`. Disk errors are logged; the entry stays in memory
+    pub fn put(&self, key: &str, entry: CachedResponse, now: SystemTime) {
+        if let Some(path) = self.path(key) {
+            let written = serde_json::to_vec(&entry)
+                .map_err(std::io::Error::from)
+                .and_then(|bytes| write_atomic(&path, &bytes));
+            if let Err(e) = written {
+                tracing::warn!("Failed to write cache entry {}: {}", path.display(), e);
+            }
+        }
+        self.remember(key, entry, now);
+    }
+
+    /// Keep `entry` in memory, dropping stale entries and then the least recently
+    /// stored ones past the limit
+    fn remember(&self, key: &str, entry: CachedResponse, now: SystemTime) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, (entry, _)| entry.is_fresh(now));
+        entries.insert(key.to_string(), (entry, now));
+        while entries.len() > self.max_entries {
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, (_, stored))| *stored)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(text: &str) -> GeminiResult {
+        GeminiResult {
+            success: true,
+            session_id: "s1".to_string(),
+            agent_messages: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_entries_expire_and_are_evicted() {
+        let cache = ResponseCache::new(None, 2);
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let ttl = Duration::from_secs(60);
+        cache.put("a", CachedResponse::new(&reply("A"), ttl, now), now);
+        assert_eq!(cache.get("a", now).unwrap().agent_messages, "A");
+        assert!(cache.get("a", now + ttl).is_none());
+
+        let later = now + Duration::from_secs(1);
+        cache.put("b", CachedResponse::new(&reply("B"), ttl, now), now);
+        cache.put("c", CachedResponse::new(&reply("C"), ttl, later), later);
+        cache.put("d", CachedResponse::new(&reply("D"), ttl, later), later);
+        assert!(cache.get("b", later).is_none());
+        assert!(cache.get("d", later).unwrap().into_result().success);
+    }
+
+    #[test]
+    fn test_disk_entries_survive_restart() {
+        let dir = std::env::temp_dir().join(format!("gemini-mcp-cache-{}", std::process::id()));
+        let now = SystemTime::now();
+        let entry = CachedResponse::new(&reply("cached"), Duration::from_secs(60), now);
+        ResponseCache::new(Some(dir.clone()), 8).put("key", entry.clone(), now);
+        let restored = ResponseCache::new(Some(dir.clone()), 8).get("key", now);
+        let expired =
+            ResponseCache::new(Some(dir.clone()), 8).get("key", now + Duration::from_secs(60));
+        let removed = !dir.join("key.json").exists();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(restored, Some(entry));
+        assert!(expired.is_none());
+        assert!(removed);
+    }
+
+    #[test]
+    fn test_digest_separates_parts() {
+        assert_eq!(digest(["ab", "c"]).len(), 64);
+        assert_ne!(digest(["ab", "c"]), digest(["a", "bc"]));
+        assert_eq!(digest(["ab", "c"]), digest(["ab", "c"]));
+    }
+}
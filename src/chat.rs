@@ -0,0 +1,135 @@
+//! Conversations given as role/content messages, for `gemini_chat`.
+//!
+//! The Gemini CLI takes one prompt per call, so [`to_prompt`] serializes the
+//! messages into a single prompt: system messages become instructions at the top,
+//! and earlier turns a `User:`/`Assistant:` transcript ahead of the turn to answer.
+//! When the call resumes a session, the CLI already holds the earlier turns, so only
+//! the messages after the last assistant message are sent.
+
+use crate::messages::{catalog, render};
+use rmcp::schemars;
+use serde::{Deserialize, Serialize};
+
+/// Most messages one call may carry
+pub const MAX_MESSAGES: usize = 200;
+
+/// Closes a serialized transcript so the reply is the next assistant turn only
+const REPLY_INSTRUCTION: &str =
+    "Reply as the assistant to the last user message. Write only the reply itself.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+impl Role {
+    fn label(self) -> &'static str {
+        match self {
+            Role::System => "System",
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+        }
+    }
+}
+
+/// One message of a conversation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ChatMessage {
+    /// "system", "user" or "assistant"
+    pub role: Role,
+    pub content: String,
+}
+
+/// The prompt that asks for the assistant's reply to `messages`, or a message saying
+/// why they can't be answered. With `resumed`, turns up to the last assistant message
+/// are left to the session
+pub fn to_prompt(messages: &[ChatMessage], resumed: bool) -> Result<String, String> {
+    if messages.is_empty() {
+        return Err(catalog().chat_messages_empty.to_string());
+    }
+    if messages.len() > MAX_MESSAGES {
+        return Err(render(catalog().chat_too_many_messages, &[&MAX_MESSAGES]));
+    }
+    if messages.last().map(|m| m.role) != Some(Role::User) {
+        return Err(catalog().chat_last_not_user.to_string());
+    }
+    if let Some(i) = messages.iter().position(|m| m.content.trim().is_empty()) {
+        return Err(render(catalog().chat_message_empty, &[&i]));
+    }
+
+    let pending = if resumed {
+        let start = messages
+            .iter()
+            .rposition(|m| m.role == Role::Assistant)
+            .map_or(0, |i| i + 1);
+        &messages[start..]
+    } else {
+        messages
+    };
+    let (system, turns): (Vec<&ChatMessage>, Vec<&ChatMessage>) =
+        pending.iter().partition(|m| m.role == Role::System);
+
+    let mut sections: Vec<String> = Vec::new();
+    if !system.is_empty() {
+        let instructions: Vec<&str> = system.iter().map(|m| m.content.trim()).collect();
+        sections.push(format!("Instructions:\n{}", instructions.join("\n\n")));
+    }
+    if turns.iter().all(|m| m.role == Role::User) {
+        // Nothing to replay: the user's messages are the prompt
+        sections.extend(turns.iter().map(|m| m.content.trim().to_string()));
+    } else {
+        let transcript: Vec<String> = turns
+            .iter()
+            .map(|m| format!("{}: {}", m.role.label(), m.content.trim()))
+            .collect();
+        sections.push(format!("Conversation:\n\n{}", transcript.join("\n\n")));
+        sections.push(REPLY_INSTRUCTION.to_string());
+    }
+    Ok(sections.join("\n\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: Role, content: &str) -> ChatMessage {
+        ChatMessage {
+            role,
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_serializes_turns_into_a_transcript() {
+        let messages = [
+            message(Role::System, "Be brief."),
+            message(Role::User, "Name a prime."),
+            message(Role::Assistant, "7"),
+            message(Role::User, "Another?"),
+        ];
+        assert_eq!(
+            to_prompt(&messages, false).unwrap(),
+            format!(
+                "Instructions:\nBe brief.\n\nConversation:\n\nUser: Name a prime.\n\nAssistant: 7\n\nUser: Another?\n\n{}",
+                REPLY_INSTRUCTION
+            )
+        );
+        assert_eq!(to_prompt(&messages, true).unwrap(), "Another?");
+        assert_eq!(
+            to_prompt(&messages[..2], false).unwrap(),
+            "Instructions:\nBe brief.\n\nName a prime."
+        );
+    }
+
+    #[test]
+    fn test_rejects_unanswerable_conversations() {
+        assert!(to_prompt(&[], false).is_err());
+        assert!(to_prompt(&[message(Role::Assistant, "Hi")], false).is_err());
+        assert!(to_prompt(&[message(Role::User, " ")], false).is_err());
+        let many = vec![message(Role::User, "Hi"); MAX_MESSAGES + 1];
+        assert!(to_prompt(&many, false).is_err());
+    }
+}
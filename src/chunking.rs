@@ -0,0 +1,86 @@
+//! Splitting text into pieces that fit one prompt.
+//!
+//! [`split`] cuts a long text at line ends, preferring blank lines, so paragraphs and
+//! top-level items stay together where they can; only a single line longer than a
+//! piece is cut mid-line. [`group`] packs short texts together for calls that combine
+//! several of them.
+
+use std::ops::Range;
+
+/// Pieces of `text`, in order, each at most `max_bytes` long
+pub fn split(text: &str, max_bytes: usize) -> Vec<&str> {
+    let max_bytes = max_bytes.max(4);
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while rest.len() > max_bytes {
+        let mut end = max_bytes;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let window = &rest[..end];
+        // A blank line in the second half of the window, or else any line end
+        let cut = window
+            .rfind("\n\n")
+            .filter(|&i| i >= end / 2)
+            .map(|i| i + 2)
+            .or_else(|| window.rfind('\n').map(|i| i + 1))
+            .unwrap_or(end);
+        pieces.push(&rest[..cut]);
+        rest = &rest[cut..];
+    }
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+    pieces
+}
+
+/// Consecutive runs of `items` whose combined length is at most `max_bytes`. An item
+/// longer than that gets a run of its own
+pub fn group<T: AsRef<str>>(items: &[T], max_bytes: usize) -> Vec<Range<usize>> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    let mut size = 0;
+    for (index, item) in items.iter().enumerate() {
+        let len = item.as_ref().len();
+        if index > start && size + len > max_bytes {
+            groups.push(start..index);
+            start = index;
+            size = 0;
+        }
+        size += len;
+    }
+    if start < items.len() {
+        groups.push(start..items.len());
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_prefers_blank_lines_and_line_ends() {
+        let text = "fn a() {\n    1\n}\n\nfn b() {\n    2\n}\n";
+        assert_eq!(split(text, 100), vec![text]);
+        assert_eq!(
+            split(text, 24),
+            vec!["fn a() {\n    1\n}\n\n", "fn b() {\n    2\n}\n"]
+        );
+        assert_eq!(split("one\ntwo\nthree\n", 9), vec!["one\ntwo\n", "three\n"]);
+
+        let long = "é".repeat(10);
+        let pieces = split(&long, 5);
+        assert_eq!(pieces.concat(), long);
+        assert!(pieces.iter().all(|p| p.len() <= 5));
+        assert!(split("", 10).is_empty());
+    }
+
+    #[test]
+    fn test_group_packs_consecutive_items() {
+        let items = ["aaaa", "bb", "cc", "dddddddd", "e"];
+        assert_eq!(group(&items, 6), vec![0..2, 2..3, 3..4, 4..5]);
+        assert_eq!(group(&items, 100), vec![0..5]);
+        assert!(group::<&str>(&[], 6).is_empty());
+    }
+}
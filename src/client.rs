@@ -0,0 +1,173 @@
+//! What the connected client supports, so responses can be shaped to fit it.
+//!
+//! The protocol version is negotiated at `initialize`: the server answers with the
+//! client's version when it supports it, so newer clients get newer features while
+//! older ones keep the 2024-11-05 behavior. Operators can pin one version instead with
+//! `--protocol-version` or GEMINI_PROTOCOL_VERSION, for clients that ask for a version
+//! they don't fully implement. Tool results carry resource links only for clients on
+//! 2025-06-18 or later, and progress notifications (including partial replies streamed
+//! while the CLI runs) are only sent when the request carried a progress token.
+//! `structuredContent` goes to every client, since tools advertise an output schema
+//! to every client and older ones ignore the field.
+
+use rmcp::model::{ProgressNotificationParam, ProgressToken, ProtocolVersion};
+use rmcp::service::{Peer, RequestContext};
+use rmcp::RoleServer;
+
+/// Protocol version to answer every client with, or `auto` to negotiate
+pub const ENV_PROTOCOL_VERSION: &str = "GEMINI_PROTOCOL_VERSION";
+const AUTO: &str = "auto";
+
+/// Protocol versions the server speaks, oldest first
+const SUPPORTED_VERSIONS: [ProtocolVersion; 3] = [
+    ProtocolVersion::V_2024_11_05,
+    ProtocolVersion::V_2025_03_26,
+    ProtocolVersion::V_2025_06_18,
+];
+
+/// Version to answer `initialize` with: the pinned one if set, else the client's if
+/// supported, else the newest supported version older than it, else the oldest
+pub fn negotiate_protocol_version(requested: &ProtocolVersion) -> ProtocolVersion {
+    if let Some(pinned) = pinned_protocol_version() {
+        return pinned;
+    }
+    SUPPORTED_VERSIONS
+        .iter()
+        .rev()
+        .find(|v| *v <= requested)
+        .unwrap_or(&SUPPORTED_VERSIONS[0])
+        .clone()
+}
+
+/// Read a `--protocol-version` value: a supported version, or `None` for `auto`
+pub fn parse_protocol_version(value: &str) -> Result<Option<ProtocolVersion>, String> {
+    let value = value.trim();
+    if value.is_empty() || value.eq_ignore_ascii_case(AUTO) {
+        return Ok(None);
+    }
+    serde_json::from_value::<ProtocolVersion>(value.into())
+        .ok()
+        .filter(|v| SUPPORTED_VERSIONS.contains(v))
+        .map(Some)
+        .ok_or_else(|| {
+            let supported: Vec<String> = SUPPORTED_VERSIONS.iter().map(|v| v.to_string()).collect();
+            format!(
+                "Unsupported protocol version '{}' (supported: {}, {})",
+                value,
+                AUTO,
+                supported.join(", ")
+            )
+        })
+}
+
+/// The version pinned with GEMINI_PROTOCOL_VERSION, unless it is unset or `auto`
+pub fn pinned_protocol_version() -> Option<ProtocolVersion> {
+    std::env::var(ENV_PROTOCOL_VERSION)
+        .ok()
+        .and_then(|v| parse_protocol_version(&v).ok().flatten())
+}
+
+/// Response features available for the current request
+#[derive(Clone, Default)]
+pub struct ClientProfile {
+    /// Client understands `resource_link` content in tool results
+    pub resource_links: bool,
+    progress: Option<(Peer<RoleServer>, ProgressToken)>,
+}
+
+impl ClientProfile {
+    /// Profile of the client that sent this request
+    pub fn from_context(context: &RequestContext<RoleServer>) -> Self {
+        let mut profile = context
+            .peer
+            .peer_info()
+            .map(|info| Self::for_protocol(&info.protocol_version))
+            .unwrap_or_default();
+        profile.progress = context
+            .meta
+            .get_progress_token()
+            .map(|token| (context.peer.clone(), token));
+        profile
+    }
+
+    /// Profile of a client that asked for protocol `version`, without progress
+    pub fn for_protocol(version: &ProtocolVersion) -> Self {
+        Self {
+            resource_links: negotiate_protocol_version(version) >= ProtocolVersion::V_2025_06_18,
+            progress: None,
+        }
+    }
+
+    /// Whether the client asked for progress notifications
+    pub fn wants_progress(&self) -> bool {
+        self.progress.is_some()
+    }
+
+    /// Report progress on a multi-step call; does nothing unless the client asked for it
+    pub async fn progress(&self, step: u32, total: u32, message: &str) {
+        self.notify(step, Some(total), message).await;
+    }
+
+    /// Send the `index`th partial reply of an open-ended call as a progress message
+    pub async fn partial_message(&self, index: u32, text: &str) {
+        self.notify(index, None, text).await;
+    }
+
+    async fn notify(&self, progress: u32, total: Option<u32>, message: &str) {
+        let Some((peer, token)) = &self.progress else {
+            return;
+        };
+        let param = ProgressNotificationParam {
+            progress_token: token.clone(),
+            progress: f64::from(progress),
+            total: total.map(f64::from),
+            message: Some(message.to_string()),
+        };
+        if let Err(e) = peer.notify_progress(param).await {
+            tracing::warn!("Failed to send progress notification: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_protocol_version() {
+        assert_eq!(
+            negotiate_protocol_version(&ProtocolVersion::V_2025_03_26),
+            ProtocolVersion::V_2025_03_26
+        );
+        // Clients newer than the server get the newest version it supports
+        let future: ProtocolVersion = serde_json::from_str("\"2099-01-01\"").unwrap();
+        assert_eq!(
+            negotiate_protocol_version(&future),
+            ProtocolVersion::V_2025_06_18
+        );
+        let ancient: ProtocolVersion = serde_json::from_str("\"2020-01-01\"").unwrap();
+        assert_eq!(
+            negotiate_protocol_version(&ancient),
+            ProtocolVersion::V_2024_11_05
+        );
+    }
+
+    #[test]
+    fn test_parse_protocol_version() {
+        assert_eq!(parse_protocol_version("auto"), Ok(None));
+        assert_eq!(parse_protocol_version(""), Ok(None));
+        assert_eq!(
+            parse_protocol_version("2024-11-05"),
+            Ok(Some(ProtocolVersion::V_2024_11_05))
+        );
+        let err = parse_protocol_version("2099-01-01").unwrap_err();
+        assert!(err.contains("2025-06-18"));
+    }
+
+    #[test]
+    fn test_default_profile_is_minimal() {
+        let profile = ClientProfile::default();
+        assert!(!profile.resource_links);
+        assert!(!profile.wants_progress());
+    }
+}
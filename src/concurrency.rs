@@ -0,0 +1,181 @@
+//! Server-wide limit on Gemini CLI processes running at the same time.
+//!
+//! `GEMINI_MAX_CONCURRENCY` caps how many calls (including background jobs) run the
+//! CLI at once; unset or `0` means no limit. A call over the limit waits for a slot
+//! for up to `GEMINI_CONCURRENCY_MAX_WAIT_SECS` (default 30, `0` to fail at once),
+//! then is rejected as busy so the client can retry later.
+//!
+//! The limit can change while calls run ([`ConcurrencyLimit::resize`]): raising it
+//! frees slots at once, lowering it takes slots out as running calls finish.
+
+use crate::config;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+pub const ENV_MAX_CONCURRENCY: &str = "GEMINI_MAX_CONCURRENCY";
+pub const ENV_CONCURRENCY_MAX_WAIT: &str = "GEMINI_CONCURRENCY_MAX_WAIT_SECS";
+const DEFAULT_MAX_WAIT_SECS: u64 = 30;
+
+/// Slots for running CLI processes. Clones share the slots
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit {
+    state: Arc<RwLock<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    /// `None` when unlimited
+    slots: Option<Arc<Semaphore>>,
+    max: usize,
+    max_wait: Duration,
+}
+
+impl ConcurrencyLimit {
+    /// At most `max` concurrent calls (`0` for no limit), waiting up to `max_wait` for a slot
+    pub fn new(max: usize, max_wait: Duration) -> Self {
+        Self {
+            state: Arc::new(RwLock::new(State {
+                slots: (max > 0).then(|| Arc::new(Semaphore::new(max))),
+                max,
+                max_wait,
+            })),
+        }
+    }
+
+    /// Limit configured through `GEMINI_MAX_CONCURRENCY` and `GEMINI_CONCURRENCY_MAX_WAIT_SECS`
+    pub fn from_env() -> Self {
+        let number = |name: &str| {
+            config::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+        Self::new(
+            number(ENV_MAX_CONCURRENCY).unwrap_or(0) as usize,
+            Duration::from_secs(number(ENV_CONCURRENCY_MAX_WAIT).unwrap_or(DEFAULT_MAX_WAIT_SECS)),
+        )
+    }
+
+    fn state(&self) -> std::sync::RwLockReadGuard<'_, State> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Most concurrent calls, `None` when unlimited
+    pub fn max(&self) -> Option<usize> {
+        let state = self.state();
+        state.slots.as_ref().map(|_| state.max)
+    }
+
+    /// Change the limit to `max` (`0` for no limit) and the wait to `max_wait`. Calls
+    /// already running keep their slots: a lower limit is reached as they finish, and
+    /// calls running when a limit is first set don't count against it
+    pub fn resize(&self, max: usize, max_wait: Duration) {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        state.max_wait = max_wait;
+        match (&state.slots, max) {
+            (_, 0) => state.slots = None,
+            (Some(slots), _) if max >= state.max => slots.add_permits(max - state.max),
+            (Some(slots), _) => {
+                // Take the surplus slots as they free up and never give them back
+                let surplus = u32::try_from(state.max - max).unwrap_or(u32::MAX);
+                let slots = Arc::clone(slots);
+                tokio::spawn(async move {
+                    if let Ok(permits) = slots.acquire_many_owned(surplus).await {
+                        permits.forget();
+                    }
+                });
+            }
+            (None, _) => state.slots = Some(Arc::new(Semaphore::new(max))),
+        }
+        state.max = max;
+    }
+
+    /// Apply `GEMINI_MAX_CONCURRENCY` and `GEMINI_CONCURRENCY_MAX_WAIT_SECS` as they are now
+    pub fn reload_from_env(&self) {
+        let fresh = Self::from_env();
+        let state = fresh.state();
+        self.resize(state.max, state.max_wait);
+    }
+
+    /// Wait for a slot, held until the returned permit is dropped. `Err` when no slot
+    /// freed up in time; `Ok(None)` when there is no limit
+    pub async fn acquire(&self) -> Result<Option<OwnedSemaphorePermit>, Busy> {
+        let (slots, max, max_wait) = {
+            let state = self.state();
+            let Some(slots) = &state.slots else {
+                return Ok(None);
+            };
+            (Arc::clone(slots), state.max, state.max_wait)
+        };
+        if let Ok(permit) = Arc::clone(&slots).try_acquire_owned() {
+            return Ok(Some(permit));
+        }
+        match tokio::time::timeout(max_wait, slots.acquire_owned()).await {
+            Ok(Ok(permit)) => Ok(Some(permit)),
+            _ => Err(Busy { running: max }),
+        }
+    }
+}
+
+/// Every slot stayed taken for the whole wait
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Busy {
+    pub running: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unlimited_by_default() {
+        let limit = ConcurrencyLimit::new(0, Duration::ZERO);
+        assert_eq!(limit.max(), None);
+        assert!(limit.acquire().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_when_full_and_frees_on_drop() {
+        let limit = ConcurrencyLimit::new(1, Duration::from_millis(20));
+        let permit = limit.acquire().await.unwrap();
+        assert!(permit.is_some());
+        assert_eq!(limit.acquire().await.unwrap_err(), Busy { running: 1 });
+
+        drop(permit);
+        assert!(limit.acquire().await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_a_slot() {
+        let limit = ConcurrencyLimit::new(1, Duration::from_secs(5));
+        let permit = limit.acquire().await.unwrap();
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            drop(permit);
+        });
+        assert!(limit.acquire().await.unwrap().is_some());
+        release.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resize_while_running() {
+        let limit = ConcurrencyLimit::new(1, Duration::from_millis(20));
+        let first = limit.acquire().await.unwrap();
+        limit.resize(2, Duration::from_millis(20));
+        assert_eq!(limit.max(), Some(2));
+        let second = limit.acquire().await.unwrap();
+        assert!(second.is_some());
+
+        // Lowering the limit waits for running calls to give their slots back
+        limit.resize(1, Duration::from_millis(20));
+        drop(first);
+        tokio::task::yield_now().await;
+        assert_eq!(limit.acquire().await.unwrap_err(), Busy { running: 1 });
+        drop(second);
+        assert!(limit.acquire().await.unwrap().is_some());
+
+        limit.resize(0, Duration::ZERO);
+        assert_eq!(limit.max(), None);
+        assert!(limit.acquire().await.unwrap().is_none());
+    }
+}
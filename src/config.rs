@@ -0,0 +1,876 @@
+//! Optional TOML configuration file, loaded with `--config <path>`.
+//!
+//! The file covers the settings operators most often pin per deployment:
+//!
+//! ```toml
+//! gemini_bin = "/usr/local/bin/gemini"
+//! model = "gemini-2.5-pro"
+//! timeout_secs = 900
+//! idle_timeout_secs = 120
+//! sandbox = true
+//! sandbox_backend = "docker"
+//! instructions_oversize = "truncate"
+//! approval_mode = "auto_edit"
+//! max_concurrency = 4
+//! allowed_models = ["gemini-2.5-pro", "gemini-2.5-flash"]
+//! model_fallbacks = ["gemini-2.5-pro", "gemini-2.5-flash"]
+//! extra_args = ["--telemetry", "false"]
+//! allow_extra_args = false
+//! proxy = "http://proxy.internal:8080"
+//! no_proxy = ["localhost", ".corp.example"]
+//! extensions = ["code-review"]
+//! allowed_mcp_servers = []
+//! working_dir = "/srv/project"
+//! prompts_dir = "prompts"
+//!
+//! [model_aliases]
+//! fast = "gemini-2.5-flash"
+//! review = "gemini-2.5-pro"
+//!
+//! [profiles.review]
+//! model = "gemini-2.5-flash"
+//! sandbox = true
+//! timeout_secs = 300
+//!
+//! [profiles.deep]
+//! model = "gemini-2.5-pro"
+//! timeout_secs = 3600
+//!
+//! [logging]
+//! audit_log = "/var/log/gemini-mcp/audit.jsonl"
+//! locale = "en"
+//! level = "info"
+//! format = "json"
+//! file = "/var/log/gemini-mcp/server.log"
+//! ```
+//!
+//! Every key is optional, and an environment variable set for the same setting wins
+//! over the file. [`Config::install`] merges the two and exports the result through
+//! the usual environment variables once, at startup. The rest of the server reads
+//! settings with [`var`], which answers from the installed configuration, so one
+//! source serves both and reloads never touch the process environment.
+//! Profiles are the exception: a call picks one with its `profile` parameter and
+//! gets the profile's values for the parameters it leaves out.
+//!
+//! [`watch`] reloads the file when it changes or, on Unix, when the server gets
+//! SIGHUP, without dropping connected clients. A reload merges the file with the
+//! environment the server started with, so keys removed from the file fall back to
+//! their defaults. `working_dir`, `prompts_dir` and the `[logging]` table are only
+//! read at startup; an invalid file is reported and the previous settings stay in
+//! force.
+
+use crate::audit::ENV_AUDIT_LOG;
+use crate::concurrency::ENV_MAX_CONCURRENCY;
+use crate::gemini::{
+    is_passthrough_name, merge_model_aliases, parse_flag, parse_model_list, parse_passthrough_list,
+    redact_proxy, ApprovalMode, ProxySetting, SandboxBackend, ENV_ALLOWED_MCP_SERVERS,
+    ENV_ALLOWED_MODELS, ENV_ALLOW_EXTRA_ARGS, ENV_APPROVAL_MODE, ENV_BIN, ENV_DEFAULT_SANDBOX,
+    ENV_DEFAULT_TIMEOUT, ENV_EXTENSIONS, ENV_EXTRA_ARGS, ENV_FORCE_MODEL, ENV_IDLE_TIMEOUT,
+    ENV_MODEL_ALIASES, ENV_MODEL_FALLBACKS, ENV_NO_PROXY, ENV_PROXY, ENV_SANDBOX_BACKEND,
+    MAX_TIMEOUT_SECS, MIN_TIMEOUT_SECS, NO_PASSTHROUGH,
+};
+use crate::instructions::{OversizeStrategy, ENV_OVERSIZE};
+use crate::logging::{self, LogFormat, ENV_LOG, ENV_LOG_FILE, ENV_LOG_FORMAT};
+use crate::messages::{Locale, ENV_LOCALE};
+use crate::prompts::ENV_PROMPTS_DIR;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// Settings from the configuration file
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Gemini CLI binary (`GEMINI_BIN`)
+    pub gemini_bin: Option<String>,
+    /// Model used when a call names none (`GEMINI_FORCE_MODEL`)
+    pub model: Option<String>,
+    /// Timeout used when a call sets none (`GEMINI_DEFAULT_TIMEOUT`)
+    pub timeout_secs: Option<u64>,
+    /// Seconds a call may go without output when it sets no idle timeout
+    /// (`GEMINI_IDLE_TIMEOUT_SECS`)
+    pub idle_timeout_secs: Option<u64>,
+    /// Whether calls that don't set `sandbox` run sandboxed (`GEMINI_DEFAULT_SANDBOX`)
+    pub sandbox: Option<bool>,
+    /// Sandbox backend for sandboxed calls that don't pick one (`GEMINI_SANDBOX_BACKEND`)
+    pub sandbox_backend: Option<String>,
+    /// Handling of GEMINI.md files over 100KB (`GEMINI_INSTRUCTIONS_OVERSIZE`)
+    pub instructions_oversize: Option<String>,
+    /// Approval mode for calls that don't set one (`GEMINI_APPROVAL_MODE`)
+    pub approval_mode: Option<String>,
+    /// Gemini processes running at once; `0` for no limit (`GEMINI_MAX_CONCURRENCY`)
+    pub max_concurrency: Option<usize>,
+    /// Models callers may request; empty allows any (`GEMINI_ALLOWED_MODELS`)
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    /// Models a call is retried on, in order, when it fails for rate limits or capacity
+    /// (`GEMINI_MODEL_FALLBACKS`)
+    #[serde(default)]
+    pub model_fallbacks: Vec<String>,
+    /// Arguments added to every CLI invocation (`GEMINI_EXTRA_ARGS`)
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+    /// Whether calls may pass their own `extra_args` (`GEMINI_ALLOW_EXTRA_ARGS`)
+    pub allow_extra_args: Option<bool>,
+    /// Proxy URL for the CLI's network traffic, or `off` (`GEMINI_PROXY`)
+    pub proxy: Option<String>,
+    /// Hosts the CLI reaches without the proxy (`GEMINI_NO_PROXY`)
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+    /// Gemini CLI extensions calls load, and the most they may ask for; empty for none
+    /// (`GEMINI_EXTENSIONS`)
+    pub extensions: Option<Vec<String>>,
+    /// MCP servers the Gemini CLI may use, and the most calls may ask for; empty for
+    /// none (`GEMINI_ALLOWED_MCP_SERVERS`)
+    pub allowed_mcp_servers: Option<Vec<String>>,
+    /// Directory the server changes into at startup, which Gemini runs in
+    pub working_dir: Option<PathBuf>,
+    /// Directory of `<name>.md` prompt templates (`GEMINI_PROMPTS_DIR`)
+    pub prompts_dir: Option<PathBuf>,
+    /// Short names callers may use for models, on top of the built-in ones
+    /// (`GEMINI_MODEL_ALIASES`)
+    #[serde(default)]
+    pub model_aliases: BTreeMap<String, String>,
+    /// Named sets of defaults callers select with the `profile` parameter
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+}
+
+/// A `[profiles.<name>]` table: values for the parameters a call leaves out
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub model: Option<String>,
+    pub timeout_secs: Option<u64>,
+    pub sandbox: Option<bool>,
+    pub sandbox_backend: Option<String>,
+    pub approval_mode: Option<String>,
+}
+
+impl Profile {
+    fn validate(&self, name: &str) -> Result<()> {
+        if self.model.as_deref().is_some_and(|m| m.trim().is_empty()) {
+            bail!("profiles.{}.model must not be empty", name);
+        }
+        if let Some(timeout) = self.timeout_secs {
+            if !(MIN_TIMEOUT_SECS..=MAX_TIMEOUT_SECS).contains(&timeout) {
+                bail!(
+                    "profiles.{}.timeout_secs must be between {} and {} seconds",
+                    name,
+                    MIN_TIMEOUT_SECS,
+                    MAX_TIMEOUT_SECS
+                );
+            }
+        }
+        if let Some(mode) = &self.approval_mode {
+            if ApprovalMode::parse(mode).is_none() {
+                bail!(
+                    "Unknown profiles.{}.approval_mode '{}' (supported: yolo, auto_edit, default)",
+                    name,
+                    mode
+                );
+            }
+        }
+        if let Some(backend) = &self.sandbox_backend {
+            if SandboxBackend::parse(backend).is_none() {
+                bail!(
+                    "Unknown profiles.{}.sandbox_backend '{}' (supported: docker, podman, sandbox-exec)",
+                    name,
+                    backend
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `[logging]` table
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// JSON Lines audit log (`GEMINI_AUDIT_LOG`)
+    pub audit_log: Option<PathBuf>,
+    /// Language of error messages (`GEMINI_MCP_LOCALE`)
+    pub locale: Option<String>,
+    /// Diagnostic log filter (`GEMINI_LOG`)
+    pub level: Option<String>,
+    /// `pretty` or `json` (`GEMINI_LOG_FORMAT`)
+    pub format: Option<String>,
+    /// Diagnostic log file (`GEMINI_LOG_FILE`)
+    pub file: Option<PathBuf>,
+}
+
+static INSTALLED: RwLock<Option<Arc<Config>>> = RwLock::new(None);
+/// The exported variables as the server found them at startup, for reloads to merge with
+static STARTUP_ENV: OnceLock<HashMap<&'static str, String>> = OnceLock::new();
+/// How often [`watch`] checks the file for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+impl Config {
+    /// Read and validate a configuration file. Relative paths in it are resolved
+    /// against the file's directory
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let mut config = Self::parse(&text)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        if let Some(dir) = &mut config.working_dir {
+            *dir = base.join(&*dir);
+            if !dir.is_dir() {
+                bail!(
+                    "Invalid config file {}: working_dir {} is not a directory",
+                    path.display(),
+                    dir.display()
+                );
+            }
+        }
+        for path in [
+            &mut config.prompts_dir,
+            &mut config.logging.audit_log,
+            &mut config.logging.file,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = base.join(&*path);
+        }
+        Ok(config)
+    }
+
+    /// Parse and validate configuration text
+    pub fn parse(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let blank = |value: &Option<String>| value.as_deref().is_some_and(|v| v.trim().is_empty());
+        if blank(&self.gemini_bin) {
+            bail!("gemini_bin must not be empty");
+        }
+        if blank(&self.model) {
+            bail!("model must not be empty");
+        }
+        if let Some(timeout) = self.timeout_secs {
+            if !(MIN_TIMEOUT_SECS..=MAX_TIMEOUT_SECS).contains(&timeout) {
+                bail!(
+                    "timeout_secs must be between {} and {} seconds",
+                    MIN_TIMEOUT_SECS,
+                    MAX_TIMEOUT_SECS
+                );
+            }
+        }
+        if let Some(idle) = self.idle_timeout_secs {
+            if !(MIN_TIMEOUT_SECS..=MAX_TIMEOUT_SECS).contains(&idle) {
+                bail!(
+                    "idle_timeout_secs must be between {} and {} seconds",
+                    MIN_TIMEOUT_SECS,
+                    MAX_TIMEOUT_SECS
+                );
+            }
+        }
+        if let Some(mode) = &self.approval_mode {
+            if ApprovalMode::parse(mode).is_none() {
+                bail!(
+                    "Unknown approval_mode '{}' (supported: yolo, auto_edit, default)",
+                    mode
+                );
+            }
+        }
+        if let Some(backend) = &self.sandbox_backend {
+            if SandboxBackend::parse(backend).is_none() {
+                bail!(
+                    "Unknown sandbox_backend '{}' (supported: docker, podman, sandbox-exec)",
+                    backend
+                );
+            }
+        }
+        if let Some(strategy) = &self.instructions_oversize {
+            if OversizeStrategy::parse(strategy).is_none() {
+                bail!(
+                    "Unknown instructions_oversize '{}' (supported: skip, fail, truncate, summarize)",
+                    strategy
+                );
+            }
+        }
+        if let Some(proxy) = &self.proxy {
+            if ProxySetting::parse(proxy).is_none() {
+                bail!(
+                    "Invalid proxy '{}' (expected a URL like http://host:port, or off)",
+                    redact_proxy(proxy)
+                );
+            }
+        }
+        if self
+            .no_proxy
+            .iter()
+            .any(|h| h.trim().is_empty() || h.contains(','))
+        {
+            bail!("no_proxy must not contain empty hosts or ','");
+        }
+        for (key, names) in [
+            ("extensions", &self.extensions),
+            ("allowed_mcp_servers", &self.allowed_mcp_servers),
+        ] {
+            if let Some(name) = names.iter().flatten().find(|n| !is_passthrough_name(n)) {
+                bail!("{} entry '{}' is not a valid name", key, name);
+            }
+        }
+        for (alias, model) in &self.model_aliases {
+            if alias.trim().is_empty() || model.trim().is_empty() {
+                bail!("model_aliases must not contain empty names");
+            }
+            if alias.contains([',', '=']) || model.contains(',') {
+                bail!("model alias '{}' must not contain ',' or '='", alias);
+            }
+        }
+        for (name, profile) in &self.profiles {
+            if name.trim().is_empty() {
+                bail!("profiles must not contain empty names");
+            }
+            profile.validate(name)?;
+        }
+        if self.allowed_models.iter().any(|m| m.trim().is_empty()) {
+            bail!("allowed_models must not contain empty names");
+        }
+        if self.model_fallbacks.iter().any(|m| m.trim().is_empty()) {
+            bail!("model_fallbacks must not contain empty names");
+        }
+        if self.extra_args.iter().any(|a| a.is_empty()) {
+            bail!("extra_args must not contain empty arguments");
+        }
+        if let (Some(model), false) = (&self.model, self.allowed_models.is_empty()) {
+            let resolved = merge_model_aliases(self.model_aliases_env().as_deref())
+                .into_iter()
+                .find(|(alias, _)| alias == model)
+                .map_or_else(|| model.clone(), |(_, target)| target);
+            if !self.allowed_models.contains(&resolved) {
+                bail!("model '{}' is not in allowed_models", model);
+            }
+        }
+        if let Some(locale) = &self.logging.locale {
+            if Locale::parse(locale).is_none() {
+                bail!("Unknown logging.locale '{}' (supported: en, zh-CN)", locale);
+            }
+        }
+        if let Some(level) = &self.logging.level {
+            if !logging::valid_filter(level) {
+                bail!("Invalid logging.level '{}'", level);
+            }
+        }
+        if let Some(format) = &self.logging.format {
+            if LogFormat::parse(format).is_none() {
+                bail!(
+                    "Unknown logging.format '{}' (supported: pretty, json)",
+                    format
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// `model_aliases` in the `alias=model,...` form of `GEMINI_MODEL_ALIASES`
+    fn model_aliases_env(&self) -> Option<String> {
+        let pairs: Vec<String> = self
+            .model_aliases
+            .iter()
+            .map(|(alias, model)| format!("{}={}", alias, model))
+            .collect();
+        (!pairs.is_empty()).then(|| pairs.join(","))
+    }
+
+    /// File values for settings whose environment variable is set replaced by the
+    /// environment's value
+    pub fn merged_with_env(self) -> Self {
+        self.merged_with(|name| std::env::var(name).ok())
+    }
+
+    /// File values replaced by those `lookup` finds for their environment variables
+    fn merged_with(mut self, lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let env = |name: &str| {
+            lookup(name)
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        if let Some(bin) = env(ENV_BIN) {
+            self.gemini_bin = Some(bin);
+        }
+        if let Some(model) = env(ENV_FORCE_MODEL) {
+            self.model = Some(model);
+        }
+        if let Some(timeout) = env(ENV_DEFAULT_TIMEOUT).and_then(|v| v.parse().ok()) {
+            self.timeout_secs = Some(timeout);
+        }
+        if let Some(idle) = env(ENV_IDLE_TIMEOUT).and_then(|v| v.parse().ok()) {
+            self.idle_timeout_secs = Some(idle);
+        }
+        if let Some(aliases) = env(ENV_MODEL_ALIASES) {
+            self.model_aliases = aliases
+                .split(',')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(alias, model)| (alias.trim().to_string(), model.trim().to_string()))
+                .collect();
+        }
+        if let Some(models) = env(ENV_ALLOWED_MODELS) {
+            self.allowed_models = parse_model_list(&models);
+        }
+        if let Some(models) = env(ENV_MODEL_FALLBACKS) {
+            self.model_fallbacks = parse_model_list(&models);
+        }
+        if let Some(args) = env(ENV_EXTRA_ARGS).and_then(|v| shell_words::split(&v).ok()) {
+            self.extra_args = args;
+        }
+        if let Some(allow) = env(ENV_ALLOW_EXTRA_ARGS).and_then(|v| parse_flag(&v)) {
+            self.allow_extra_args = Some(allow);
+        }
+        if let Some(proxy) = env(ENV_PROXY) {
+            self.proxy = Some(proxy);
+        }
+        if let Some(hosts) = env(ENV_NO_PROXY) {
+            self.no_proxy = hosts
+                .split(',')
+                .map(str::trim)
+                .filter(|h| !h.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        if let Some(names) = env(ENV_EXTENSIONS) {
+            self.extensions = Some(parse_passthrough_list(&names));
+        }
+        if let Some(names) = env(ENV_ALLOWED_MCP_SERVERS) {
+            self.allowed_mcp_servers = Some(parse_passthrough_list(&names));
+        }
+        if let Some(sandbox) = env(ENV_DEFAULT_SANDBOX).and_then(|v| parse_flag(&v)) {
+            self.sandbox = Some(sandbox);
+        }
+        if let Some(backend) = env(ENV_SANDBOX_BACKEND) {
+            self.sandbox_backend = Some(backend);
+        }
+        if let Some(strategy) = env(ENV_OVERSIZE) {
+            self.instructions_oversize = Some(strategy);
+        }
+        if let Some(mode) = env(ENV_APPROVAL_MODE) {
+            self.approval_mode = Some(mode);
+        }
+        if let Some(max) = env(ENV_MAX_CONCURRENCY).and_then(|v| v.parse().ok()) {
+            self.max_concurrency = Some(max);
+        }
+        if let Some(dir) = env(ENV_PROMPTS_DIR) {
+            self.prompts_dir = Some(PathBuf::from(dir));
+        }
+        if let Some(log) = env(ENV_AUDIT_LOG) {
+            self.logging.audit_log = Some(PathBuf::from(log));
+        }
+        if let Some(locale) = env(ENV_LOCALE) {
+            self.logging.locale = Some(locale);
+        }
+        if let Some(level) = env(ENV_LOG) {
+            self.logging.level = Some(level);
+        }
+        if let Some(format) = env(ENV_LOG_FORMAT) {
+            self.logging.format = Some(format);
+        }
+        if let Some(file) = env(ENV_LOG_FILE) {
+            self.logging.file = Some(PathBuf::from(file));
+        }
+        self
+    }
+
+    /// Merge with the environment and make the result the server's configuration:
+    /// settings are exported as environment variables and the working directory is
+    /// entered. Call once at startup, before the async runtime starts any threads,
+    /// since changing the environment while other threads read it is unsound
+    pub fn install(self) -> Result<Arc<Config>> {
+        let config = self.merged_with_startup_env();
+        config.export();
+        if let Some(dir) = &config.working_dir {
+            std::env::set_current_dir(dir)
+                .with_context(|| format!("Failed to change to working_dir {}", dir.display()))?;
+        }
+        Ok(config.make_current())
+    }
+
+    /// Load `path` again and make it the server's configuration, keeping the working
+    /// directory and logging settings of the running server. The new settings are
+    /// seen through [`var`]; the environment is left as [`Config::install`] set it.
+    /// On error the previous settings stay in force
+    pub fn reload(path: &Path) -> Result<Arc<Config>> {
+        let mut config = Self::load(path)?.merged_with_startup_env();
+        let previous = current();
+        for (key, changed) in [
+            ("working_dir", config.working_dir != previous.working_dir),
+            ("prompts_dir", config.prompts_dir != previous.prompts_dir),
+            ("logging", config.logging != previous.logging),
+        ] {
+            if changed {
+                tracing::warn!("{} changed in {}; restart to apply it", key, path.display());
+            }
+        }
+        config.working_dir = previous.working_dir.clone();
+        config.prompts_dir = previous.prompts_dir.clone();
+        config.logging = previous.logging.clone();
+        Ok(config.make_current())
+    }
+
+    /// Merged with the environment the server started with, before any of it was
+    /// overwritten by [`Config::export`]
+    fn merged_with_startup_env(self) -> Self {
+        let startup = STARTUP_ENV.get_or_init(|| {
+            Self::default()
+                .exports()
+                .into_iter()
+                .filter_map(|(name, _)| std::env::var(name).ok().map(|value| (name, value)))
+                .collect()
+        });
+        self.merged_with(|name| startup.get(name).cloned())
+    }
+
+    fn make_current(self) -> Arc<Config> {
+        let config = Arc::new(self);
+        *INSTALLED.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::clone(&config));
+        config
+    }
+
+    /// Set every exported variable this configuration has a value for and clear the rest
+    fn export(&self) {
+        for (name, value) in self.exports() {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+    }
+
+    /// The environment variables standing for the settings, with their values
+    fn exports(&self) -> Vec<(&'static str, Option<String>)> {
+        let config = self;
+        vec![
+            (ENV_BIN, config.gemini_bin.clone()),
+            (ENV_FORCE_MODEL, config.model.clone()),
+            (
+                ENV_DEFAULT_TIMEOUT,
+                config.timeout_secs.map(|t| t.to_string()),
+            ),
+            (
+                ENV_IDLE_TIMEOUT,
+                config.idle_timeout_secs.map(|t| t.to_string()),
+            ),
+            (ENV_DEFAULT_SANDBOX, config.sandbox.map(|s| s.to_string())),
+            (ENV_SANDBOX_BACKEND, config.sandbox_backend.clone()),
+            (ENV_OVERSIZE, config.instructions_oversize.clone()),
+            (ENV_APPROVAL_MODE, config.approval_mode.clone()),
+            (
+                ENV_MAX_CONCURRENCY,
+                config.max_concurrency.map(|m| m.to_string()),
+            ),
+            (ENV_MODEL_ALIASES, config.model_aliases_env()),
+            (
+                ENV_ALLOWED_MODELS,
+                Some(config.allowed_models.join(",")).filter(|m| !m.is_empty()),
+            ),
+            (
+                ENV_MODEL_FALLBACKS,
+                Some(config.model_fallbacks.join(",")).filter(|m| !m.is_empty()),
+            ),
+            (
+                ENV_EXTRA_ARGS,
+                Some(shell_words::join(&config.extra_args)).filter(|a| !a.is_empty()),
+            ),
+            (
+                ENV_ALLOW_EXTRA_ARGS,
+                config.allow_extra_args.map(|a| a.to_string()),
+            ),
+            (ENV_PROXY, config.proxy.clone()),
+            (
+                ENV_NO_PROXY,
+                Some(config.no_proxy.join(",")).filter(|h| !h.is_empty()),
+            ),
+            (
+                ENV_EXTENSIONS,
+                config.extensions.as_deref().map(passthrough_env),
+            ),
+            (
+                ENV_ALLOWED_MCP_SERVERS,
+                config.allowed_mcp_servers.as_deref().map(passthrough_env),
+            ),
+            (
+                ENV_PROMPTS_DIR,
+                config.prompts_dir.as_ref().map(|p| p.display().to_string()),
+            ),
+            (
+                ENV_AUDIT_LOG,
+                config
+                    .logging
+                    .audit_log
+                    .as_ref()
+                    .map(|p| p.display().to_string()),
+            ),
+            (ENV_LOCALE, config.logging.locale.clone()),
+            (ENV_LOG, config.logging.level.clone()),
+            (ENV_LOG_FORMAT, config.logging.format.clone()),
+            (
+                ENV_LOG_FILE,
+                config
+                    .logging
+                    .file
+                    .as_ref()
+                    .map(|p| p.display().to_string()),
+            ),
+        ]
+    }
+}
+
+/// The environment form of an extension or MCP server list
+fn passthrough_env(names: &[String]) -> String {
+    if names.is_empty() {
+        return NO_PASSTHROUGH.to_string();
+    }
+    names.join(",")
+}
+
+/// The installed configuration, or an empty one when no file was loaded
+pub fn current() -> Arc<Config> {
+    INSTALLED
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+/// The value of the environment variable `name`, like [`std::env::var`], except that
+/// settings the installed configuration exports come from the configuration, so they
+/// follow reloads
+pub fn var(name: &str) -> Result<String, std::env::VarError> {
+    let installed = INSTALLED.read().unwrap_or_else(|e| e.into_inner()).clone();
+    var_in(installed.as_deref(), name)
+}
+
+/// [`var`] with `installed` as the installed configuration
+fn var_in(installed: Option<&Config>, name: &str) -> Result<String, std::env::VarError> {
+    let Some(config) = installed else {
+        return std::env::var(name);
+    };
+    match config.exports().into_iter().find(|(n, _)| *n == name) {
+        Some((_, value)) => value.ok_or(std::env::VarError::NotPresent),
+        None => std::env::var(name),
+    }
+}
+
+/// The profile called `name` in the installed configuration
+pub fn profile(name: &str) -> Option<Profile> {
+    current().profiles.get(name).cloned()
+}
+
+/// Reload the configuration file at `path` whenever it changes or, on Unix, the
+/// process gets SIGHUP, calling `on_reload` with the new settings. Runs until the
+/// task is dropped
+pub async fn watch(path: PathBuf, on_reload: impl Fn(&Config)) {
+    let fingerprint = |path: &Path| -> Option<(SystemTime, u64)> {
+        let meta = std::fs::metadata(path).ok()?;
+        Some((meta.modified().ok()?, meta.len()))
+    };
+    let mut seen = fingerprint(&path);
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    #[cfg(unix)]
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+
+    loop {
+        #[cfg(unix)]
+        let hangup_received = async {
+            if let Some(signal) = hangup.as_mut() {
+                if signal.recv().await.is_some() {
+                    return;
+                }
+            }
+            // Without a handler, or once it is gone, only file changes reload
+            std::future::pending::<()>().await
+        };
+        #[cfg(not(unix))]
+        let hangup_received = std::future::pending::<()>();
+
+        let forced = tokio::select! {
+            _ = interval.tick() => false,
+            _ = hangup_received => true,
+        };
+        let now = fingerprint(&path);
+        if !forced && (now == seen || now.is_none()) {
+            continue;
+        }
+        seen = now;
+        match Config::reload(&path) {
+            Ok(config) => {
+                tracing::info!("Reloaded config file {}", path.display());
+                on_reload(&config);
+            }
+            Err(e) => tracing::error!("Keeping the previous settings: {:#}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_full_config() {
+        let config = Config::parse(
+            r#"
+gemini_bin = "/opt/gemini"
+model = "gemini-2.5-pro"
+timeout_secs = 900
+idle_timeout_secs = 120
+sandbox = true
+sandbox_backend = "podman"
+max_concurrency = 4
+allowed_models = ["gemini-2.5-pro", "gemini-2.5-flash"]
+
+[model_aliases]
+review = "gemini-2.5-pro"
+
+[profiles.review]
+model = "review"
+sandbox = true
+timeout_secs = 300
+
+[profiles.deep]
+model = "gemini-2.5-pro"
+timeout_secs = 3600
+
+[logging]
+audit_log = "audit.jsonl"
+locale = "zh-CN"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.gemini_bin.as_deref(), Some("/opt/gemini"));
+        assert_eq!(config.timeout_secs, Some(900));
+        assert_eq!(config.idle_timeout_secs, Some(120));
+        assert_eq!(config.sandbox, Some(true));
+        assert_eq!(config.sandbox_backend.as_deref(), Some("podman"));
+        assert_eq!(config.max_concurrency, Some(4));
+        assert_eq!(config.allowed_models.len(), 2);
+        assert_eq!(config.model_aliases["review"], "gemini-2.5-pro");
+        assert_eq!(config.profiles["review"].sandbox, Some(true));
+        assert_eq!(config.profiles["deep"].timeout_secs, Some(3600));
+        assert_eq!(config.profiles["deep"].approval_mode, None);
+        assert!(Config::parse("allowed_models = [\"gemini-2.5-pro\"]\nmodel = \"smart\"").is_ok());
+        assert_eq!(config.logging.locale.as_deref(), Some("zh-CN"));
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_values() {
+        for text in [
+            "timeout_secs = 0",
+            "timeout_secs = 7200",
+            "idle_timeout_secs = 0",
+            "model = \" \"",
+            "unknown_key = 1",
+            "allowed_models = [\"a\"]\nmodel = \"b\"",
+            "extra_args = [\"\"]",
+            "model_fallbacks = [\" \"]",
+            "approval_mode = \"ask\"",
+            "sandbox_backend = \"firejail\"",
+            "instructions_oversize = \"drop\"",
+            "proxy = \"proxy.internal:8080\"",
+            "no_proxy = [\"\"]",
+            "extensions = [\"--yolo\"]",
+            "allowed_mcp_servers = [\"a b\"]",
+            "[model_aliases]\nfast = \"\"",
+            "[model_aliases]\n\"a,b\" = \"gemini-2.5-pro\"",
+            "[profiles.slow]\ntimeout_secs = 7200",
+            "[profiles.ask]\napproval_mode = \"ask\"",
+            "[profiles.x]\ntemperature = 1",
+            "[logging]\nlocale = \"fr\"",
+            "[logging]\nformat = \"xml\"",
+        ] {
+            assert!(Config::parse(text).is_err(), "accepted: {}", text);
+        }
+    }
+
+    #[test]
+    fn test_environment_overrides_file() {
+        let config = Config::parse("model = \"gemini-2.5-pro\"\nmax_concurrency = 4\n").unwrap();
+        let env: HashMap<&str, &str> = [
+            (ENV_FORCE_MODEL, " gemini-2.5-flash "),
+            (ENV_MAX_CONCURRENCY, "2"),
+            (ENV_APPROVAL_MODE, ""),
+            (ENV_NO_PROXY, "localhost, .corp.example"),
+            (ENV_ALLOWED_MCP_SERVERS, "none"),
+            (ENV_DEFAULT_SANDBOX, "TRUE"),
+            (ENV_ALLOW_EXTRA_ARGS, "maybe"),
+            (
+                ENV_EXTRA_ARGS,
+                "--proxy 'http://proxy:8080' --telemetry false",
+            ),
+        ]
+        .into();
+        let merged = config.merged_with(|name| env.get(name).map(|v| v.to_string()));
+        assert_eq!(merged.model.as_deref(), Some("gemini-2.5-flash"));
+        assert_eq!(
+            merged.extra_args,
+            ["--proxy", "http://proxy:8080", "--telemetry", "false"]
+        );
+        assert_eq!(merged.max_concurrency, Some(2));
+        assert_eq!(merged.approval_mode, None);
+        assert_eq!(merged.no_proxy, ["localhost", ".corp.example"]);
+        assert_eq!(merged.allowed_mcp_servers, Some(Vec::new()));
+        assert_eq!(merged.sandbox, Some(true));
+        assert_eq!(merged.allow_extra_args, None);
+
+        // Settings left out of both are cleared on export
+        let exports: HashMap<_, _> = merged.exports().into_iter().collect();
+        assert_eq!(exports[ENV_MAX_CONCURRENCY].as_deref(), Some("2"));
+        assert_eq!(exports[ENV_BIN], None);
+        assert_eq!(exports[ENV_ALLOWED_MCP_SERVERS].as_deref(), Some("none"));
+    }
+
+    #[test]
+    fn test_var_reads_exported_settings_from_the_configuration() {
+        std::env::set_var("GEMINI_TEST_CONFIG_VAR", "kept");
+        let config = Config {
+            model: Some("gemini-2.5-pro".to_string()),
+            ..Config::default()
+        };
+        let model = var_in(Some(&config), ENV_FORCE_MODEL);
+        let bin = var_in(Some(&config), ENV_BIN);
+        let other = var_in(Some(&config), "GEMINI_TEST_CONFIG_VAR");
+        let uninstalled = var_in(None, "GEMINI_TEST_CONFIG_VAR");
+        std::env::remove_var("GEMINI_TEST_CONFIG_VAR");
+
+        assert_eq!(model.as_deref(), Ok("gemini-2.5-pro"));
+        assert_eq!(bin, Err(std::env::VarError::NotPresent));
+        assert_eq!(other.as_deref(), Ok("kept"));
+        assert_eq!(uninstalled.as_deref(), Ok("kept"));
+    }
+
+    #[test]
+    fn test_load_resolves_paths_against_file() {
+        let dir = std::env::temp_dir().join(format!("gemini-mcp-config-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("project")).unwrap();
+        let path = dir.join("gemini-mcp.toml");
+        std::fs::write(
+            &path,
+            "working_dir = \"project\"\nprompts_dir = \"prompts\"\n[logging]\naudit_log = \"audit.jsonl\"\n",
+        )
+        .unwrap();
+        let config = Config::load(&path);
+        std::fs::write(&path, "working_dir = \"missing\"\n").unwrap();
+        let missing = Config::load(&path);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let config = config.unwrap();
+        assert_eq!(config.working_dir, Some(dir.join("project")));
+        assert_eq!(config.prompts_dir, Some(dir.join("prompts")));
+        assert_eq!(config.logging.audit_log, Some(dir.join("audit.jsonl")));
+        assert!(missing.is_err());
+    }
+}
@@ -0,0 +1,201 @@
+//! Daemon mode: one server shared by every MCP client on the machine.
+//!
+//! `--daemon --socket <path>` listens on a UNIX domain socket (a named pipe such as
+//! `\\.\pipe\gemini-mcp` on Windows) instead of stdio. Each connection is an MCP session
+//! with the same framing detection as stdio, and every session gets a handle to the
+//! same [`GeminiServer`], so the reply cache, session registry, history, quotas and
+//! background jobs are shared. Clients that can only spawn a stdio server run
+//! `gemini-mcp-rs connect --socket <path>`, which [`relay`]s stdio to the daemon.
+//!
+//! There is no authentication: on Unix the socket is only accessible to its owner, and
+//! Windows named pipes refuse remote clients.
+
+use crate::server::GeminiServer;
+use crate::transport::AdaptiveStream;
+use anyhow::{Context, Result};
+use rmcp::ServiceExt;
+use std::path::Path;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+/// How the daemon describes its transport to clients
+pub const DAEMON_TRANSPORT: &str =
+    "local socket shared by every connected client, newline-delimited JSON or Content-Length framing (detected from the client)";
+
+/// Accept connections on `path` until the future is dropped, serving each with a new
+/// client handle of `server`
+#[cfg(unix)]
+pub async fn serve(path: &Path, server: GeminiServer) -> Result<()> {
+    let socket = bind(path)?;
+    tracing::info!("Serving MCP on {}", path.display());
+    loop {
+        let (stream, _) = socket
+            .listener
+            .accept()
+            .await
+            .context("Failed to accept a connection")?;
+        spawn_session(server.for_new_client(), stream);
+    }
+}
+
+/// Accept connections on the named pipe `path` until the future is dropped, serving
+/// each with a new client handle of `server`
+#[cfg(windows)]
+pub async fn serve(path: &Path, server: GeminiServer) -> Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let mut pipe = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(path)
+        .with_context(|| format!("Failed to create named pipe {}", path.display()))?;
+    tracing::info!("Serving MCP on {}", path.display());
+    loop {
+        pipe.connect()
+            .await
+            .context("Failed to accept a connection")?;
+        // The next client connects to a new instance of the pipe
+        let connected = std::mem::replace(
+            &mut pipe,
+            ServerOptions::new()
+                .create(path)
+                .with_context(|| format!("Failed to create named pipe {}", path.display()))?,
+        );
+        spawn_session(server.for_new_client(), connected);
+    }
+}
+
+/// Serve one connection until the client disconnects
+fn spawn_session<S>(server: GeminiServer, stream: S)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    tokio::spawn(async move {
+        match server.serve(AdaptiveStream::new(stream)).await {
+            Ok(service) => {
+                if let Err(e) = service.waiting().await {
+                    tracing::warn!("Daemon session ended with an error: {:?}", e);
+                }
+            }
+            Err(e) => tracing::warn!("Failed to start a daemon session: {:?}", e),
+        }
+    });
+}
+
+/// Pass stdin to the daemon listening on `path` and its replies to stdout, until either
+/// side closes
+pub async fn relay(path: &Path) -> Result<()> {
+    let stream = connect(path)
+        .await
+        .with_context(|| format!("No server is listening on {}", path.display()))?;
+    let (mut read, mut write) = tokio::io::split(stream);
+    let upstream = async {
+        tokio::io::copy(&mut tokio::io::stdin(), &mut write).await?;
+        write.shutdown().await
+    };
+    let downstream = async {
+        tokio::io::copy(&mut read, &mut tokio::io::stdout())
+            .await
+            .map(|_| ())
+    };
+    tokio::try_join!(upstream, downstream).context("Connection to the server failed")?;
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn connect(path: &Path) -> std::io::Result<tokio::net::UnixStream> {
+    tokio::net::UnixStream::connect(path).await
+}
+
+#[cfg(windows)]
+async fn connect(path: &Path) -> std::io::Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    use tokio::net::windows::named_pipe::ClientOptions;
+    use windows_sys::Win32::Foundation::ERROR_PIPE_BUSY;
+
+    // Every instance is busy until the daemon creates the next one
+    for _ in 0..50 {
+        match ClientOptions::new().open(path) {
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY as i32) => {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            }
+            result => return result,
+        }
+    }
+    ClientOptions::new().open(path)
+}
+
+/// A listening socket, whose file is removed when it is dropped
+#[cfg(unix)]
+struct BoundSocket {
+    listener: tokio::net::UnixListener,
+    path: std::path::PathBuf,
+}
+
+#[cfg(unix)]
+impl Drop for BoundSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Listen on `path`, replacing a socket left behind by a server that is gone. A live
+/// server or a file that isn't a socket is an error
+#[cfg(unix)]
+fn bind(path: &Path) -> Result<BoundSocket> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            anyhow::bail!("{} exists and is not a socket", path.display());
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            anyhow::bail!("Another server is already listening on {}", path.display());
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)
+        .with_context(|| format!("Failed to listen on {}", path.display()))?;
+    let socket = BoundSocket {
+        listener,
+        path: path.to_path_buf(),
+    };
+    // Anyone who can connect can run Gemini, so only the owner may
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to restrict access to {}", path.display()))?;
+    Ok(socket)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bind_replaces_stale_sockets_only() {
+        let dir = std::env::temp_dir().join(format!("gemini-mcp-daemon-{}", std::process::id()));
+        let path = dir.join("gemini-mcp.sock");
+
+        let socket = bind(&path).unwrap();
+        let mode = std::os::unix::fs::PermissionsExt::mode(
+            &std::fs::metadata(&path).unwrap().permissions(),
+        );
+        let busy = bind(&path).map(|_| ());
+        // A socket nobody listens on any more is replaced
+        let stale = std::os::unix::net::UnixListener::bind(dir.join("stale.sock")).unwrap();
+        drop(stale);
+        let replaced = bind(&dir.join("stale.sock")).map(|_| ());
+        std::fs::write(dir.join("notes.txt"), "keep me").unwrap();
+        let not_socket = bind(&dir.join("notes.txt")).map(|_| ());
+        drop(socket);
+        let removed = !path.exists();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(busy.is_err());
+        assert!(replaced.is_ok());
+        assert!(not_socket.is_err());
+        assert!(removed);
+    }
+}
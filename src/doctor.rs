@@ -0,0 +1,177 @@
+//! Installation checks printed by `gemini-mcp-rs doctor`.
+//!
+//! Covers what a broken deployment most often trips over before its first call: the
+//! Gemini CLI binary and its version (the same check as the `gemini_health` tool),
+//! configured credentials, and write access to the directories the server and the CLI
+//! write to. Like `gemini_health`, the credentials check only looks for them; the
+//! `check_auth` tool proves they work.
+
+use crate::health::{self, AuthMethod};
+use crate::scratch::ScratchRoot;
+use crate::{audit, state};
+use serde::Serialize;
+use std::fmt::Write;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// Outcome of one check
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    /// What was found, or what is wrong and how to fix it
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, outcome: Result<String, String>) -> Self {
+        let ok = outcome.is_ok();
+        let detail = outcome.unwrap_or_else(|e| e);
+        Self { name, ok, detail }
+    }
+}
+
+/// Every check, in the order they ran
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnosis {
+    /// Whether every check passed
+    pub ok: bool,
+    pub checks: Vec<Check>,
+}
+
+/// Run every check against the server's configuration
+pub async fn diagnose() -> Diagnosis {
+    let report = health::check(None, health::VERSION_TIMEOUT).await;
+    let mut checks = vec![
+        Check::new(
+            "binary",
+            report
+                .path
+                .clone()
+                .ok_or_else(|| format!("{} was not found (set GEMINI_BIN or PATH)", report.bin)),
+        ),
+        Check::new(
+            "version",
+            report
+                .version
+                .clone()
+                .ok_or_else(|| report.error.clone().unwrap_or_default()),
+        ),
+        Check::new("auth", auth_outcome(report.auth)),
+    ];
+
+    match std::env::current_dir() {
+        Ok(dir) => checks.push(Check::new("working_dir", check_writable(&dir, false))),
+        Err(e) => checks.push(Check::new("working_dir", Err(e.to_string()))),
+    }
+    let scratch = ScratchRoot::from_env();
+    checks.push(Check::new(
+        "scratch_root",
+        check_writable(scratch.root(), true),
+    ));
+    if let Some(dir) = state::state_dir() {
+        checks.push(Check::new("state_dir", check_writable(&dir, true)));
+    }
+    if let Some(dir) = audit::configured_path()
+        .as_deref()
+        .and_then(Path::parent)
+        .filter(|p| !p.as_os_str().is_empty())
+    {
+        checks.push(Check::new("audit_log", check_writable(dir, true)));
+    }
+
+    Diagnosis {
+        ok: checks.iter().all(|c| c.ok),
+        checks,
+    }
+}
+
+fn auth_outcome(method: AuthMethod) -> Result<String, String> {
+    let name = serde_json::to_value(method)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_default();
+    match method {
+        AuthMethod::None => Err(format!(
+            "no credentials found. {}",
+            health::auth_remediation(method)
+        )),
+        _ => Ok(name),
+    }
+}
+
+/// Whether a file can be created in `dir`, creating `dir` first when `create` is set
+fn check_writable(dir: &Path, create: bool) -> Result<String, String> {
+    if create {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+    }
+    let probe = dir.join(format!(".gemini-mcp-doctor-{}", std::process::id()));
+    match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+    {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            Ok(format!("{} is writable", dir.display()))
+        }
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            Ok(format!("{} is writable", dir.display()))
+        }
+        Err(e) => Err(format!("cannot write to {}: {}", dir.display(), e)),
+    }
+}
+
+/// Human-readable form of a diagnosis, one line per check
+pub fn render(diagnosis: &Diagnosis) -> String {
+    let mut out = String::new();
+    for check in &diagnosis.checks {
+        let status = if check.ok { "ok" } else { "FAIL" };
+        writeln!(out, "  [{:<4}] {:<14} {}", status, check.name, check.detail).ok();
+    }
+    let summary = if diagnosis.ok {
+        "All checks passed"
+    } else {
+        "Some checks failed"
+    };
+    writeln!(out, "\n{}", summary).ok();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_writable() {
+        let dir = std::env::temp_dir()
+            .join(format!("gemini-mcp-doctor-{}", std::process::id()))
+            .join("nested");
+
+        let missing = check_writable(&dir, false);
+        let created = check_writable(&dir, true);
+        let leftovers = std::fs::read_dir(&dir).map(|d| d.count()).unwrap_or(1);
+        let _ = std::fs::remove_dir_all(dir.parent().unwrap());
+
+        assert!(missing.is_err());
+        assert!(created.is_ok());
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn test_render_marks_failures() {
+        let diagnosis = Diagnosis {
+            ok: false,
+            checks: vec![
+                Check::new("binary", Ok("/usr/bin/gemini".to_string())),
+                Check::new("auth", auth_outcome(AuthMethod::None)),
+            ],
+        };
+        let text = render(&diagnosis);
+
+        assert!(text.contains("[ok  ] binary"), "{}", text);
+        assert!(text.contains("[FAIL] auth"), "{}", text);
+        assert!(text.ends_with("Some checks failed\n"));
+    }
+}
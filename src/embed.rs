@@ -0,0 +1,366 @@
+//! Text embeddings for the `gemini_embed` tool.
+//!
+//! The Gemini CLI has no embedding command, so this calls the Gemini API's
+//! `batchEmbedContents` endpoint directly, with the API key the CLI would use
+//! (`GEMINI_API_KEY` or `GOOGLE_API_KEY`). `GOOGLE_GEMINI_BASE_URL` points it at
+//! another endpoint, as it does for the CLI. One request embeds up to
+//! [`MAX_TEXTS`] texts; vectors come back as numbers or, to keep large batches
+//! compact, as base64 of little-endian `f32`s.
+
+use crate::messages::{catalog, render};
+use base64::Engine;
+use serde::Serialize;
+use std::time::Duration;
+
+pub const ENV_EMBED_MODEL: &str = "GEMINI_EMBED_MODEL";
+pub const DEFAULT_EMBED_MODEL: &str = "gemini-embedding-001";
+const ENV_BASE_URL: &str = "GOOGLE_GEMINI_BASE_URL";
+const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com";
+/// Most texts one call may embed, the API's batch limit
+pub const MAX_TEXTS: usize = 100;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// What the embeddings will be used for, which the model optimizes them for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskType {
+    RetrievalQuery,
+    RetrievalDocument,
+    SemanticSimilarity,
+    Classification,
+    Clustering,
+    QuestionAnswering,
+    FactVerification,
+    CodeRetrievalQuery,
+}
+
+impl TaskType {
+    const ALL: [TaskType; 8] = [
+        TaskType::RetrievalQuery,
+        TaskType::RetrievalDocument,
+        TaskType::SemanticSimilarity,
+        TaskType::Classification,
+        TaskType::Clustering,
+        TaskType::QuestionAnswering,
+        TaskType::FactVerification,
+        TaskType::CodeRetrievalQuery,
+    ];
+
+    /// Parse a task type in snake case, or the API's upper case
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_lowercase().replace('-', "_");
+        Self::ALL.into_iter().find(|t| t.as_str() == value)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TaskType::RetrievalQuery => "retrieval_query",
+            TaskType::RetrievalDocument => "retrieval_document",
+            TaskType::SemanticSimilarity => "semantic_similarity",
+            TaskType::Classification => "classification",
+            TaskType::Clustering => "clustering",
+            TaskType::QuestionAnswering => "question_answering",
+            TaskType::FactVerification => "fact_verification",
+            TaskType::CodeRetrievalQuery => "code_retrieval_query",
+        }
+    }
+}
+
+/// How vectors are returned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// Arrays of numbers
+    #[default]
+    Float,
+    /// Base64 of the little-endian `f32` values
+    Base64,
+}
+
+impl Encoding {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "float" => Some(Encoding::Float),
+            "base64" => Some(Encoding::Base64),
+            _ => None,
+        }
+    }
+}
+
+/// Texts to embed and how
+#[derive(Debug, Clone, Default)]
+pub struct EmbedRequest {
+    pub texts: Vec<String>,
+    /// Embedding model; defaults to `GEMINI_EMBED_MODEL` or [`DEFAULT_EMBED_MODEL`]
+    pub model: Option<String>,
+    pub task_type: Option<TaskType>,
+    /// Truncate vectors to this many dimensions
+    pub dimensions: Option<u32>,
+    pub encoding: Encoding,
+}
+
+/// One text's vector, in the requested encoding
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Embedding {
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub values: Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base64: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Embeddings {
+    pub model: String,
+    pub dimensions: usize,
+    pub encoding: Encoding,
+    pub embeddings: Vec<Embedding>,
+}
+
+/// A failed embedding request
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EmbedError {
+    /// The request was rejected before calling the API
+    #[error("{0}")]
+    Invalid(String),
+    /// No API key is configured
+    #[error("{}", catalog().embed_no_api_key)]
+    NoApiKey,
+    /// The API answered with an error status
+    #[error("Gemini API error {status}: {message}")]
+    Api { status: u16, message: String },
+    /// The API couldn't be reached or answered with something unreadable
+    #[error("Gemini API request failed: {0}")]
+    Transport(String),
+}
+
+/// Check the texts before anything is sent
+pub fn validate(texts: &[String]) -> Result<(), EmbedError> {
+    if texts.is_empty() {
+        return Err(EmbedError::Invalid(catalog().embed_texts_empty.to_string()));
+    }
+    if texts.len() > MAX_TEXTS {
+        return Err(EmbedError::Invalid(render(
+            catalog().embed_too_many_texts,
+            &[&MAX_TEXTS],
+        )));
+    }
+    if let Some(i) = texts.iter().position(|t| t.trim().is_empty()) {
+        return Err(EmbedError::Invalid(render(
+            catalog().embed_text_empty,
+            &[&i],
+        )));
+    }
+    Ok(())
+}
+
+/// The model used when a request names none
+pub fn default_model() -> String {
+    std::env::var(ENV_EMBED_MODEL)
+        .ok()
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty())
+        .unwrap_or_else(|| DEFAULT_EMBED_MODEL.to_string())
+}
+
+fn api_key() -> Option<String> {
+    ["GEMINI_API_KEY", "GOOGLE_API_KEY"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .map(|key| key.trim().to_string())
+        .find(|key| !key.is_empty())
+}
+
+fn base_url() -> String {
+    std::env::var(ENV_BASE_URL)
+        .ok()
+        .map(|url| url.trim().trim_end_matches('/').to_string())
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| DEFAULT_BASE_URL.to_string())
+}
+
+/// Embed every text in `request`, in order
+pub async fn embed(request: EmbedRequest) -> Result<Embeddings, EmbedError> {
+    validate(&request.texts)?;
+    let key = api_key().ok_or(EmbedError::NoApiKey)?;
+    let model = request.model.clone().unwrap_or_else(default_model);
+    let url = format!(
+        "{}/v1beta/models/{}:batchEmbedContents",
+        base_url(),
+        model.trim_start_matches("models/")
+    );
+
+    let response = reqwest::Client::new()
+        .post(url)
+        .timeout(REQUEST_TIMEOUT)
+        .header("x-goog-api-key", key)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(request_body(&request, &model).to_string())
+        .send()
+        .await
+        .map_err(|e| EmbedError::Transport(e.to_string()))?;
+    let status = response.status();
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| EmbedError::Transport(e.to_string()))?;
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+    if !status.is_success() {
+        return Err(EmbedError::Api {
+            status: status.as_u16(),
+            message: api_error_message(&body)
+                .unwrap_or_else(|| status.canonical_reason().unwrap_or("").to_string()),
+        });
+    }
+    let vectors = parse_vectors(&body, request.texts.len())?;
+    Ok(encode(model, vectors, request.encoding))
+}
+
+/// The `batchEmbedContents` request for `request`'s texts
+fn request_body(request: &EmbedRequest, model: &str) -> serde_json::Value {
+    let name = format!("models/{}", model.trim_start_matches("models/"));
+    let requests: Vec<serde_json::Value> = request
+        .texts
+        .iter()
+        .map(|text| {
+            let mut item = serde_json::json!({
+                "model": name,
+                "content": { "parts": [{ "text": text }] },
+            });
+            if let Some(task) = request.task_type {
+                item["taskType"] = task.as_str().to_ascii_uppercase().into();
+            }
+            if let Some(dimensions) = request.dimensions {
+                item["outputDimensionality"] = dimensions.into();
+            }
+            item
+        })
+        .collect();
+    serde_json::json!({ "requests": requests })
+}
+
+/// The message of an API error body (`{"error": {"message": ...}}`)
+fn api_error_message(body: &serde_json::Value) -> Option<String> {
+    body.pointer("/error/message")
+        .and_then(|m| m.as_str())
+        .map(str::to_string)
+}
+
+/// The vectors of a `batchEmbedContents` response, which must hold one per text
+fn parse_vectors(body: &serde_json::Value, expected: usize) -> Result<Vec<Vec<f32>>, EmbedError> {
+    let vectors: Option<Vec<Vec<f32>>> = body
+        .get("embeddings")
+        .and_then(|e| e.as_array())
+        .and_then(|embeddings| {
+            embeddings
+                .iter()
+                .map(|e| -> Option<Vec<f32>> {
+                    e.get("values")?
+                        .as_array()?
+                        .iter()
+                        .map(|v| v.as_f64().map(|v| v as f32))
+                        .collect()
+                })
+                .collect()
+        });
+    match vectors {
+        Some(vectors) if vectors.len() == expected => Ok(vectors),
+        _ => Err(EmbedError::Transport(format!(
+            "expected {} embeddings in the response",
+            expected
+        ))),
+    }
+}
+
+fn encode(model: String, vectors: Vec<Vec<f32>>, encoding: Encoding) -> Embeddings {
+    let dimensions = vectors.first().map_or(0, Vec::len);
+    let embeddings = vectors
+        .into_iter()
+        .enumerate()
+        .map(|(index, values)| match encoding {
+            Encoding::Float => Embedding {
+                index,
+                values: Some(values),
+                base64: None,
+            },
+            Encoding::Base64 => {
+                let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+                Embedding {
+                    index,
+                    values: None,
+                    base64: Some(base64::engine::general_purpose::STANDARD.encode(bytes)),
+                }
+            }
+        })
+        .collect();
+    Embeddings {
+        model,
+        dimensions,
+        encoding,
+        embeddings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_body() {
+        let request = EmbedRequest {
+            texts: vec!["alpha".to_string(), "beta".to_string()],
+            task_type: TaskType::parse("RETRIEVAL_DOCUMENT"),
+            dimensions: Some(256),
+            ..Default::default()
+        };
+        let body = request_body(&request, "models/gemini-embedding-001");
+        let first = &body["requests"][0];
+        assert_eq!(first["model"], "models/gemini-embedding-001");
+        assert_eq!(first["content"]["parts"][0]["text"], "alpha");
+        assert_eq!(first["taskType"], "RETRIEVAL_DOCUMENT");
+        assert_eq!(first["outputDimensionality"], 256);
+        assert_eq!(body["requests"][1]["content"]["parts"][0]["text"], "beta");
+
+        let plain = request_body(&EmbedRequest::default(), "m");
+        assert_eq!(plain["requests"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_parse_and_encode_vectors() {
+        let body = serde_json::json!({
+            "embeddings": [{ "values": [1.0, -0.5] }, { "values": [0.25, 2.0] }]
+        });
+        let vectors = parse_vectors(&body, 2).unwrap();
+        assert!(parse_vectors(&body, 3).is_err());
+        assert!(parse_vectors(&serde_json::json!({}), 1).is_err());
+
+        let floats = encode("m".to_string(), vectors.clone(), Encoding::Float);
+        assert_eq!(floats.dimensions, 2);
+        assert_eq!(floats.embeddings[1].values, Some(vec![0.25, 2.0]));
+
+        let packed = encode("m".to_string(), vectors, Encoding::Base64);
+        // 1.0 and -0.5 as little-endian f32s
+        assert_eq!(packed.embeddings[0].base64.as_deref(), Some("AACAPwAAAL8="));
+        assert_eq!(packed.embeddings[0].values, None);
+    }
+
+    #[test]
+    fn test_validate_and_parse_options() {
+        assert!(validate(&[]).is_err());
+        assert!(validate(&["a".to_string(), " ".to_string()]).is_err());
+        let many = vec!["a".to_string(); MAX_TEXTS + 1];
+        assert!(validate(&many).is_err());
+        assert!(validate(&["a".to_string()]).is_ok());
+
+        assert_eq!(
+            TaskType::parse("semantic-similarity"),
+            Some(TaskType::SemanticSimilarity)
+        );
+        assert_eq!(TaskType::parse("search"), None);
+        assert_eq!(Encoding::parse("BASE64"), Some(Encoding::Base64));
+        assert_eq!(Encoding::parse("f16"), None);
+        assert_eq!(
+            api_error_message(&serde_json::json!({"error": {"message": "API key not valid"}})),
+            Some("API key not valid".to_string())
+        );
+    }
+}
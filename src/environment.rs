@@ -0,0 +1,236 @@
+//! Which of the server's environment variables the Gemini CLI inherits.
+//!
+//! By default the CLI only gets the variables it needs: the system basics (`PATH`,
+//! `HOME`, locale, temp directories and their Windows counterparts), Gemini and
+//! Google Cloud settings, Node.js, proxies and TLS certificates, sandbox runtimes and
+//! OpenTelemetry. Anything else in the server's environment, such as unrelated API
+//! tokens, stays out of reach of the CLI and the tools and extensions it runs.
+//!
+//! GEMINI_ENV_POLICY picks the policy: `allowlist` (the default) passes the variables
+//! above plus those in GEMINI_ENV_ALLOW, `denylist` passes everything except the
+//! variables in GEMINI_ENV_DENY (or, when that is unset, common secret names), and
+//! `inherit` passes everything. Names are matched case-insensitively, and a `*` at
+//! either end of a pattern matches any prefix or suffix. Variables a call sets
+//! explicitly, such as its `env` parameter, are always passed.
+
+use std::ffi::OsString;
+
+pub const ENV_POLICY: &str = "GEMINI_ENV_POLICY";
+pub const ENV_ALLOW: &str = "GEMINI_ENV_ALLOW";
+pub const ENV_DENY: &str = "GEMINI_ENV_DENY";
+
+/// Variables passed under the `allowlist` policy
+const DEFAULT_ALLOW: &[&str] = &[
+    // System
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "SHELL",
+    "LANG",
+    "LANGUAGE",
+    "LC_*",
+    "TZ",
+    "TERM",
+    "COLORTERM",
+    "NO_COLOR",
+    "FORCE_COLOR",
+    "TMPDIR",
+    "TMP",
+    "TEMP",
+    "XDG_*",
+    // Windows
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "USERPROFILE",
+    "USERNAME",
+    "HOMEDRIVE",
+    "HOMEPATH",
+    "PROGRAMDATA",
+    "PROGRAMFILES*",
+    "PROGRAMW6432",
+    "COMMONPROGRAMFILES*",
+    "COMPUTERNAME",
+    "OS",
+    "PROCESSOR_*",
+    "NUMBER_OF_PROCESSORS",
+    // Gemini CLI and Google Cloud credentials
+    "GEMINI_*",
+    "GOOGLE_*",
+    "CLOUDSDK_*",
+    // Node.js runtime
+    "NODE_*",
+    "NPM_CONFIG_*",
+    "NVM_*",
+    // Network
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "NO_PROXY",
+    "ALL_PROXY",
+    "SSL_CERT_FILE",
+    "SSL_CERT_DIR",
+    // Sandboxing
+    "SANDBOX*",
+    "SEATBELT_PROFILE",
+    "DOCKER_*",
+    "PODMAN_*",
+    "CONTAINER_HOST",
+    // Telemetry and debugging
+    "OTEL_*",
+    "DEBUG",
+    "DEBUG_PORT",
+];
+
+/// Variables withheld under the `denylist` policy when GEMINI_ENV_DENY is unset
+const DEFAULT_DENY: &[&str] = &[
+    "*_TOKEN",
+    "*_SECRET",
+    "*_SECRET_KEY",
+    "*_PASSWORD",
+    "*_PASSWD",
+    "AWS_*",
+    "AZURE_*",
+    "OPENAI_API_KEY",
+    "ANTHROPIC_API_KEY",
+    "GITHUB_PAT",
+    "NPM_TOKEN",
+    "DATABASE_URL",
+];
+
+/// How the CLI's environment is derived from the server's
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvPolicy {
+    /// Pass every variable
+    Inherit,
+    /// Pass only variables matching these patterns
+    Allowlist(Vec<String>),
+    /// Pass every variable except those matching these patterns
+    Denylist(Vec<String>),
+}
+
+impl EnvPolicy {
+    /// The policy set with GEMINI_ENV_POLICY, GEMINI_ENV_ALLOW and GEMINI_ENV_DENY.
+    /// Unknown policies fall back to the allowlist with a warning
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+        let policy = var(ENV_POLICY).unwrap_or_default();
+        match policy.trim().to_ascii_lowercase().as_str() {
+            "inherit" | "inherit-all" | "all" => Self::Inherit,
+            "denylist" | "deny" => Self::Denylist(match var(ENV_DENY) {
+                Some(deny) => parse_patterns(&deny),
+                None => DEFAULT_DENY.iter().map(|p| p.to_string()).collect(),
+            }),
+            other => {
+                if !matches!(other, "" | "allowlist" | "allow") {
+                    tracing::warn!(
+                        "Unknown {} '{}' (supported: allowlist, denylist, inherit); using allowlist",
+                        ENV_POLICY,
+                        policy
+                    );
+                }
+                let mut allow: Vec<String> = DEFAULT_ALLOW.iter().map(|p| p.to_string()).collect();
+                allow.extend(
+                    var(ENV_ALLOW)
+                        .as_deref()
+                        .map(parse_patterns)
+                        .unwrap_or_default(),
+                );
+                Self::Allowlist(allow)
+            }
+        }
+    }
+
+    /// Whether the CLI may inherit the variable called `name`
+    pub fn passes(&self, name: &str) -> bool {
+        match self {
+            Self::Inherit => true,
+            Self::Allowlist(patterns) => patterns.iter().any(|p| matches(p, name)),
+            Self::Denylist(patterns) => !patterns.iter().any(|p| matches(p, name)),
+        }
+    }
+
+    /// Names of the variables in `vars` the CLI may not inherit
+    pub fn withheld(&self, vars: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+        vars.into_iter()
+            .filter(|name| !self.passes(&name.to_string_lossy()))
+            .collect()
+    }
+}
+
+/// The server's variables the CLI may not inherit under the configured policy
+pub fn withheld_vars() -> Vec<OsString> {
+    EnvPolicy::from_env().withheld(std::env::vars_os().map(|(name, _)| name))
+}
+
+/// Comma-separated patterns
+fn parse_patterns(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Whether `name` matches `pattern`, ignoring case. A leading or trailing `*` matches
+/// any suffix or prefix
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_ascii_uppercase();
+    let name = name.to_ascii_uppercase();
+    match (pattern.strip_prefix('*'), pattern.strip_suffix('*')) {
+        (Some(suffix), _) => name.ends_with(suffix.trim_end_matches('*')),
+        (None, Some(prefix)) => name.starts_with(prefix),
+        (None, None) => name == pattern,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(vars: &[&str]) -> Vec<OsString> {
+        vars.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn test_allowlist_passes_what_the_cli_needs() {
+        let policy = EnvPolicy::Allowlist(DEFAULT_ALLOW.iter().map(|p| p.to_string()).collect());
+        let withheld = policy.withheld(names(&[
+            "PATH",
+            "Path",
+            "HOME",
+            "LC_ALL",
+            "GEMINI_API_KEY",
+            "GOOGLE_CLOUD_PROJECT",
+            "https_proxy",
+            "NODE_OPTIONS",
+            "GITHUB_TOKEN",
+            "STRIPE_SECRET_KEY",
+        ]));
+        assert_eq!(withheld, names(&["GITHUB_TOKEN", "STRIPE_SECRET_KEY"]));
+    }
+
+    #[test]
+    fn test_denylist_and_inherit() {
+        let policy = EnvPolicy::Denylist(DEFAULT_DENY.iter().map(|p| p.to_string()).collect());
+        assert!(policy.passes("GEMINI_API_KEY"));
+        assert!(policy.passes("MY_SETTING"));
+        assert!(!policy.passes("GITHUB_TOKEN"));
+        assert!(!policy.passes("aws_access_key_id"));
+        assert!(!policy.passes("DB_PASSWORD"));
+        assert!(EnvPolicy::Inherit.passes("GITHUB_TOKEN"));
+    }
+
+    #[test]
+    fn test_pattern_matching() {
+        assert!(matches("LC_*", "LC_CTYPE"));
+        assert!(matches("*_TOKEN", "npm_token"));
+        assert!(matches("DEBUG", "debug"));
+        assert!(!matches("DEBUG", "DEBUGGER"));
+        assert!(!matches("LC_*", "CLC_X"));
+    }
+}
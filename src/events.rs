@@ -0,0 +1,389 @@
+//! Typed model of the Gemini CLI's `--output-format stream-json` events.
+//!
+//! Each line of output is a JSON object whose `type` selects its shape. Types the model
+//! doesn't know parse as [`EventKind::Other`], and fields of an unexpected shape read as
+//! absent, so output from a newer or older CLI degrades gracefully instead of failing
+//! the run. Fields any event may carry, such as `session_id` or the Gemini API's
+//! `usageMetadata` that some CLI versions pass through, are read whatever the type.
+
+use crate::gemini::TokenUsage;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+/// Role of the assistant's own messages
+pub const ROLE_ASSISTANT: &str = "assistant";
+
+/// One line of stream-json output
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Event {
+    /// The `type` as written, including types [`EventKind`] doesn't know
+    pub type_name: String,
+    pub kind: EventKind,
+    pub session_id: Option<String>,
+    pub usage_metadata: Option<UsageMetadata>,
+    pub model_version: Option<String>,
+    pub finish_reason: Option<String>,
+    /// Set on events reporting a failure
+    pub error: Option<ErrorDetail>,
+    /// Description of the failure on `error` events
+    pub message: Option<String>,
+}
+
+impl Event {
+    pub fn from_json(value: &Value) -> Self {
+        let common = Common::deserialize(value).unwrap_or_default();
+        Self {
+            type_name: common.type_name.unwrap_or_default(),
+            kind: EventKind::deserialize(value).unwrap_or(EventKind::Other),
+            session_id: common.session_id.filter(|s| !s.is_empty()),
+            usage_metadata: common.usage_metadata,
+            model_version: common.model_version.filter(|m| !m.is_empty()),
+            finish_reason: common.finish_reason,
+            error: common.error,
+            message: common.message,
+        }
+    }
+
+    /// Whether the event reports a failure: it carries an `error`, or its type names one
+    /// (`error`, `tool_error`, `failure`, ...)
+    pub fn is_failure(&self) -> bool {
+        let type_name = self.type_name.to_lowercase();
+        self.error.is_some() || type_name.contains("fail") || type_name.contains("error")
+    }
+
+    /// What went wrong, for failure events that say
+    pub fn failure_message(&self) -> Option<&str> {
+        match &self.error {
+            Some(ErrorDetail::Described { message }) => Some(message),
+            Some(ErrorDetail::Other(Value::Object(_))) => None,
+            _ => self.message.as_deref(),
+        }
+    }
+
+    /// Token counts the event reports, from usage metadata or a `result` event's stats
+    pub fn usage(&self) -> Option<TokenUsage> {
+        self.usage_metadata
+            .and_then(|meta| meta.token_usage())
+            .or_else(|| match &self.kind {
+                EventKind::Result(ResultEvent {
+                    stats: Some(stats), ..
+                }) => Some(stats.token_usage()),
+                _ => None,
+            })
+    }
+}
+
+/// The type-specific part of an event
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    /// The session started; `session_id` is set
+    Init(InitEvent),
+    Message(MessageEvent),
+    /// The model's reasoning, when the CLI reports it
+    Thought(ThoughtEvent),
+    ToolUse(ToolUseEvent),
+    ToolResult(ToolResultEvent),
+    Error(ErrorEvent),
+    /// The run finished
+    Result(ResultEvent),
+    #[default]
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+pub struct InitEvent {
+    #[serde(default, deserialize_with = "lenient")]
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+pub struct MessageEvent {
+    /// `user` for the prompt, `assistant` for the reply
+    #[serde(default, deserialize_with = "lenient")]
+    pub role: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub content: Option<String>,
+    /// Whether `content` continues the previous message
+    #[serde(default, deserialize_with = "lenient")]
+    pub delta: Option<bool>,
+    /// Set when `content` is the model's reasoning rather than its reply
+    #[serde(default, deserialize_with = "lenient")]
+    pub thought: Option<bool>,
+}
+
+impl MessageEvent {
+    pub fn is_assistant(&self) -> bool {
+        self.role.as_deref() == Some(ROLE_ASSISTANT)
+    }
+
+    pub fn is_thought(&self) -> bool {
+        self.thought == Some(true)
+    }
+}
+
+/// A summary of the model's reasoning: a `subject` line and a `description`, or the
+/// text as `content`
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+pub struct ThoughtEvent {
+    #[serde(default, deserialize_with = "lenient")]
+    pub subject: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub content: Option<String>,
+}
+
+impl ThoughtEvent {
+    /// The thought as one text, `None` when it is empty
+    pub fn text(&self) -> Option<String> {
+        let parts: Vec<&str> = [&self.subject, &self.description, &self.content]
+            .into_iter()
+            .filter_map(|part| part.as_deref().map(str::trim))
+            .filter(|part| !part.is_empty())
+            .collect();
+        (!parts.is_empty()).then(|| parts.join("\n"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+pub struct ToolUseEvent {
+    #[serde(default, deserialize_with = "lenient")]
+    pub tool_name: Option<String>,
+    /// Pairs the call with its `tool_result`
+    #[serde(default, deserialize_with = "lenient")]
+    pub tool_id: Option<String>,
+    #[serde(default)]
+    pub parameters: Option<Value>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub timestamp: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+pub struct ToolResultEvent {
+    #[serde(default, deserialize_with = "lenient")]
+    pub tool_id: Option<String>,
+    /// `success` when the tool worked
+    #[serde(default, deserialize_with = "lenient")]
+    pub status: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub timestamp: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+pub struct ErrorEvent {
+    /// e.g. `warning` or `error`
+    #[serde(default, deserialize_with = "lenient")]
+    pub severity: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+pub struct ResultEvent {
+    /// `success`, or how the run ended otherwise
+    #[serde(default, deserialize_with = "lenient")]
+    pub status: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub stats: Option<Stats>,
+}
+
+/// Token counts in a `result` event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub struct Stats {
+    #[serde(default, deserialize_with = "lenient")]
+    pub input_tokens: Option<u64>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub output_tokens: Option<u64>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub total_tokens: Option<u64>,
+}
+
+impl Stats {
+    pub fn token_usage(&self) -> TokenUsage {
+        let input_tokens = self.input_tokens.unwrap_or(0);
+        let output_tokens = self.output_tokens.unwrap_or(0);
+        TokenUsage {
+            input_tokens,
+            output_tokens,
+            total_tokens: self.total_tokens.unwrap_or(input_tokens + output_tokens),
+        }
+    }
+}
+
+/// A Gemini API `usageMetadata` object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageMetadata {
+    #[serde(default, deserialize_with = "lenient")]
+    pub prompt_token_count: Option<u64>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub candidates_token_count: Option<u64>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub total_token_count: Option<u64>,
+}
+
+impl UsageMetadata {
+    /// The counts, unless the prompt's is missing
+    pub fn token_usage(&self) -> Option<TokenUsage> {
+        let input_tokens = self.prompt_token_count?;
+        let output_tokens = self.candidates_token_count.unwrap_or(0);
+        Some(TokenUsage {
+            input_tokens,
+            output_tokens,
+            total_tokens: self
+                .total_token_count
+                .unwrap_or(input_tokens + output_tokens),
+        })
+    }
+}
+
+/// The `error` field of a failed event
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum ErrorDetail {
+    /// An object with a `message`
+    Described { message: String },
+    /// Anything else, kept as written
+    Other(Value),
+}
+
+impl ErrorDetail {
+    /// The message, or the raw value when there is none
+    pub fn describe(&self) -> String {
+        match self {
+            ErrorDetail::Described { message } => message.clone(),
+            ErrorDetail::Other(value) => value.to_string(),
+        }
+    }
+}
+
+/// Fields read from every event
+#[derive(Debug, Default, Deserialize)]
+struct Common {
+    #[serde(rename = "type", default, deserialize_with = "lenient")]
+    type_name: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    session_id: Option<String>,
+    #[serde(
+        rename = "usageMetadata",
+        alias = "usage_metadata",
+        default,
+        deserialize_with = "lenient"
+    )]
+    usage_metadata: Option<UsageMetadata>,
+    #[serde(
+        rename = "modelVersion",
+        alias = "model_version",
+        default,
+        deserialize_with = "lenient"
+    )]
+    model_version: Option<String>,
+    #[serde(
+        rename = "finish_reason",
+        alias = "finishReason",
+        default,
+        deserialize_with = "lenient"
+    )]
+    finish_reason: Option<String>,
+    #[serde(default)]
+    error: Option<ErrorDetail>,
+    #[serde(default, deserialize_with = "lenient")]
+    message: Option<String>,
+}
+
+/// Read a field as `T`, or as absent when it has another shape, so one odd field
+/// doesn't hide the rest of the event
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let value = Value::deserialize(deserializer)?;
+    Ok(serde_json::from_value(value).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parses_known_event_types() {
+        let init = Event::from_json(&json!({"type": "init", "session_id": "s1", "model": "m"}));
+        assert_eq!(init.session_id.as_deref(), Some("s1"));
+        assert_eq!(
+            init.kind,
+            EventKind::Init(InitEvent {
+                model: Some("m".to_string())
+            })
+        );
+
+        let message = Event::from_json(
+            &json!({"type": "message", "role": "assistant", "content": "Hi", "delta": true}),
+        );
+        assert!(matches!(&message.kind, EventKind::Message(m) if m.is_assistant()));
+
+        let result = Event::from_json(&json!({
+            "type": "result",
+            "status": "success",
+            "stats": {"input_tokens": 10, "output_tokens": 4}
+        }));
+        assert_eq!(result.usage().map(|u| u.total_tokens), Some(14));
+        assert!(!result.is_failure());
+
+        let thought = Event::from_json(&json!({
+            "type": "thought",
+            "subject": "Planning",
+            "description": "Read the tests first"
+        }));
+        assert!(matches!(&thought.kind, EventKind::Thought(t)
+            if t.text().as_deref() == Some("Planning\nRead the tests first")));
+        let thought_part = Event::from_json(
+            &json!({"type": "message", "role": "assistant", "content": "Hmm", "thought": true}),
+        );
+        assert!(matches!(&thought_part.kind, EventKind::Message(m) if m.is_thought()));
+    }
+
+    #[test]
+    fn test_unknown_types_and_odd_fields_degrade() {
+        let unknown = Event::from_json(&json!({"type": "checkpoint", "session_id": "s2"}));
+        assert_eq!(unknown.kind, EventKind::Other);
+        assert_eq!(unknown.type_name, "checkpoint");
+        assert_eq!(unknown.session_id.as_deref(), Some("s2"));
+
+        let odd = Event::from_json(&json!({
+            "type": "message",
+            "role": "assistant",
+            "content": {"parts": []},
+            "session_id": 7,
+            "usageMetadata": {"promptTokenCount": 3, "candidatesTokenCount": 2}
+        }));
+        assert!(matches!(&odd.kind, EventKind::Message(m) if m.content.is_none()));
+        assert_eq!(odd.session_id, None);
+        assert_eq!(odd.usage().map(|u| u.total_tokens), Some(5));
+
+        assert_eq!(Event::from_json(&json!("not an object")), Event::default());
+    }
+
+    #[test]
+    fn test_failure_messages() {
+        let described = Event::from_json(&json!({
+            "type": "tool_result",
+            "status": "error",
+            "error": {"message": "permission denied"}
+        }));
+        assert!(described.is_failure());
+        assert_eq!(described.failure_message(), Some("permission denied"));
+
+        let error = Event::from_json(&json!({"type": "error", "message": "Quota exceeded"}));
+        assert!(error.is_failure());
+        assert_eq!(error.failure_message(), Some("Quota exceeded"));
+
+        let raw = Event::from_json(&json!({"type": "tool_result", "error": "boom"}));
+        assert_eq!(
+            raw.error.as_ref().map(ErrorDetail::describe).as_deref(),
+            Some("\"boom\"")
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Export of recorded conversations as JSONL chat records.
+//!
+//! Each session in the invocation history becomes one line in the common chat
+//! fine-tuning format, `{"messages": [{"role": "user", ...}, {"role": "assistant", ...}]}`,
+//! with a `metadata` object describing where it came from. Calls made without a
+//! session (e.g. `gemini_ask`) become single-turn records. Failed calls are left out,
+//! and every message passes through [`Redactor`] first.
+
+use crate::history::InvocationRecord;
+use crate::redact::Redactor;
+use serde::Serialize;
+
+/// One message of a chat record
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChatMessage {
+    pub role: &'static str,
+    pub content: String,
+}
+
+/// Where a chat record came from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChatMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    pub tool: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// RFC 3339 UTC timestamp of the first exported call
+    pub started_at: String,
+    pub turns: usize,
+    /// Number of secrets or personal details replaced in this record
+    pub redactions: usize,
+}
+
+/// One conversation in chat format
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChatRecord {
+    pub messages: Vec<ChatMessage>,
+    pub metadata: ChatMetadata,
+}
+
+/// Build chat records from invocations, given oldest first, optionally only for one session
+pub fn chat_records(
+    invocations: &[InvocationRecord],
+    session_id: Option<&str>,
+    redactor: &Redactor,
+) -> Vec<ChatRecord> {
+    let mut records: Vec<ChatRecord> = Vec::new();
+    for invocation in invocations {
+        if session_id.is_some() && invocation.session_id.as_deref() != session_id {
+            continue;
+        }
+        let Some(response) = invocation
+            .response
+            .as_deref()
+            .filter(|_| invocation.success)
+        else {
+            continue;
+        };
+
+        let (prompt, prompt_redactions) = redactor.redact(&invocation.prompt);
+        let (response, response_redactions) = redactor.redact(response);
+        let turn = [
+            ChatMessage {
+                role: "user",
+                content: prompt,
+            },
+            ChatMessage {
+                role: "assistant",
+                content: response,
+            },
+        ];
+
+        let existing = invocation.session_id.as_ref().and_then(|id| {
+            records
+                .iter()
+                .position(|r| r.metadata.session_id.as_ref() == Some(id))
+        });
+        match existing {
+            Some(index) => {
+                let record = &mut records[index];
+                record.messages.extend(turn);
+                record.metadata.turns += 1;
+                record.metadata.redactions += prompt_redactions + response_redactions;
+                if invocation.model.is_some() {
+                    record.metadata.model = invocation.model.clone();
+                }
+            }
+            None => records.push(ChatRecord {
+                messages: turn.into(),
+                metadata: ChatMetadata {
+                    session_id: invocation.session_id.clone(),
+                    tool: invocation.tool.clone(),
+                    model: invocation.model.clone(),
+                    started_at: invocation.started_at.clone(),
+                    turns: 1,
+                    redactions: prompt_redactions + response_redactions,
+                },
+            }),
+        }
+    }
+    records
+}
+
+/// Serialize records as JSON Lines
+pub fn to_jsonl(records: &[ChatRecord]) -> String {
+    records
+        .iter()
+        .filter_map(|record| serde_json::to_string(record).ok())
+        .map(|line| line + "\n")
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(
+        id: u64,
+        session_id: Option<&str>,
+        prompt: &str,
+        response: Option<&str>,
+    ) -> InvocationRecord {
+        InvocationRecord {
+            id,
+            tool: "gemini".to_string(),
+            session_id: session_id.map(String::from),
+            model: Some("gemini-2.5-pro".to_string()),
+            prompt_summary: prompt.to_string(),
+            started_at: format!("2024-01-01T00:00:0{}Z", id),
+            duration_ms: 10,
+            success: response.is_some(),
+            error: None,
+            cost_usd: None,
+            prompt: prompt.to_string(),
+            response: response.map(String::from),
+            events: None,
+        }
+    }
+
+    #[test]
+    fn test_groups_sessions_and_skips_failures() {
+        let invocations = [
+            record(1, Some("s1"), "hello", Some("hi")),
+            record(2, None, "one-shot", Some("answer")),
+            record(3, Some("s1"), "broken", None),
+            record(4, Some("s1"), "mail ada@example.com", Some("done")),
+        ];
+        let records = chat_records(&invocations, None, &Redactor::new());
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].metadata.session_id.as_deref(), Some("s1"));
+        assert_eq!(records[0].metadata.turns, 2);
+        assert_eq!(records[0].metadata.redactions, 1);
+        let contents: Vec<&str> = records[0]
+            .messages
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(contents, ["hello", "hi", "mail [REDACTED:email]", "done"]);
+        assert_eq!(records[1].metadata.session_id, None);
+
+        let only_s1 = chat_records(&invocations, Some("s1"), &Redactor::new());
+        assert_eq!(only_s1.len(), 1);
+    }
+
+    #[test]
+    fn test_to_jsonl() {
+        let records = chat_records(
+            &[record(1, Some("s1"), "hello", Some("hi"))],
+            None,
+            &Redactor::new(),
+        );
+        let jsonl = to_jsonl(&records);
+        assert_eq!(jsonl.lines().count(), 1);
+        let value: serde_json::Value = serde_json::from_str(jsonl.trim_end()).unwrap();
+        assert_eq!(value["messages"][0]["role"], "user");
+        assert_eq!(value["messages"][1]["content"], "hi");
+        assert_eq!(value["metadata"]["session_id"], "s1");
+    }
+}
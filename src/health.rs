@@ -0,0 +1,228 @@
+//! Installation check behind the `gemini_health` tool.
+//!
+//! Runs `gemini --version` the same way real calls start the CLI, so a client can
+//! find a missing binary, a broken Node install or an unconfigured account at
+//! startup rather than on its first real prompt. The CLI has no command that reports
+//! authentication status, so [`detect_auth`] only looks for configured credentials;
+//! `auth: "none"` means none were found where the CLI usually looks, not that every
+//! call will fail.
+
+use crate::gemini::{gemini_bin, parse_flag, program_command};
+use crate::messages::catalog;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+/// How long `gemini --version` may take
+pub const VERSION_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Credentials the Gemini CLI would find
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    /// `GEMINI_API_KEY` or `GOOGLE_API_KEY`
+    ApiKey,
+    /// `GOOGLE_GENAI_USE_VERTEXAI` with a project or API key
+    VertexAi,
+    /// Cached Google login in `~/.gemini/oauth_creds.json`
+    Oauth,
+    /// `~/.gemini/.env`, whose contents aren't inspected
+    EnvFile,
+    None,
+}
+
+/// Result of a health check
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    /// Whether `gemini --version` ran and succeeded
+    pub healthy: bool,
+    /// Binary name or path as configured
+    pub bin: String,
+    /// Where the binary was found, when it could be located
+    pub path: Option<String>,
+    pub version: Option<String>,
+    /// Time taken by `gemini --version`
+    pub latency_ms: u64,
+    pub auth: AuthMethod,
+    pub error: Option<String>,
+}
+
+/// Check the binary named by `choice` (or the configured default)
+pub async fn check(choice: Option<&str>, limit: Duration) -> HealthReport {
+    let bin = gemini_bin(choice);
+    let path = locate(&bin);
+    let auth = configured_auth();
+
+    let mut cmd = program_command(&bin);
+    cmd.arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let started = Instant::now();
+    let outcome = tokio::time::timeout(limit, cmd.output()).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let (version, error) = match outcome {
+        Err(_) => (
+            None,
+            Some(format!(
+                "gemini --version did not finish within {}s",
+                limit.as_secs()
+            )),
+        ),
+        Ok(Err(e)) => (None, Some(format!("Failed to run {}: {}", bin, e))),
+        Ok(Ok(output)) if !output.status.success() => (
+            None,
+            Some(format!(
+                "gemini --version failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+        ),
+        Ok(Ok(output)) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            match stdout.lines().map(str::trim).find(|l| !l.is_empty()) {
+                Some(version) => (Some(version.to_string()), None),
+                None => (None, Some("gemini --version printed nothing".to_string())),
+            }
+        }
+    };
+
+    HealthReport {
+        healthy: error.is_none(),
+        bin,
+        path: path.map(|p| p.display().to_string()),
+        version,
+        latency_ms,
+        auth,
+        error,
+    }
+}
+
+/// Credentials the CLI would find in the server's environment and home directory
+pub fn configured_auth() -> AuthMethod {
+    detect_auth(home_dir().as_deref(), |name| std::env::var(name).ok())
+}
+
+/// What to do when the CLI rejects the credentials found as `method`
+pub fn auth_remediation(method: AuthMethod) -> &'static str {
+    let messages = catalog();
+    match method {
+        AuthMethod::ApiKey => messages.auth_remediation_api_key,
+        AuthMethod::VertexAi => messages.auth_remediation_vertex_ai,
+        AuthMethod::Oauth => messages.auth_remediation_oauth,
+        AuthMethod::EnvFile => messages.auth_remediation_env_file,
+        AuthMethod::None => messages.auth_remediation_none,
+    }
+}
+
+/// Credentials found in the environment (read through `var`) or under `home`
+pub fn detect_auth(home: Option<&Path>, var: impl Fn(&str) -> Option<String>) -> AuthMethod {
+    let set = |name: &str| var(name).is_some_and(|v| !v.trim().is_empty());
+    let vertex = var("GOOGLE_GENAI_USE_VERTEXAI").and_then(|v| parse_flag(&v)) == Some(true);
+    if vertex && (set("GOOGLE_CLOUD_PROJECT") || set("GOOGLE_API_KEY")) {
+        return AuthMethod::VertexAi;
+    }
+    if set("GEMINI_API_KEY") || set("GOOGLE_API_KEY") {
+        return AuthMethod::ApiKey;
+    }
+    let Some(dir) = home.map(|h| h.join(".gemini")) else {
+        return AuthMethod::None;
+    };
+    if dir.join("oauth_creds.json").is_file() {
+        AuthMethod::Oauth
+    } else if dir.join(".env").is_file() {
+        AuthMethod::EnvFile
+    } else {
+        AuthMethod::None
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Full path of `program`, looked up on PATH when it's a bare name
+pub(crate) fn locate(program: &str) -> Option<PathBuf> {
+    #[cfg(windows)]
+    return crate::gemini::resolve_on_path(program);
+
+    #[cfg(not(windows))]
+    {
+        let path = Path::new(program);
+        if path.components().count() > 1 {
+            return is_executable(path).then(|| path.to_path_buf());
+        }
+        std::env::split_paths(&std::env::var_os("PATH")?)
+            .map(|dir| dir.join(program))
+            .find(|candidate| is_executable(candidate))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let map: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| map.get(name).cloned()
+    }
+
+    #[test]
+    fn test_detect_auth() {
+        assert_eq!(
+            detect_auth(None, env(&[("GEMINI_API_KEY", "k")])),
+            AuthMethod::ApiKey
+        );
+        assert_eq!(
+            detect_auth(
+                None,
+                env(&[
+                    ("GOOGLE_GENAI_USE_VERTEXAI", "true"),
+                    ("GOOGLE_CLOUD_PROJECT", "p")
+                ])
+            ),
+            AuthMethod::VertexAi
+        );
+        assert_eq!(
+            detect_auth(None, env(&[("GEMINI_API_KEY", " ")])),
+            AuthMethod::None
+        );
+
+        let home = std::env::temp_dir().join(format!("gemini-mcp-health-{}", std::process::id()));
+        std::fs::create_dir_all(home.join(".gemini")).unwrap();
+        std::fs::write(home.join(".gemini/oauth_creds.json"), "{}").unwrap();
+        let auth = detect_auth(Some(&home), env(&[]));
+        let _ = std::fs::remove_dir_all(&home);
+        assert_eq!(auth, AuthMethod::Oauth);
+    }
+
+    #[tokio::test]
+    async fn test_missing_binary_is_unhealthy() {
+        let report = check(Some("/nonexistent/gemini-mcp-test-bin"), VERSION_TIMEOUT).await;
+        assert!(!report.healthy);
+        assert!(report.path.is_none());
+        assert!(report.version.is_none());
+        assert!(report.error.is_some());
+    }
+}
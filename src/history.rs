@@ -0,0 +1,436 @@
+//! In-memory record of recent tool invocations.
+//!
+//! Every call through the server is appended here so clients can browse what the
+//! server has done (via MCP resources) without dedicated tools. The log is bounded;
+//! the oldest records are dropped first.
+//!
+//! Each record keeps the call's full prompt and reply, and its event log when the
+//! caller asked for `return_all_messages`, so a session's transcript can be read
+//! back later and pulled into another prompt without running Gemini again.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAX_HISTORY_RECORDS: usize = 200; // Maximum invocations kept in memory
+const PROMPT_SUMMARY_CHARS: usize = 80; // Prompt prefix kept for display
+
+/// One completed tool invocation
+#[derive(Debug, Clone, Serialize)]
+pub struct InvocationRecord {
+    pub id: u64,
+    pub tool: String,
+    pub session_id: Option<String>,
+    pub model: Option<String>,
+    pub prompt_summary: String,
+    /// RFC 3339 UTC timestamp of when the call started
+    pub started_at: String,
+    pub duration_ms: u64,
+    pub success: bool,
+    pub error: Option<String>,
+    /// Estimated USD cost, when usage was reported and the model is priced
+    pub cost_usd: Option<f64>,
+    /// Full prompt, kept for exports but left out of resources
+    #[serde(skip)]
+    pub prompt: String,
+    /// Reply of a successful call, kept for exports but left out of resources
+    #[serde(skip)]
+    pub response: Option<String>,
+    /// Event log of a call that asked for all messages, for transcripts
+    #[serde(skip)]
+    pub events: Option<Vec<Value>>,
+}
+
+/// Aggregated view of all recorded invocations sharing a SESSION_ID
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub first_seen: String,
+    pub last_seen: String,
+    pub calls: usize,
+    pub last_prompt_summary: String,
+    pub invocation_ids: Vec<u64>,
+    /// Sum of the estimated costs of the session's priced calls
+    pub cost_usd: Option<f64>,
+}
+
+/// A session's recorded calls in order, with their full prompts and replies
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionTranscript {
+    #[serde(flatten)]
+    pub summary: SessionSummary,
+    pub turns: Vec<TranscriptTurn>,
+}
+
+/// One call in a [`SessionTranscript`]
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptTurn {
+    pub invocation_id: u64,
+    pub tool: String,
+    pub started_at: String,
+    pub success: bool,
+    pub prompt: String,
+    pub agent_messages: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The call's full event log, when it was made with `return_all_messages`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<Value>>,
+}
+
+/// Fields describing an invocation, before it's assigned an id
+#[derive(Debug, Clone)]
+pub struct NewInvocation {
+    pub tool: String,
+    pub session_id: Option<String>,
+    pub model: Option<String>,
+    pub prompt: String,
+    pub started_at: SystemTime,
+    pub duration: Duration,
+    pub success: bool,
+    pub error: Option<String>,
+    pub cost_usd: Option<f64>,
+    pub response: Option<String>,
+    pub events: Option<Vec<Value>>,
+}
+
+#[derive(Debug, Default)]
+struct HistoryInner {
+    records: VecDeque<InvocationRecord>,
+    next_id: u64,
+}
+
+/// Bounded, thread-safe invocation log
+#[derive(Debug, Default)]
+pub struct History {
+    inner: Mutex<HistoryInner>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an invocation and return its id
+    pub fn record(&self, invocation: NewInvocation) -> u64 {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.records.push_back(InvocationRecord {
+            id,
+            tool: invocation.tool,
+            session_id: invocation.session_id.filter(|s| !s.is_empty()),
+            model: invocation.model,
+            prompt_summary: summarize_prompt(&invocation.prompt),
+            started_at: format_timestamp(invocation.started_at),
+            duration_ms: invocation.duration.as_millis() as u64,
+            success: invocation.success,
+            error: invocation.error,
+            cost_usd: invocation.cost_usd,
+            prompt: invocation.prompt,
+            response: invocation.response,
+            events: invocation.events,
+        });
+        while inner.records.len() > MAX_HISTORY_RECORDS {
+            inner.records.pop_front();
+        }
+        id
+    }
+
+    /// All records, newest first
+    pub fn invocations(&self) -> Vec<InvocationRecord> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.records.iter().rev().cloned().collect()
+    }
+
+    pub fn invocation(&self, id: u64) -> Option<InvocationRecord> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.records.iter().find(|r| r.id == id).cloned()
+    }
+
+    /// Sessions seen in the log, most recently used first
+    pub fn sessions(&self) -> Vec<SessionSummary> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let mut sessions: Vec<SessionSummary> = Vec::new();
+        for record in &inner.records {
+            let Some(session_id) = &record.session_id else {
+                continue;
+            };
+            match sessions.iter_mut().find(|s| &s.session_id == session_id) {
+                Some(summary) => {
+                    summary.last_seen = record.started_at.clone();
+                    summary.calls += 1;
+                    summary.last_prompt_summary = record.prompt_summary.clone();
+                    summary.invocation_ids.push(record.id);
+                    if let Some(cost) = record.cost_usd {
+                        *summary.cost_usd.get_or_insert(0.0) += cost;
+                    }
+                }
+                None => sessions.push(SessionSummary {
+                    session_id: session_id.clone(),
+                    first_seen: record.started_at.clone(),
+                    last_seen: record.started_at.clone(),
+                    calls: 1,
+                    last_prompt_summary: record.prompt_summary.clone(),
+                    invocation_ids: vec![record.id],
+                    cost_usd: record.cost_usd,
+                }),
+            }
+        }
+        // RFC 3339 UTC timestamps sort lexicographically
+        sessions.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+        sessions
+    }
+
+    pub fn session(&self, session_id: &str) -> Option<SessionSummary> {
+        self.sessions()
+            .into_iter()
+            .find(|s| s.session_id == session_id)
+    }
+
+    /// The recorded calls of a session, oldest first
+    pub fn transcript(&self, session_id: &str) -> Option<SessionTranscript> {
+        let summary = self.session(session_id)?;
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let turns = inner
+            .records
+            .iter()
+            .filter(|r| r.session_id.as_deref() == Some(session_id))
+            .map(|r| TranscriptTurn {
+                invocation_id: r.id,
+                tool: r.tool.clone(),
+                started_at: r.started_at.clone(),
+                success: r.success,
+                prompt: r.prompt.clone(),
+                agent_messages: r.response.clone(),
+                error: r.error.clone(),
+                events: r.events.clone(),
+            })
+            .collect();
+        Some(SessionTranscript { summary, turns })
+    }
+}
+
+/// First line of the prompt, shortened for display
+pub(crate) fn summarize_prompt(prompt: &str) -> String {
+    let first_line = prompt.trim().lines().next().unwrap_or("");
+    if first_line.chars().count() <= PROMPT_SUMMARY_CHARS {
+        return first_line.to_string();
+    }
+    let truncated: String = first_line.chars().take(PROMPT_SUMMARY_CHARS).collect();
+    format!("{}...", truncated)
+}
+
+/// Format a timestamp as RFC 3339 UTC with second precision
+pub fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Milliseconds since the Unix epoch of an RFC 3339 timestamp such as
+/// `2025-06-01T12:00:00.250Z` or `2025-06-01T14:00:00+02:00`
+pub(crate) fn parse_timestamp_ms(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let (date, time) = text.split_once(['T', 't', ' '])?;
+    let mut date = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let (clock, offset_secs) = match time.find(['Z', 'z', '+', '-']) {
+        Some(i) if matches!(&time[i..], "Z" | "z") => (&time[..i], 0),
+        Some(i) => {
+            let sign = if time[i..].starts_with('-') { -1 } else { 1 };
+            let (hours, minutes) = time[i + 1..].split_once(':')?;
+            let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+            (&time[..i], sign * offset)
+        }
+        None => (time, 0),
+    };
+    let (hms, fraction) = clock.split_once('.').unwrap_or((clock, ""));
+    let mut hms = hms.splitn(3, ':').map(|p| p.parse::<i64>().ok());
+    let (hour, minute, second) = (hms.next()??, hms.next()??, hms.next()??);
+    let millis = format!("{:0<3}", fraction)
+        .get(..3)
+        .and_then(|m| m.parse::<i64>().ok())?;
+
+    let secs = days_from_civil(year, month as u32, day as u32) * 86_400
+        + hour * 3600
+        + minute * 60
+        + second
+        - offset_secs;
+    Some(secs * 1000 + millis)
+}
+
+/// Convert a (year, month, day) civil date to days since 1970-01-01
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    // Howard Hinnant's days-from-civil algorithm
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Convert days since 1970-01-01 to a (year, month, day) civil date
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's days-to-civil algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invocation(session_id: Option<&str>, prompt: &str, secs: u64) -> NewInvocation {
+        NewInvocation {
+            tool: "gemini".to_string(),
+            session_id: session_id.map(String::from),
+            model: None,
+            prompt: prompt.to_string(),
+            started_at: UNIX_EPOCH + Duration::from_secs(secs),
+            duration: Duration::from_millis(1500),
+            success: true,
+            error: None,
+            cost_usd: None,
+            response: None,
+            events: None,
+        }
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(1_709_210_096)),
+            "2024-02-29T12:34:56Z"
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamp_ms() {
+        assert_eq!(parse_timestamp_ms("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_timestamp_ms("2024-02-29T12:34:56.25Z"),
+            Some(1_709_210_096_250)
+        );
+        assert_eq!(
+            parse_timestamp_ms("2024-02-29T14:34:56+02:00"),
+            Some(1_709_210_096_000)
+        );
+        assert_eq!(parse_timestamp_ms("yesterday"), None);
+        assert_eq!(parse_timestamp_ms("2024-13-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn test_summarize_prompt() {
+        assert_eq!(summarize_prompt("  short\nsecond line"), "short");
+        let long = "x".repeat(100);
+        assert_eq!(summarize_prompt(&long), format!("{}...", "x".repeat(80)));
+    }
+
+    #[test]
+    fn test_history_groups_sessions() {
+        let history = History::new();
+        history.record(invocation(Some("s1"), "first", 100));
+        history.record(invocation(None, "one-shot", 200));
+        history.record(invocation(Some("s2"), "other", 300));
+        let last = history.record(invocation(Some("s1"), "follow up", 400));
+
+        let sessions = history.sessions();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].session_id, "s1");
+        assert_eq!(sessions[0].calls, 2);
+        assert_eq!(sessions[0].last_prompt_summary, "follow up");
+        assert_eq!(sessions[1].session_id, "s2");
+        assert!(sessions[0].cost_usd.is_none());
+
+        assert_eq!(history.invocations().len(), 4);
+        assert_eq!(history.invocations()[0].id, last);
+        assert_eq!(
+            history.invocation(last).unwrap().prompt_summary,
+            "follow up"
+        );
+    }
+
+    #[test]
+    fn test_session_cost_sums_priced_calls() {
+        let history = History::new();
+        history.record(NewInvocation {
+            cost_usd: Some(0.25),
+            ..invocation(Some("s1"), "first", 100)
+        });
+        history.record(invocation(Some("s1"), "unpriced", 200));
+        history.record(NewInvocation {
+            cost_usd: Some(0.5),
+            ..invocation(Some("s1"), "third", 300)
+        });
+
+        assert_eq!(history.session("s1").unwrap().cost_usd, Some(0.75));
+    }
+
+    #[test]
+    fn test_transcript_lists_session_turns_in_order() {
+        let history = History::new();
+        history.record(NewInvocation {
+            response: Some("Hi".to_string()),
+            ..invocation(Some("s1"), "hello", 100)
+        });
+        history.record(invocation(Some("s2"), "elsewhere", 200));
+        history.record(NewInvocation {
+            response: Some("Done".to_string()),
+            events: Some(vec![serde_json::json!({"type": "result"})]),
+            ..invocation(Some("s1"), "now the rest", 300)
+        });
+
+        let transcript = history.transcript("s1").unwrap();
+        assert_eq!(transcript.summary.calls, 2);
+        let prompts: Vec<_> = transcript.turns.iter().map(|t| t.prompt.as_str()).collect();
+        assert_eq!(prompts, ["hello", "now the rest"]);
+        assert_eq!(transcript.turns[1].agent_messages.as_deref(), Some("Done"));
+
+        let json = serde_json::to_value(&transcript).unwrap();
+        assert_eq!(json["session_id"], "s1");
+        assert!(json["turns"][0].get("events").is_none());
+        assert_eq!(json["turns"][1]["events"][0]["type"], "result");
+        assert!(history.transcript("missing").is_none());
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let history = History::new();
+        for i in 0..(MAX_HISTORY_RECORDS as u64 + 5) {
+            history.record(invocation(None, "p", i));
+        }
+        let records = history.invocations();
+        assert_eq!(records.len(), MAX_HISTORY_RECORDS);
+        assert!(history.invocation(1).is_none());
+    }
+}
@@ -0,0 +1,286 @@
+//! Image inputs for prompts.
+//!
+//! The Gemini CLI sends image files named with its `@path` file-reference syntax to the
+//! model as inline data. Images inside the working directory are referenced where they
+//! are. Images given as base64 `data:` URIs, and image files elsewhere in
+//! `GEMINI_INCLUDE_ROOT`, are written to a temporary directory that the call adds to its
+//! include directories, and which is removed when the [`Images`] are dropped. Image
+//! files outside both are refused, as attachments are.
+
+use crate::attach::{file_reference, is_plain_relative, is_within};
+use crate::messages::{catalog, render};
+use base64::Engine;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Most images one call may attach
+pub const MAX_IMAGES: usize = 10;
+/// Largest image that may be attached; the Gemini API caps inline data per request
+pub const MAX_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+
+/// Image formats the Gemini API accepts, as MIME type and file extensions
+const IMAGE_TYPES: &[(&str, &[&str])] = &[
+    ("image/png", &["png"]),
+    ("image/jpeg", &["jpg", "jpeg"]),
+    ("image/webp", &["webp"]),
+    ("image/heic", &["heic"]),
+    ("image/heif", &["heif"]),
+];
+
+static NEXT_STAGING_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Images for one call: the paths to reference, and the temporary directory holding
+/// the staged ones
+#[derive(Debug, Default)]
+pub struct Images {
+    /// Relative to the working directory for images in it, absolute for staged ones
+    references: Vec<PathBuf>,
+    staging: Option<PathBuf>,
+}
+
+impl Images {
+    /// Directory the CLI must be allowed to read for the staged images to be found
+    pub fn include_directory(&self) -> Option<&Path> {
+        self.staging.as_deref()
+    }
+
+    /// `prompt` followed by references to the images
+    pub fn append_to_prompt(&self, prompt: &str) -> String {
+        if self.references.is_empty() {
+            return prompt.to_string();
+        }
+        let mut prompt = format!("{}\n\nAttached images:", prompt);
+        for path in &self.references {
+            prompt.push_str(&format!("\n{}", file_reference(path)));
+        }
+        prompt
+    }
+
+    /// Write `bytes` to the staging directory, creating it on first use
+    fn stage(&mut self, bytes: &[u8], extension: &str) -> std::io::Result<PathBuf> {
+        let dir = match &self.staging {
+            Some(dir) => dir.clone(),
+            None => {
+                let dir = std::env::temp_dir().join(format!(
+                    "gemini-mcp-images-{}-{}",
+                    std::process::id(),
+                    NEXT_STAGING_ID.fetch_add(1, Ordering::Relaxed)
+                ));
+                std::fs::create_dir_all(&dir)?;
+                let dir = dir.canonicalize()?;
+                self.staging = Some(dir.clone());
+                dir
+            }
+        };
+        let path = dir.join(format!("image-{}.{}", self.references.len() + 1, extension));
+        std::fs::write(&path, bytes)?;
+        Ok(path)
+    }
+}
+
+impl Drop for Images {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.staging {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// Check the images in `specs`, which are file paths (relative to `base` unless
+/// absolute, and inside `base` or `root`) or base64 `data:` URIs, staging the ones the
+/// CLI can't reference in place
+pub fn resolve(specs: &[String], base: &Path, root: &Path) -> Result<Images, String> {
+    let specs: Vec<&str> = specs
+        .iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if specs.len() > MAX_IMAGES {
+        return Err(render(catalog().too_many_images, &[&MAX_IMAGES]));
+    }
+    let base = base.canonicalize().unwrap_or_else(|_| base.to_path_buf());
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+
+    let mut images = Images::default();
+    for (index, spec) in specs.into_iter().enumerate() {
+        let reference = if spec.starts_with("data:") {
+            let name = format!("images[{}]", index);
+            let (extension, bytes) = decode_data_uri(spec)
+                .ok_or_else(|| render(catalog().image_data_invalid, &[&name]))?;
+            check_size(&name, bytes.len() as u64)?;
+            images
+                .stage(&bytes, extension)
+                .map_err(|e| render(catalog().image_stage_failed, &[&name, &e]))?
+        } else {
+            let path = base
+                .join(spec)
+                .canonicalize()
+                .ok()
+                .filter(|p| p.is_file())
+                .ok_or_else(|| render(catalog().image_not_found, &[&spec]))?;
+            if !is_within(&path, &[&base, &root]) {
+                return Err(render(catalog().path_outside_workspace, &[&spec]));
+            }
+            let extension = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(str::to_ascii_lowercase)
+                .filter(|e| {
+                    IMAGE_TYPES
+                        .iter()
+                        .any(|(_, exts)| exts.contains(&e.as_str()))
+                })
+                .ok_or_else(|| render(catalog().image_type_unsupported, &[&spec]))?;
+            check_size(spec, std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0))?;
+            match path.strip_prefix(&base) {
+                Ok(relative) if is_plain_relative(relative) => relative.to_path_buf(),
+                _ => {
+                    let bytes = std::fs::read(&path)
+                        .map_err(|_| render(catalog().image_not_found, &[&spec]))?;
+                    images
+                        .stage(&bytes, &extension)
+                        .map_err(|e| render(catalog().image_stage_failed, &[&spec, &e]))?
+                }
+            }
+        };
+        images.references.push(reference);
+    }
+    Ok(images)
+}
+
+fn check_size(name: &str, size: u64) -> Result<(), String> {
+    if size > MAX_IMAGE_BYTES {
+        return Err(render(
+            catalog().image_too_large,
+            &[&name, &size, &MAX_IMAGE_BYTES],
+        ));
+    }
+    Ok(())
+}
+
+/// The file extension and decoded bytes of a `data:<mime>;base64,<payload>` URI with
+/// a supported image type
+fn decode_data_uri(uri: &str) -> Option<(&'static str, Vec<u8>)> {
+    let (header, payload) = uri.strip_prefix("data:")?.split_once(',')?;
+    let mime = header.strip_suffix(";base64")?.trim().to_ascii_lowercase();
+    let extension = IMAGE_TYPES
+        .iter()
+        .find(|(m, _)| *m == mime)
+        .map(|(_, exts)| exts[0])?;
+    let payload: String = payload.chars().filter(|c| !c.is_whitespace()).collect();
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(payload)
+        .ok()?;
+    Some((extension, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "gemini-mcp-images-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(dir.join("shots")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_workspace_images_are_referenced_in_place() {
+        let dir = temp_dir("refs");
+        std::fs::write(dir.join("shots/login page.PNG"), b"\x89PNG").unwrap();
+
+        let images = resolve(&["shots/login page.PNG".to_string()], &dir, &dir);
+        let _ = std::fs::remove_dir_all(&dir);
+        let images = images.unwrap();
+
+        assert!(images.include_directory().is_none());
+        assert_eq!(
+            images.append_to_prompt("What is wrong here?"),
+            "What is wrong here?\n\nAttached images:\n@shots/login\\ page.PNG"
+        );
+    }
+
+    #[test]
+    fn test_data_uris_are_staged_and_removed() {
+        let dir = temp_dir("staged");
+        let images = resolve(
+            &["data:image/png;base64,iVBO\nRw0K".to_string()],
+            &dir,
+            &dir,
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+        let images = images.unwrap();
+
+        let staging = images.include_directory().unwrap().to_path_buf();
+        let staged = staging.join("image-1.png");
+        assert_eq!(std::fs::read(&staged).unwrap(), b"\x89PNG\r\n");
+        assert!(images
+            .append_to_prompt("Describe")
+            .ends_with("/image-1.png"));
+
+        drop(images);
+        assert!(!staging.exists());
+    }
+
+    #[test]
+    fn test_images_elsewhere_in_the_include_root_are_staged() {
+        let root = temp_dir("root");
+        std::fs::write(root.join("diagram.png"), b"\x89PNG").unwrap();
+
+        let images = resolve(&["../diagram.png".to_string()], &root.join("shots"), &root);
+        let _ = std::fs::remove_dir_all(&root);
+        let images = images.unwrap();
+
+        let staged = images.include_directory().unwrap().join("image-1.png");
+        assert_eq!(std::fs::read(staged).unwrap(), b"\x89PNG");
+    }
+
+    #[test]
+    fn test_rejects_images_outside_the_workspace() {
+        let dir = temp_dir("jail");
+        let workspace = dir.join("shots");
+        std::fs::write(dir.join("secret.png"), b"\x89PNG").unwrap();
+
+        let parent = resolve(&["../secret.png".to_string()], &workspace, &workspace);
+        let absolute = resolve(&["/etc/passwd".to_string()], &workspace, &workspace);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let error = parent.unwrap_err();
+        assert!(error.contains("outside"), "error was: {}", error);
+        assert!(error.contains("../secret.png"), "error was: {}", error);
+        assert!(absolute.is_err());
+    }
+
+    #[test]
+    fn test_rejects_unsupported_images() {
+        let dir = temp_dir("reject");
+        std::fs::write(dir.join("notes.txt"), "hi").unwrap();
+
+        let results = [
+            resolve(&["notes.txt".to_string()], &dir, &dir),
+            resolve(&["missing.png".to_string()], &dir, &dir),
+            resolve(
+                &["data:image/svg+xml;base64,PHN2Zz4=".to_string()],
+                &dir,
+                &dir,
+            ),
+            resolve(
+                &["data:image/png;base64,not base64!".to_string()],
+                &dir,
+                &dir,
+            ),
+            resolve(
+                &vec!["data:image/png;base64,AA==".to_string(); MAX_IMAGES + 1],
+                &dir,
+                &dir,
+            ),
+        ];
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(results.iter().all(Result::is_err));
+    }
+}
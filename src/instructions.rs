@@ -0,0 +1,377 @@
+//! GEMINI.md discovery.
+//!
+//! Instructions are layered like the Gemini CLI's own context files: starting from
+//! the working directory, every directory up to the repository root (the nearest
+//! ancestor containing `.git`) may hold a GEMINI.md, and all of them are used, the
+//! root's first so that deeper, more specific files come last. Outside a repository
+//! only the working directory's file is read.
+//!
+//! A line of the form `@include <path>` is replaced by the content of that file,
+//! relative to the including file. Includes may nest up to [`MAX_INCLUDE_DEPTH`]
+//! levels and must stay inside the root; cycles and missing files are skipped with a
+//! warning. Files over [`MAX_FILE_BYTES`] are handled by an [`OversizeStrategy`].
+
+use crate::config;
+use crate::stream::decode_text;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+pub const GEMINI_MD_FILE: &str = "GEMINI.md";
+/// Largest instruction file that is used as is, per file
+pub const MAX_FILE_BYTES: u64 = 100 * 1024;
+pub const MAX_INCLUDE_DEPTH: usize = 5;
+const INCLUDE_DIRECTIVE: &str = "@include ";
+pub(crate) const ENV_OVERSIZE: &str = "GEMINI_INSTRUCTIONS_OVERSIZE";
+
+/// What to do with an instruction file over [`MAX_FILE_BYTES`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OversizeStrategy {
+    /// Leave the file out, with a warning in the server log
+    #[default]
+    Skip,
+    /// Refuse the call
+    Fail,
+    /// Keep the lines that fit, followed by a marker saying how much was cut
+    Truncate,
+    /// Use a summary written by a quick Gemini call, cached until the file changes
+    Summarize,
+}
+
+impl OversizeStrategy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "skip" => Some(Self::Skip),
+            "fail" => Some(Self::Fail),
+            "truncate" => Some(Self::Truncate),
+            "summarize" => Some(Self::Summarize),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Skip => "skip",
+            Self::Fail => "fail",
+            Self::Truncate => "truncate",
+            Self::Summarize => "summarize",
+        }
+    }
+}
+
+/// Strategy for calls that don't choose one, from GEMINI_INSTRUCTIONS_OVERSIZE
+pub(crate) fn default_oversize() -> OversizeStrategy {
+    config::var(ENV_OVERSIZE)
+        .ok()
+        .and_then(|v| OversizeStrategy::parse(&v))
+        .unwrap_or_default()
+}
+
+/// Instructions assembled from one or more files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Instructions {
+    pub content: String,
+    /// Every file that contributed, in the order it was read
+    pub files: Vec<PathBuf>,
+    /// Files over [`MAX_FILE_BYTES`] that were left out: all of them with `skip` and
+    /// `fail`, and those with no cached summary yet with `summarize`
+    pub oversized: Vec<PathBuf>,
+}
+
+/// Load the layered GEMINI.md files that apply to `dir`, or `None` when there are none
+pub fn load(dir: &Path, oversize: OversizeStrategy) -> Option<Instructions> {
+    let dir = dir.canonicalize().ok()?;
+    let root = dir
+        .ancestors()
+        .find(|a| a.join(".git").exists())
+        .unwrap_or(&dir)
+        .to_path_buf();
+
+    let mut dirs: Vec<&Path> = dir
+        .ancestors()
+        .take_while(|a| a.starts_with(&root))
+        .collect();
+    dirs.reverse();
+
+    let mut loader = Loader {
+        root,
+        oversize,
+        instructions: Instructions::default(),
+        stack: HashSet::new(),
+    };
+    for dir in dirs {
+        let path = dir.join(GEMINI_MD_FILE);
+        if !path.is_file() {
+            continue;
+        }
+        if let Some(content) = loader.read(&path, 0) {
+            let content = content.trim();
+            if content.is_empty() {
+                continue;
+            }
+            if !loader.instructions.content.is_empty() {
+                loader.instructions.content.push_str("\n\n");
+            }
+            loader.instructions.content.push_str(content);
+        }
+    }
+    Some(loader.instructions).filter(|i| !i.content.is_empty() || !i.oversized.is_empty())
+}
+
+/// Summaries of oversized files, by path, size and modification time
+type SummaryKey = (PathBuf, u64, Option<SystemTime>);
+
+fn summaries() -> &'static Mutex<HashMap<SummaryKey, String>> {
+    static SUMMARIES: OnceLock<Mutex<HashMap<SummaryKey, String>>> = OnceLock::new();
+    SUMMARIES.get_or_init(Default::default)
+}
+
+fn summary_key(path: &Path) -> Option<SummaryKey> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((path.to_path_buf(), meta.len(), meta.modified().ok()))
+}
+
+/// Remember `summary` for the current version of the oversized file at `path`
+pub fn cache_summary(path: &Path, summary: &str) {
+    if let Some(key) = summary_key(path) {
+        let mut summaries = summaries().lock().unwrap_or_else(|e| e.into_inner());
+        summaries.insert(key, truncate(summary, MAX_FILE_BYTES as usize, path));
+    }
+}
+
+fn cached_summary(path: &Path) -> Option<String> {
+    let key = summary_key(path)?;
+    let summaries = summaries().lock().unwrap_or_else(|e| e.into_inner());
+    summaries.get(&key).cloned()
+}
+
+/// The whole lines of `text` that fit in `limit` bytes, followed by a marker
+/// saying how much of `path` was cut, or `text` itself when it fits
+fn truncate(text: &str, limit: usize, path: &Path) -> String {
+    if text.len() <= limit {
+        return text.to_string();
+    }
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = text[..end].rfind('\n').map_or(end, |i| i + 1);
+    format!(
+        "{}\n[... {} truncated: kept {} of {} bytes ...]\n",
+        text[..end].trim_end(),
+        path.display(),
+        end,
+        text.len()
+    )
+}
+
+struct Loader {
+    root: PathBuf,
+    oversize: OversizeStrategy,
+    instructions: Instructions,
+    /// Files currently being expanded, to break include cycles
+    stack: HashSet<PathBuf>,
+}
+
+impl Loader {
+    /// Content of `path` with its includes expanded
+    fn read(&mut self, path: &Path, depth: usize) -> Option<String> {
+        let path = match path.canonicalize() {
+            Ok(path) if path.starts_with(&self.root) => path,
+            Ok(path) => {
+                tracing::warn!(
+                    "Skipping {}: outside {}",
+                    path.display(),
+                    self.root.display()
+                );
+                return None;
+            }
+            Err(e) => {
+                tracing::warn!("Skipping instructions {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        if self.stack.contains(&path) {
+            tracing::warn!("Skipping {}: included in a cycle", path.display());
+            return None;
+        }
+        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let text = if size <= MAX_FILE_BYTES {
+            decode_text(&std::fs::read(&path).ok()?)
+        } else {
+            match self.oversize {
+                OversizeStrategy::Truncate => truncate(
+                    &decode_text(&std::fs::read(&path).ok()?),
+                    MAX_FILE_BYTES as usize,
+                    &path,
+                ),
+                // The summary replaces the file, its @include lines included
+                OversizeStrategy::Summarize => match cached_summary(&path) {
+                    Some(summary) => {
+                        self.instructions.files.push(path);
+                        return Some(summary);
+                    }
+                    None => return self.leave_out(path, size),
+                },
+                OversizeStrategy::Skip | OversizeStrategy::Fail => {
+                    return self.leave_out(path, size)
+                }
+            }
+        };
+        self.instructions.files.push(path.clone());
+        self.stack.insert(path.clone());
+
+        let base = path.parent().unwrap_or(&self.root).to_path_buf();
+        let mut out = String::with_capacity(text.len());
+        for line in text.lines() {
+            match line.trim().strip_prefix(INCLUDE_DIRECTIVE) {
+                Some(target) if depth < MAX_INCLUDE_DEPTH => {
+                    if let Some(included) = self.read(&base.join(target.trim()), depth + 1) {
+                        out.push_str(included.trim_end());
+                        out.push('\n');
+                    }
+                }
+                Some(_) => {
+                    tracing::warn!(
+                        "Skipping include in {}: nested more than {} levels",
+                        path.display(),
+                        MAX_INCLUDE_DEPTH
+                    );
+                }
+                None => {
+                    out.push_str(line);
+                    out.push('\n');
+                }
+            }
+        }
+        self.stack.remove(&path);
+        Some(out)
+    }
+
+    /// Record that the oversized file at `path` is left out
+    fn leave_out(&mut self, path: PathBuf, size: u64) -> Option<String> {
+        tracing::warn!(
+            "Skipping {}: {} bytes exceeds the {} byte limit",
+            path.display(),
+            size,
+            MAX_FILE_BYTES
+        );
+        self.instructions.oversized.push(path);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "gemini-mcp-instructions-{}-{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::create_dir_all(dir.join("crates/app")).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn test_layers_parent_and_child_files() {
+        let root = repo("layers");
+        std::fs::write(root.join(GEMINI_MD_FILE), "Root rules.\n").unwrap();
+        std::fs::write(root.join("crates/app").join(GEMINI_MD_FILE), "App rules.\n").unwrap();
+
+        let loaded = load(&root.join("crates/app"), OversizeStrategy::Skip);
+        let from_root = load(&root, OversizeStrategy::Skip);
+        let _ = std::fs::remove_dir_all(&root);
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.content, "Root rules.\n\nApp rules.");
+        assert_eq!(
+            loaded.files,
+            [
+                root.join(GEMINI_MD_FILE),
+                root.join("crates/app").join(GEMINI_MD_FILE)
+            ]
+        );
+        assert_eq!(from_root.unwrap().content, "Root rules.");
+    }
+
+    #[test]
+    fn test_expands_includes_and_breaks_cycles() {
+        let root = repo("includes");
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::write(
+            root.join(GEMINI_MD_FILE),
+            "Intro.\n@include docs/style.md\nOutro.\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("docs/style.md"),
+            "Use tabs.\n@include ../GEMINI.md\n@include missing.md\n",
+        )
+        .unwrap();
+
+        let loaded = load(&root, OversizeStrategy::Skip);
+        let _ = std::fs::remove_dir_all(&root);
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.content, "Intro.\nUse tabs.\nOutro.");
+        assert_eq!(
+            loaded.files,
+            [root.join(GEMINI_MD_FILE), root.join("docs/style.md")]
+        );
+    }
+
+    #[test]
+    fn test_includes_stay_inside_root() {
+        let root = repo("escape");
+        let outside = root.with_extension("outside.md");
+        std::fs::write(&outside, "Secret.\n").unwrap();
+        std::fs::write(
+            root.join(GEMINI_MD_FILE),
+            format!("Rules.\n@include {}\n", outside.display()),
+        )
+        .unwrap();
+
+        let loaded = load(&root, OversizeStrategy::Skip);
+        let _ = std::fs::remove_dir_all(&root);
+        let _ = std::fs::remove_file(&outside);
+
+        assert_eq!(loaded.unwrap().content, "Rules.");
+    }
+
+    #[test]
+    fn test_oversized_files_follow_the_strategy() {
+        let root = repo("oversize");
+        let big = "Rule.\n".repeat(MAX_FILE_BYTES as usize / 6 + 1);
+        std::fs::write(root.join(GEMINI_MD_FILE), &big).unwrap();
+        let path = root.join(GEMINI_MD_FILE);
+
+        let skipped = load(&root, OversizeStrategy::Skip).unwrap();
+        let truncated = load(&root, OversizeStrategy::Truncate).unwrap();
+        let pending = load(&root, OversizeStrategy::Summarize).unwrap();
+        cache_summary(&path, "Follow the rules.");
+        let summarized = load(&root, OversizeStrategy::Summarize).unwrap();
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(skipped.content, "");
+        assert_eq!(skipped.oversized, [path.clone()]);
+        assert!(truncated.content.len() <= MAX_FILE_BYTES as usize + 200);
+        assert!(truncated.content.starts_with("Rule.\nRule.\n"));
+        assert!(truncated.content.contains("truncated: kept"));
+        assert!(truncated.oversized.is_empty());
+        assert_eq!(pending.oversized, [path.clone()]);
+        assert_eq!(summarized.content, "Follow the rules.");
+        assert_eq!(summarized.files, [path]);
+        assert_eq!(
+            OversizeStrategy::parse(" Summarize"),
+            Some(OversizeStrategy::Summarize)
+        );
+        assert_eq!(OversizeStrategy::parse("drop"), None);
+    }
+}
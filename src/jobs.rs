@@ -0,0 +1,533 @@
+//! Background jobs.
+//!
+//! Prompts submitted with `gemini_submit` run outside the request that created
+//! them, at most `GEMINI_MAX_BACKGROUND_JOBS` (default 2) at a time, and are polled
+//! with `gemini_job_status`.
+//!
+//! When a state directory is configured (see [`crate::state`]), the job list is
+//! saved to `jobs.json` after every change. On startup queued jobs are restored
+//! and run, while jobs that were running when the server stopped are marked failed
+//! with a resume hint: their CLI process died with the server.
+//!
+//! Jobs may be delayed (`run_at`) or recurring (`schedule`, see
+//! [`crate::schedule`]). A recurring job queues its next occurrence when it starts;
+//! cancelling the queued occurrence ends the series.
+
+use crate::gemini::Options;
+use crate::history::format_timestamp;
+use crate::messages::{catalog, render};
+use crate::schedule::Schedule;
+use crate::sessions::Attribution;
+use crate::state::{state_dir, write_atomic};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const ENV_MAX_BACKGROUND_JOBS: &str = "GEMINI_MAX_BACKGROUND_JOBS";
+const DEFAULT_MAX_BACKGROUND_JOBS: usize = 2;
+const JOBS_FILE: &str = "jobs.json";
+const MAX_FINISHED_JOBS: usize = 100; // Finished jobs kept for status queries
+
+/// Lifecycle of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Succeeded => "succeeded",
+            JobState::Failed => "failed",
+            JobState::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobState::Succeeded | JobState::Failed | JobState::Cancelled
+        )
+    }
+}
+
+/// What a job runs: the persistable subset of [`Options`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobRequest {
+    pub prompt: String,
+    #[serde(default)]
+    pub sandbox: bool,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub response_language: Option<String>,
+    #[serde(default)]
+    pub gemini_bin: Option<String>,
+    #[serde(flatten)]
+    pub attribution: Attribution,
+}
+
+impl JobRequest {
+    pub fn to_options(&self) -> Options {
+        Options {
+            prompt: self.prompt.clone(),
+            sandbox: self.sandbox,
+            session_id: self.session_id.clone(),
+            model: self.model.clone(),
+            timeout_secs: self.timeout_secs,
+            response_language: self.response_language.clone(),
+            gemini_bin: self.gemini_bin.clone(),
+            attribution: self.attribution.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// A submitted job and, once finished, its outcome
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub state: JobState,
+    pub request: JobRequest,
+    pub created_at: String,
+    /// Earliest time the job may start; unset means as soon as possible
+    #[serde(default)]
+    pub run_at: Option<String>,
+    /// Cron expression for recurring jobs
+    #[serde(default)]
+    pub schedule: Option<String>,
+    /// For recurring jobs, the id of the first run; shared by every run of the series
+    #[serde(default)]
+    pub series_id: Option<String>,
+    #[serde(default)]
+    pub started_at: Option<String>,
+    #[serde(default)]
+    pub finished_at: Option<String>,
+    /// Session the job ran in, for resuming the conversation
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Agent reply, when the job succeeded
+    #[serde(default)]
+    pub output: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// How to recover a job that was interrupted
+    #[serde(default)]
+    pub resume_hint: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JobFile {
+    next_id: u64,
+    jobs: Vec<Job>,
+}
+
+/// Thread-safe job list, optionally persisted to disk
+#[derive(Debug)]
+pub struct JobQueue {
+    inner: Mutex<JobFile>,
+    path: Option<PathBuf>,
+    max_running: usize,
+}
+
+impl JobQueue {
+    /// Load jobs from `path` (if given), failing any that were interrupted mid-run
+    pub fn new(path: Option<PathBuf>, max_running: usize) -> Self {
+        let mut file: JobFile = path
+            .as_ref()
+            .and_then(|p| std::fs::read(p).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        let interrupted = recover_interrupted(&mut file, &format_timestamp(SystemTime::now()));
+
+        let queue = Self {
+            inner: Mutex::new(JobFile::default()),
+            path,
+            max_running: max_running.max(1),
+        };
+        if interrupted {
+            queue.save(&file);
+        }
+        *queue.inner.lock().unwrap_or_else(|e| e.into_inner()) = file;
+        queue
+    }
+
+    /// Queue configured through `GEMINI_STATE_DIR` and `GEMINI_MAX_BACKGROUND_JOBS`
+    pub fn from_env() -> Self {
+        let max_running = std::env::var(ENV_MAX_BACKGROUND_JOBS)
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_BACKGROUND_JOBS);
+        Self::new(state_dir().map(|d| d.join(JOBS_FILE)), max_running)
+    }
+
+    /// Most jobs run at the same time
+    pub fn max_running(&self) -> usize {
+        self.max_running
+    }
+
+    /// Whether jobs are saved to disk and survive a restart
+    pub fn is_persistent(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Add a job to the end of the queue
+    pub fn submit(&self, request: JobRequest) -> Job {
+        let mut file = self.lock();
+        let job = push_job(&mut file, request, None, None, None);
+        self.save(&file);
+        job
+    }
+
+    /// Queue a job that starts no earlier than `run_at`, recurring on `schedule` if given
+    pub fn schedule(
+        &self,
+        request: JobRequest,
+        run_at: SystemTime,
+        schedule: Option<String>,
+    ) -> Job {
+        let mut file = self.lock();
+        let job = push_job(
+            &mut file,
+            request,
+            Some(format_timestamp(run_at)),
+            schedule,
+            None,
+        );
+        self.save(&file);
+        job
+    }
+
+    /// Cancel a queued job. Returns the job as it is afterwards, or `None` if unknown;
+    /// jobs that already started are returned unchanged
+    pub fn cancel(&self, id: &str) -> Option<Job> {
+        let mut file = self.lock();
+        let job = file.jobs.iter_mut().find(|j| j.id == id)?;
+        if job.state != JobState::Queued {
+            return Some(job.clone());
+        }
+        job.state = JobState::Cancelled;
+        job.finished_at = Some(format_timestamp(SystemTime::now()));
+        let job = job.clone();
+        prune_finished(&mut file.jobs);
+        self.save(&file);
+        Some(job)
+    }
+
+    /// Mark due queued jobs as running, oldest first, while there is capacity
+    pub fn start_ready(&self) -> Vec<Job> {
+        self.start_ready_at(SystemTime::now())
+    }
+
+    fn start_ready_at(&self, now: SystemTime) -> Vec<Job> {
+        let mut file = self.lock();
+        let running = file
+            .jobs
+            .iter()
+            .filter(|j| j.state == JobState::Running)
+            .count();
+        let timestamp = format_timestamp(now);
+        let mut started = Vec::new();
+        // RFC 3339 UTC timestamps compare correctly as strings
+        for job in file
+            .jobs
+            .iter_mut()
+            .filter(|j| j.state == JobState::Queued)
+            .filter(|j| {
+                j.run_at
+                    .as_deref()
+                    .map_or(true, |t| t <= timestamp.as_str())
+            })
+            .take(self.max_running.saturating_sub(running))
+        {
+            job.state = JobState::Running;
+            job.started_at = Some(timestamp.clone());
+            started.push(job.clone());
+        }
+
+        // Queue the next occurrence of recurring jobs as they start
+        for job in &started {
+            let next = job
+                .schedule
+                .as_deref()
+                .and_then(|s| Schedule::parse(s).ok())
+                .and_then(|s| s.next_after(unix_secs(now)));
+            if let Some(next) = next {
+                push_job(
+                    &mut file,
+                    job.request.clone(),
+                    Some(format_timestamp(UNIX_EPOCH + Duration::from_secs(next))),
+                    job.schedule.clone(),
+                    job.series_id.clone(),
+                );
+            }
+        }
+
+        if !started.is_empty() {
+            self.save(&file);
+        }
+        started
+    }
+
+    /// Record the outcome of a running job: its reply on success, or the error.
+    /// Returns the finished job
+    pub fn finish(
+        &self,
+        id: &str,
+        session_id: Option<String>,
+        outcome: Result<String, String>,
+    ) -> Option<Job> {
+        let mut file = self.lock();
+        let job = file.jobs.iter_mut().find(|j| j.id == id)?;
+        job.finished_at = Some(format_timestamp(SystemTime::now()));
+        job.session_id = session_id.filter(|s| !s.is_empty());
+        match outcome {
+            Ok(output) => {
+                job.state = JobState::Succeeded;
+                job.output = Some(output);
+            }
+            Err(error) => {
+                job.state = JobState::Failed;
+                job.error = Some(error);
+            }
+        }
+        let job = job.clone();
+        prune_finished(&mut file.jobs);
+        self.save(&file);
+        Some(job)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.lock().jobs.iter().find(|j| j.id == id).cloned()
+    }
+
+    /// All known jobs, newest first
+    pub fn list(&self) -> Vec<Job> {
+        self.lock().jobs.iter().rev().cloned().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, JobFile> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn save(&self, file: &JobFile) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_vec_pretty(file)
+            .map_err(std::io::Error::from)
+            .and_then(|bytes| write_atomic(path, &bytes));
+        if let Err(e) = result {
+            tracing::warn!(
+                "Failed to save background jobs to {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
+
+fn push_job(
+    file: &mut JobFile,
+    request: JobRequest,
+    run_at: Option<String>,
+    schedule: Option<String>,
+    series_id: Option<String>,
+) -> Job {
+    file.next_id += 1;
+    let id = format!("job-{}", file.next_id);
+    let series_id = match &schedule {
+        Some(_) => series_id.or_else(|| Some(id.clone())),
+        None => None,
+    };
+    let job = Job {
+        id,
+        state: JobState::Queued,
+        request,
+        created_at: format_timestamp(SystemTime::now()),
+        run_at,
+        schedule,
+        series_id,
+        started_at: None,
+        finished_at: None,
+        session_id: None,
+        output: None,
+        error: None,
+        resume_hint: None,
+    };
+    file.jobs.push(job.clone());
+    job
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Fail jobs left running by a previous server process; returns whether any were
+fn recover_interrupted(file: &mut JobFile, now: &str) -> bool {
+    let mut changed = false;
+    for job in file
+        .jobs
+        .iter_mut()
+        .filter(|j| j.state == JobState::Running)
+    {
+        job.state = JobState::Failed;
+        job.finished_at = Some(now.to_string());
+        job.error = Some(catalog().job_interrupted.to_string());
+        job.resume_hint = Some(match &job.request.session_id {
+            Some(session_id) => render(catalog().job_resume_session_hint, &[session_id]),
+            None => catalog().job_resume_hint.to_string(),
+        });
+        changed = true;
+    }
+    changed
+}
+
+/// Drop the oldest finished jobs beyond [`MAX_FINISHED_JOBS`]
+fn prune_finished(jobs: &mut Vec<Job>) {
+    let finished = jobs.iter().filter(|j| j.state.is_finished()).count();
+    let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+    jobs.retain(|job| {
+        if excess > 0 && job.state.is_finished() {
+            excess -= 1;
+            return false;
+        }
+        true
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(prompt: &str) -> JobRequest {
+        JobRequest {
+            prompt: prompt.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("gemini-mcp-jobs-{}-{}", name, std::process::id()))
+            .join(JOBS_FILE)
+    }
+
+    #[test]
+    fn test_start_ready_respects_capacity() {
+        let queue = JobQueue::new(None, 2);
+        let first = queue.submit(request("one"));
+        queue.submit(request("two"));
+        queue.submit(request("three"));
+
+        let started = queue.start_ready();
+        assert_eq!(started.len(), 2);
+        assert_eq!(started[0].id, first.id);
+        assert!(queue.start_ready().is_empty());
+
+        queue.finish(&first.id, Some("s1".to_string()), Ok("done".to_string()));
+        let job = queue.get(&first.id).unwrap();
+        assert_eq!(job.state, JobState::Succeeded);
+        assert_eq!(job.output.as_deref(), Some("done"));
+        assert_eq!(queue.start_ready().len(), 1);
+    }
+
+    #[test]
+    fn test_restart_restores_queued_and_fails_running() {
+        let path = temp_path("restart");
+        {
+            let queue = JobQueue::new(Some(path.clone()), 1);
+            queue.submit(JobRequest {
+                session_id: Some("s1".to_string()),
+                ..request("running")
+            });
+            queue.submit(request("queued"));
+            queue.start_ready();
+        }
+
+        let queue = JobQueue::new(Some(path.clone()), 1);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+
+        let jobs = queue.list();
+        assert_eq!(jobs.len(), 2);
+        let interrupted = queue.get("job-1").unwrap();
+        assert_eq!(interrupted.state, JobState::Failed);
+        assert!(interrupted.resume_hint.unwrap().contains("s1"));
+        assert_eq!(queue.get("job-2").unwrap().state, JobState::Queued);
+
+        // Ids keep counting after a restart
+        assert_eq!(queue.submit(request("next")).id, "job-3");
+        assert_eq!(queue.start_ready()[0].id, "job-2");
+    }
+
+    #[test]
+    fn test_delayed_job_waits_until_due() {
+        let queue = JobQueue::new(None, 2);
+        let now = SystemTime::now();
+        let delayed = queue.schedule(request("later"), now + Duration::from_secs(3600), None);
+
+        assert!(queue.start_ready_at(now).is_empty());
+        let started = queue.start_ready_at(now + Duration::from_secs(3601));
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].id, delayed.id);
+    }
+
+    #[test]
+    fn test_recurring_job_queues_next_occurrence() {
+        let queue = JobQueue::new(None, 2);
+        // 2024-03-01T02:30:00Z
+        let first_run = UNIX_EPOCH + Duration::from_secs(1_709_260_200);
+        queue.schedule(
+            request("nightly"),
+            first_run,
+            Some("30 2 * * *".to_string()),
+        );
+
+        let started = queue.start_ready_at(first_run);
+        assert_eq!(started.len(), 1);
+        let next = queue.list().into_iter().next().unwrap();
+        assert_eq!(next.state, JobState::Queued);
+        assert_eq!(next.run_at.as_deref(), Some("2024-03-02T02:30:00Z"));
+        assert_eq!(next.schedule.as_deref(), Some("30 2 * * *"));
+        assert_eq!(next.series_id, started[0].series_id);
+        assert_eq!(next.series_id.as_deref(), Some(started[0].id.as_str()));
+
+        // Cancelling the pending occurrence ends the series
+        assert_eq!(queue.cancel(&next.id).unwrap().state, JobState::Cancelled);
+        assert_eq!(
+            queue.cancel(&started[0].id).unwrap().state,
+            JobState::Running
+        );
+        assert!(queue.cancel("job-99").is_none());
+    }
+
+    #[test]
+    fn test_prune_finished_keeps_queued_jobs() {
+        let queue = JobQueue::new(None, 1);
+        for i in 0..(MAX_FINISHED_JOBS + 3) {
+            let job = queue.submit(request(&format!("job {}", i)));
+            queue.start_ready();
+            queue.finish(&job.id, None, Err("failed".to_string()));
+        }
+        let pending = queue.submit(request("pending"));
+
+        let jobs = queue.list();
+        assert_eq!(jobs.len(), MAX_FINISHED_JOBS + 1);
+        assert_eq!(jobs[0].id, pending.id);
+        assert!(queue.get("job-1").is_none());
+    }
+}
@@ -1,3 +1,51 @@
+pub mod attach;
+pub mod audit;
+pub mod budget;
+pub mod cache;
+pub mod chat;
+pub mod chunking;
+pub mod client;
+pub mod concurrency;
+pub mod config;
+pub mod daemon;
+pub mod doctor;
+pub mod embed;
+pub mod environment;
+pub mod events;
+pub mod export;
 pub mod gemini;
+pub mod health;
+pub mod history;
+pub mod images;
+pub mod instructions;
+pub mod jobs;
+pub mod live;
+pub mod logging;
+pub mod messages;
+pub mod metrics;
+pub mod origin;
+pub mod patch;
+pub mod policy;
+pub mod postprocess;
+pub mod pricing;
+pub mod process;
+pub mod prompts;
+pub mod pty;
+pub mod quota;
+pub mod ratelimit;
+pub mod redact;
+pub mod report;
+pub mod review;
+pub mod schedule;
+pub mod scratch;
 pub mod server;
+pub mod sessions;
+pub mod spool;
+pub mod state;
+pub mod stream;
+pub mod summarize;
+pub mod transcripts;
 pub mod transport;
+pub mod truncate;
+pub mod validate;
+pub mod workspace;
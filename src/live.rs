@@ -0,0 +1,641 @@
+//! Long-lived CLI processes for `gemini_followup`.
+//!
+//! Each call to the `gemini` tool starts the CLI afresh and has it resume the session
+//! from disk, which costs seconds per turn. `gemini_followup` instead keeps one CLI per
+//! session running in Agent Client Protocol mode (`--experimental-acp`), a JSON-RPC
+//! dialogue over its stdin and stdout: the process is started and the session created
+//! once, and every follow-up is a `session/prompt` request whose reply streams back as
+//! `session/update` notifications.
+//!
+//! A process idle for `GEMINI_LIVE_IDLE_SECS` (default 600) is stopped, and at most
+//! `GEMINI_LIVE_MAX_PROCESSES` (default 4) are kept, the least recently used idle one
+//! making room for a new one. A turn that fails, times out or is cancelled stops its
+//! process as well. A follow-up to a session without a process loads the session into
+//! a new one, or, when the CLI can't load sessions, runs as a one-off resumed call.
+//!
+//! The CLI only asks for permission for actions its approval mode doesn't allow, and
+//! as in headless calls those are refused.
+
+use crate::gemini::{self, CallControl, GeminiError, GeminiResult, Options, Stopwatch};
+use crate::process::{self, ProcessTree};
+use crate::stream::decode_line;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Split};
+use tokio::process::{Child, ChildStdin, ChildStdout};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+pub const ENV_LIVE_IDLE_SECS: &str = "GEMINI_LIVE_IDLE_SECS";
+pub const ENV_LIVE_MAX_PROCESSES: &str = "GEMINI_LIVE_MAX_PROCESSES";
+pub const DEFAULT_IDLE_SECS: u64 = 600; // 10 minutes
+pub const DEFAULT_MAX_PROCESSES: usize = 4;
+/// Agent Client Protocol version spoken to the CLI
+const PROTOCOL_VERSION: u64 = 1;
+const METHOD_INITIALIZE: &str = "initialize";
+const METHOD_NEW_SESSION: &str = "session/new";
+const METHOD_LOAD_SESSION: &str = "session/load";
+const METHOD_PROMPT: &str = "session/prompt";
+const METHOD_UPDATE: &str = "session/update";
+const METHOD_REQUEST_PERMISSION: &str = "session/request_permission";
+/// JSON-RPC error code for methods the client doesn't implement
+const METHOD_NOT_FOUND: i64 = -32601;
+const MAX_STDERR_BYTES: usize = 100_000;
+/// How long to wait for a CLI that closed its stdout to exit
+const EXIT_WAIT: Duration = Duration::from_secs(1);
+
+/// JSON-RPC connection to a CLI running in ACP mode. Dropping it kills the CLI
+struct Connection {
+    child: Child,
+    _tree: ProcessTree,
+    stdin: ChildStdin,
+    stdout: Split<BufReader<ChildStdout>>,
+    stderr: Arc<Mutex<String>>,
+    next_id: u64,
+    /// The CLI closed its stdout or couldn't be written to
+    closed: bool,
+}
+
+impl Connection {
+    fn spawn(opts: &Options) -> Result<Self, GeminiError> {
+        let mut cmd = gemini::live_command(opts);
+        cmd.kill_on_drop(true);
+        process::configure(&mut cmd);
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| GeminiError::SpawnFailed(e.to_string()))?;
+        tracing::debug!(pid = child.id(), "spawned live gemini");
+        let tree = ProcessTree::attach(&child);
+        let pipe = |name: &str| GeminiError::Io(format!("Failed to get {}", name));
+        let stdin = child.stdin.take().ok_or_else(|| pipe("stdin"))?;
+        let stdout = child.stdout.take().ok_or_else(|| pipe("stdout"))?;
+        let stderr_pipe = child.stderr.take().ok_or_else(|| pipe("stderr"))?;
+
+        // Keep the start of stderr for errors, reading on so the CLI never blocks on it
+        let stderr = Arc::new(Mutex::new(String::new()));
+        let captured = stderr.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr_pipe).split(b'\n');
+            while let Ok(Some(bytes)) = lines.next_segment().await {
+                let mut captured = captured.lock().unwrap_or_else(|e| e.into_inner());
+                if captured.len() < MAX_STDERR_BYTES {
+                    if !captured.is_empty() {
+                        captured.push('\n');
+                    }
+                    captured.push_str(&decode_line(&bytes));
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            _tree: tree,
+            stdin,
+            stdout: BufReader::new(stdout).split(b'\n'),
+            stderr,
+            next_id: 0,
+            closed: false,
+        })
+    }
+
+    async fn send(&mut self, message: Value) -> Result<(), GeminiError> {
+        let mut line = message.to_string();
+        line.push('\n');
+        let written = async {
+            self.stdin.write_all(line.as_bytes()).await?;
+            self.stdin.flush().await
+        }
+        .await;
+        if written.is_err() {
+            return Err(self.exited().await);
+        }
+        Ok(())
+    }
+
+    /// Send a request and read the CLI's messages until its response arrives, passing
+    /// `session/update` notifications to `on_update` and answering the CLI's requests
+    async fn request(
+        &mut self,
+        method: &str,
+        params: Value,
+        on_update: &mut (dyn FnMut(&Value) + Send),
+    ) -> Result<Value, GeminiError> {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}))
+            .await?;
+        loop {
+            let line = match self.stdout.next_segment().await {
+                Ok(Some(line)) => line,
+                Ok(None) | Err(_) => return Err(self.exited().await),
+            };
+            let Ok(message) = serde_json::from_slice::<Value>(&line) else {
+                tracing::debug!(line = %decode_line(&line), "ignoring non-JSON output from live gemini");
+                continue;
+            };
+            match message.get("method").and_then(Value::as_str) {
+                Some(METHOD_UPDATE) => {
+                    if let Some(params) = message.get("params") {
+                        on_update(params);
+                    }
+                }
+                Some(incoming) => {
+                    if let Some(request_id) = message.get("id") {
+                        let mut reply = answer(incoming, message.get("params"));
+                        reply["jsonrpc"] = "2.0".into();
+                        reply["id"] = request_id.clone();
+                        self.send(reply).await?;
+                    }
+                }
+                None if message.get("id").and_then(Value::as_u64) == Some(id) => {
+                    if let Some(error) = message.get("error") {
+                        let text = error
+                            .get("message")
+                            .and_then(Value::as_str)
+                            .map_or_else(|| error.to_string(), String::from);
+                        return Err(GeminiError::from_event(&text));
+                    }
+                    return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+                }
+                // The response to a request that was given up on
+                None => {}
+            }
+        }
+    }
+
+    /// Error for a CLI that stopped talking, with its exit code once it has exited
+    async fn exited(&mut self) -> GeminiError {
+        self.closed = true;
+        let code = tokio::time::timeout(EXIT_WAIT, self.child.wait())
+            .await
+            .ok()
+            .and_then(|status| status.ok())
+            .and_then(|status| status.code());
+        let stderr = self
+            .stderr
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        match crate::gemini::auth_failure_line(&stderr) {
+            Some(line) => GeminiError::AuthError(line.to_string()),
+            None => GeminiError::NonZeroExit { code, stderr },
+        }
+    }
+}
+
+/// Reply to a request from the CLI. Permission requests are refused, as in headless
+/// calls; nothing else was advertised
+fn answer(method: &str, params: Option<&Value>) -> Value {
+    if method != METHOD_REQUEST_PERMISSION {
+        return json!({"error": {
+            "code": METHOD_NOT_FOUND,
+            "message": format!("Method not found: {}", method),
+        }});
+    }
+    let reject = params
+        .and_then(|p| p.get("options"))
+        .and_then(Value::as_array)
+        .and_then(|options| {
+            options
+                .iter()
+                .find(|o| o.get("kind").and_then(Value::as_str) == Some("reject_once"))
+        })
+        .and_then(|option| option.get("optionId"));
+    let outcome = match reject {
+        Some(id) => json!({"outcome": "selected", "optionId": id}),
+        None => json!({"outcome": "cancelled"}),
+    };
+    json!({"result": {"outcome": outcome}})
+}
+
+/// What one prompt turn has produced so far
+struct Turn {
+    messages: String,
+    tool_calls: Vec<String>,
+    events: Vec<Value>,
+    keep_events: bool,
+    partial_messages: Option<UnboundedSender<String>>,
+    stopwatch: Stopwatch,
+    control: CallControl,
+}
+
+impl Turn {
+    /// A turn of a call that started at `started`, whose process is ready now
+    fn new(opts: &Options, started: Instant) -> Self {
+        Self {
+            stopwatch: Stopwatch::new(started),
+            messages: String::new(),
+            tool_calls: Vec::new(),
+            events: Vec::new(),
+            keep_events: opts.return_all_messages,
+            partial_messages: opts.partial_messages.clone(),
+            control: opts.control.clone(),
+        }
+    }
+
+    /// Take in the params of a `session/update` notification
+    fn update(&mut self, params: &Value) {
+        let Some(update) = params.get("update") else {
+            return;
+        };
+        match update.get("sessionUpdate").and_then(Value::as_str) {
+            Some("agent_message_chunk") => {
+                let text = update
+                    .get("content")
+                    .filter(|c| c.get("type").and_then(Value::as_str) == Some("text"))
+                    .and_then(|c| c.get("text"))
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                self.messages.push_str(text);
+                if let (Some(partial), false) = (&self.partial_messages, text.is_empty()) {
+                    // A closed receiver only means nobody is listening any more
+                    let _ = partial.send(text.to_string());
+                }
+            }
+            Some("tool_call") => {
+                let name = ["title", "toolCallId"]
+                    .iter()
+                    .find_map(|key| update.get(*key).and_then(Value::as_str));
+                if let Some(name) = name {
+                    self.tool_calls.push(name.to_string());
+                }
+            }
+            _ => {}
+        }
+        if self.keep_events {
+            self.events.push(update.clone());
+        }
+        self.stopwatch.event(!self.messages.is_empty());
+        self.control.touch();
+    }
+
+    fn into_result(
+        self,
+        opts: &Options,
+        session_id: &str,
+        stop_reason: Option<String>,
+    ) -> GeminiResult {
+        GeminiResult {
+            success: true,
+            session_id: session_id.to_string(),
+            agent_messages: self.messages,
+            all_messages: self.events,
+            return_all_messages: self.keep_events,
+            tool_calls: self.tool_calls,
+            model: opts
+                .model
+                .as_deref()
+                .map(str::trim)
+                .filter(|m| !m.is_empty())
+                .map(String::from)
+                .or_else(gemini::get_force_model),
+            finish_reason: stop_reason,
+            timings: Some(self.stopwatch.stop()),
+            ..Default::default()
+        }
+    }
+}
+
+/// A CLI process holding one session
+struct LiveProcess {
+    connection: Connection,
+    session_id: String,
+    /// Set while a prompt is in flight. Still set afterwards means the turn was
+    /// abandoned midway and the process can't be trusted with another
+    turn_open: bool,
+}
+
+impl LiveProcess {
+    /// Start a CLI and create the session, or load `opts.session_id` into it. `None`
+    /// when there is a session to load but the CLI can't load sessions
+    async fn start(opts: &Options) -> Result<Option<Self>, GeminiError> {
+        let mut connection = Connection::spawn(opts)?;
+        let init = connection
+            .request(
+                METHOD_INITIALIZE,
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "clientCapabilities": {"fs": {"readTextFile": false, "writeTextFile": false}},
+                }),
+                &mut |_| {},
+            )
+            .await?;
+        let cwd = match &opts.working_dir {
+            Some(dir) => dir.clone(),
+            None => std::env::current_dir().map_err(|e| GeminiError::Io(e.to_string()))?,
+        };
+
+        let session_id = match &opts.session_id {
+            Some(session_id) => {
+                let can_load = init
+                    .pointer("/agentCapabilities/loadSession")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false);
+                if !can_load {
+                    return Ok(None);
+                }
+                // Loading replays the conversation as updates, which are old news here
+                connection
+                    .request(
+                        METHOD_LOAD_SESSION,
+                        json!({"sessionId": session_id, "cwd": cwd, "mcpServers": []}),
+                        &mut |_| {},
+                    )
+                    .await?;
+                session_id.clone()
+            }
+            None => connection
+                .request(
+                    METHOD_NEW_SESSION,
+                    json!({"cwd": cwd, "mcpServers": []}),
+                    &mut |_| {},
+                )
+                .await?
+                .get("sessionId")
+                .and_then(Value::as_str)
+                .map(String::from)
+                .ok_or_else(|| GeminiError::NoOutput("sessionId".to_string()))?,
+        };
+        tracing::debug!(session_id = %session_id, "live gemini session ready");
+        Ok(Some(Self {
+            connection,
+            session_id,
+            turn_open: false,
+        }))
+    }
+
+    fn usable(&self) -> bool {
+        !self.turn_open && !self.connection.closed
+    }
+
+    /// Send `prompt` and collect the reply into `turn`. Returns the stop reason
+    async fn prompt(
+        &mut self,
+        prompt: String,
+        turn: &mut Turn,
+    ) -> Result<Option<String>, GeminiError> {
+        self.turn_open = true;
+        let response = self
+            .connection
+            .request(
+                METHOD_PROMPT,
+                json!({
+                    "sessionId": self.session_id,
+                    "prompt": [{"type": "text", "text": prompt}],
+                }),
+                &mut |params| turn.update(params),
+            )
+            .await;
+        self.turn_open = false;
+        Ok(response?
+            .get("stopReason")
+            .and_then(Value::as_str)
+            .map(String::from))
+    }
+}
+
+type Slot = Arc<AsyncMutex<LiveProcess>>;
+
+struct Entry {
+    process: Slot,
+    last_used: Instant,
+}
+
+/// The running CLI processes by session id
+pub struct LivePool {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+    idle: Duration,
+    max_processes: usize,
+}
+
+impl LivePool {
+    pub fn new(idle: Duration, max_processes: usize) -> Self {
+        Self {
+            entries: Arc::default(),
+            idle,
+            max_processes: max_processes.max(1),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let idle = std::env::var(ENV_LIVE_IDLE_SECS)
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|&secs| secs > 0)
+            .unwrap_or(DEFAULT_IDLE_SECS);
+        let max_processes = std::env::var(ENV_LIVE_MAX_PROCESSES)
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_MAX_PROCESSES);
+        Self::new(Duration::from_secs(idle), max_processes)
+    }
+
+    /// Run `opts.prompt` as the next turn of its session's live process, starting one
+    /// when the session has none
+    pub async fn run(&self, opts: Options) -> Result<GeminiResult, GeminiError> {
+        let limit = Duration::from_secs(
+            opts.timeout_secs
+                .unwrap_or_else(gemini::get_default_timeout),
+        );
+        let started = Instant::now();
+        let turn = async {
+            let Some((slot, mut live, started)) = self.acquire(&opts).await? else {
+                return Ok(None);
+            };
+            let prompt = gemini::prepare_prompt(&opts, started).await?;
+            let mut turn = Turn::new(&opts, started);
+            let outcome = live.prompt(prompt, &mut turn).await;
+            let session_id = live.session_id.clone();
+            let usable = live.usable();
+            drop(live);
+            if usable {
+                self.check_in(&session_id, slot);
+            } else {
+                self.remove(&session_id, &slot);
+            }
+            let stop_reason = outcome?;
+            Ok::<_, GeminiError>(Some(turn.into_result(&opts, &session_id, stop_reason)))
+        };
+
+        match gemini::supervise(turn, limit, gemini::idle_limit(&opts), &opts.control).await {
+            Ok(Ok(Some(result))) => Ok(result),
+            Ok(Ok(None)) => {
+                tracing::debug!("gemini can't load sessions, resuming with a one-off call");
+                gemini::run(opts).await
+            }
+            Ok(Err(e)) => Err(e),
+            // The abandoned turn left its process marked, so it is replaced on next use
+            Err(reason) => Err(reason.into_error(limit)),
+        }
+    }
+
+    /// The session's process, locked for a turn, and whether it was just started
+    async fn acquire(
+        &self,
+        opts: &Options,
+    ) -> Result<Option<(Slot, OwnedMutexGuard<LiveProcess>, bool)>, GeminiError> {
+        let existing = opts
+            .session_id
+            .as_deref()
+            .and_then(|id| self.lock().get(id).map(|entry| entry.process.clone()));
+        if let Some(slot) = existing {
+            let live = slot.clone().lock_owned().await;
+            if live.usable() {
+                return Ok(Some((slot, live, false)));
+            }
+            let session_id = live.session_id.clone();
+            drop(live);
+            self.remove(&session_id, &slot);
+        }
+        match LiveProcess::start(opts).await? {
+            Some(process) => {
+                let slot = Arc::new(AsyncMutex::new(process));
+                let live = slot.clone().lock_owned().await;
+                Ok(Some((slot, live, true)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Keep `process` for the session's next turn, making room under the process limit
+    /// and stopping it once it has been idle too long
+    fn check_in(&self, session_id: &str, process: Slot) {
+        let mut entries = self.lock();
+        entries.insert(
+            session_id.to_string(),
+            Entry {
+                process,
+                last_used: Instant::now(),
+            },
+        );
+        while entries.len() > self.max_processes {
+            // Only idle processes can go; busy ones are over the limit for a while
+            let Some(oldest) = entries
+                .iter()
+                .filter(|(id, entry)| *id != session_id && entry.process.try_lock().is_ok())
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(id, _)| id.clone())
+            else {
+                break;
+            };
+            tracing::debug!(session_id = %oldest, "stopping live gemini to make room");
+            entries.remove(&oldest);
+        }
+        drop(entries);
+
+        let entries = Arc::downgrade(&self.entries);
+        let (session_id, idle) = (session_id.to_string(), self.idle);
+        tokio::spawn(async move {
+            tokio::time::sleep(idle).await;
+            let Some(entries) = entries.upgrade() else {
+                return;
+            };
+            let mut entries = entries.lock().unwrap_or_else(|e| e.into_inner());
+            let expired = entries.get(&session_id).is_some_and(|entry| {
+                entry.last_used.elapsed() >= idle && entry.process.try_lock().is_ok()
+            });
+            if expired {
+                tracing::debug!(session_id = %session_id, "stopping idle live gemini");
+                entries.remove(&session_id);
+            }
+        });
+    }
+
+    /// Forget the session's process if it is still `process`
+    fn remove(&self, session_id: &str, process: &Slot) {
+        let mut entries = self.lock();
+        if entries
+            .get(session_id)
+            .is_some_and(|entry| Arc::ptr_eq(&entry.process, process))
+        {
+            entries.remove(session_id);
+        }
+    }
+
+    /// Stop every idle process, e.g. on shutdown. Processes mid-turn stop when it ends
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_requests_are_refused() {
+        let params = json!({"options": [
+            {"optionId": "allow", "kind": "allow_once"},
+            {"optionId": "deny", "kind": "reject_once"},
+        ]});
+        assert_eq!(
+            answer(METHOD_REQUEST_PERMISSION, Some(&params))["result"]["outcome"],
+            json!({"outcome": "selected", "optionId": "deny"})
+        );
+        assert_eq!(
+            answer(METHOD_REQUEST_PERMISSION, None)["result"]["outcome"]["outcome"],
+            "cancelled"
+        );
+        assert_eq!(
+            answer("fs/read_text_file", None)["error"]["code"],
+            METHOD_NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn test_turn_collects_updates() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let opts = Options {
+            return_all_messages: true,
+            partial_messages: Some(tx),
+            model: Some("gemini-2.5-flash".to_string()),
+            ..Default::default()
+        };
+        let mut turn = Turn::new(&opts, Instant::now());
+        let chunk = |text: &str| {
+            json!({"sessionId": "s1", "update": {
+                "sessionUpdate": "agent_message_chunk",
+                "content": {"type": "text", "text": text},
+            }})
+        };
+        turn.update(&chunk("Hello, "));
+        turn.update(&json!({"sessionId": "s1", "update": {
+            "sessionUpdate": "tool_call", "toolCallId": "t1", "title": "ReadFile",
+        }}));
+        turn.update(&chunk("world"));
+        turn.update(&json!({"sessionId": "s1"}));
+
+        let result = turn.into_result(&opts, "s1", Some("end_turn".to_string()));
+        assert!(result.success);
+        assert_eq!(result.session_id, "s1");
+        assert_eq!(result.agent_messages, "Hello, world");
+        assert_eq!(result.tool_calls, ["ReadFile"]);
+        assert_eq!(result.all_messages.len(), 3);
+        assert_eq!(result.model.as_deref(), Some("gemini-2.5-flash"));
+        assert_eq!(result.finish_reason.as_deref(), Some("end_turn"));
+        let timings = result.timings.unwrap();
+        assert!(timings.first_event_ms <= timings.first_content_ms);
+        assert!(timings.first_content_ms.unwrap() <= timings.total_ms);
+        assert_eq!(rx.try_recv().unwrap(), "Hello, ");
+        assert_eq!(rx.try_recv().unwrap(), "world");
+    }
+
+    #[tokio::test]
+    async fn test_pool_reports_spawn_failures() {
+        let pool = LivePool::new(Duration::from_secs(60), 2);
+        let opts = Options {
+            prompt: "hi".to_string(),
+            gemini_bin: Some("/nonexistent/gemini-live-test".to_string()),
+            ..Default::default()
+        };
+        let err = pool.run(opts).await.unwrap_err();
+        assert!(matches!(err, GeminiError::SpawnFailed(_)));
+        assert!(pool.lock().is_empty());
+    }
+}
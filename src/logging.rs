@@ -0,0 +1,114 @@
+//! Diagnostic logging through `tracing`.
+//!
+//! Logs go to stderr, which MCP clients usually capture, or to the file named by
+//! `--log-file` / `GEMINI_LOG_FILE`. Never stdout: that carries the stdio transport.
+//!
+//! `GEMINI_LOG` takes `tracing` filter directives (default `info`). Every tool call
+//! runs in a `tool_call` span; at `debug` the span also reports the spawned CLI's PID,
+//! exit code, output byte counts and each call's duration. `GEMINI_LOG_FORMAT`
+//! selects `pretty` (default, one human-readable line per event) or `json`
+//! (one JSON object per line, for log shippers).
+
+use anyhow::{Context, Result};
+use std::io::IsTerminal;
+use std::path::Path;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::EnvFilter;
+
+pub const ENV_LOG: &str = "GEMINI_LOG";
+pub const ENV_LOG_FORMAT: &str = "GEMINI_LOG_FORMAT";
+pub const ENV_LOG_FILE: &str = "GEMINI_LOG_FILE";
+const DEFAULT_FILTER: &str = "info";
+
+/// How log events are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    #[default]
+    Pretty,
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "pretty" | "text" => Some(LogFormat::Pretty),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `directives` is a valid `GEMINI_LOG` filter
+pub fn valid_filter(directives: &str) -> bool {
+    EnvFilter::try_new(directives).is_ok()
+}
+
+/// Install the global subscriber, writing to `file` (appended) or stderr. Filter and
+/// format come from `GEMINI_LOG` and `GEMINI_LOG_FORMAT`; invalid values fall back
+/// to the defaults with a warning
+pub fn init(file: Option<&Path>) -> Result<()> {
+    let directives = std::env::var(ENV_LOG).unwrap_or_default();
+    let directives = Some(directives.trim()).filter(|d| !d.is_empty());
+    let (filter, filter_invalid) = match directives.map(EnvFilter::try_new) {
+        Some(Ok(filter)) => (filter, false),
+        Some(Err(_)) => (EnvFilter::new(DEFAULT_FILTER), true),
+        None => (EnvFilter::new(DEFAULT_FILTER), false),
+    };
+    let format_value = std::env::var(ENV_LOG_FORMAT).ok();
+    let format = format_value.as_deref().map(LogFormat::parse);
+
+    let (writer, ansi) = match file {
+        Some(path) => {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {}", path.display()))?;
+            (BoxMakeWriter::new(std::sync::Mutex::new(file)), false)
+        }
+        None => (
+            BoxMakeWriter::new(std::io::stderr),
+            std::io::stderr().is_terminal(),
+        ),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format.flatten().unwrap_or_default() {
+        LogFormat::Pretty => builder.try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    }
+    .map_err(|e| anyhow::anyhow!("Failed to initialize logging: {}", e))?;
+
+    if filter_invalid {
+        tracing::warn!(
+            "{}={:?} is not a valid filter; using '{}'",
+            ENV_LOG,
+            directives.unwrap_or_default(),
+            DEFAULT_FILTER
+        );
+    }
+    if let (Some(value), Some(None)) = (&format_value, format) {
+        tracing::warn!(
+            "{}={:?} is not a log format (pretty, json); using pretty",
+            ENV_LOG_FORMAT,
+            value
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_format_and_filter() {
+        assert_eq!(LogFormat::parse(" JSON "), Some(LogFormat::Json));
+        assert_eq!(LogFormat::parse("pretty"), Some(LogFormat::Pretty));
+        assert_eq!(LogFormat::parse("xml"), None);
+        assert!(valid_filter("debug,rmcp=warn"));
+        assert!(!valid_filter("gemini_mcp_rs=loud"));
+    }
+}
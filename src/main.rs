@@ -1,8 +1,32 @@
-use anyhow::Result;
-use clap::Parser;
-use gemini_mcp_rs::server::GeminiServer;
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use gemini_mcp_rs::config::{self, Config};
+use gemini_mcp_rs::gemini::{self, ApprovalMode, Options};
+use gemini_mcp_rs::history::format_timestamp;
+use gemini_mcp_rs::pricing::PricingTable;
+use gemini_mcp_rs::server::{self, GeminiServer};
 use gemini_mcp_rs::transport::AdaptiveStdio;
+use gemini_mcp_rs::{audit, client, daemon, doctor, logging, origin, report};
+use rmcp::transport::streamable_http_server::{
+    session::local::LocalSessionManager, StreamableHttpServerConfig, StreamableHttpService,
+};
 use rmcp::ServiceExt;
+use std::future::IntoFuture;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tokio_util::sync::CancellationToken;
+
+/// Path the HTTP transport serves MCP on
+const MCP_PATH: &str = "/mcp";
+const METRICS_PATH: &str = "/metrics";
+const HTTP_TRANSPORT: &str = "streamable HTTP at /mcp, shared by every connected client";
+const DEFAULT_LISTEN: &str = "127.0.0.1:8787";
+/// How long open connections get to close once the running calls have drained
+const CLOSE_GRACE: Duration = Duration::from_secs(5);
+const ENV_SHUTDOWN_GRACE_SECS: &str = "GEMINI_SHUTDOWN_GRACE_SECS";
+/// How long running calls get to finish on shutdown before they are killed
+const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
 
 /// MCP server wrapping the Gemini CLI for AI-driven tasks
 #[derive(Parser)]
@@ -14,12 +38,119 @@ use rmcp::ServiceExt;
     after_help = "ENVIRONMENT VARIABLES:
   GEMINI_BIN                   Override the gemini binary path (default: 'gemini')
   GEMINI_DEFAULT_TIMEOUT       Default timeout in seconds (1-3600, default: 600)
+  GEMINI_IDLE_TIMEOUT_SECS     Stop calls after this many seconds without CLI output (1-3600,
+                               default: no idle limit)
   GEMINI_FORCE_MODEL           Default model when request omits 'model' parameter
+  GEMINI_EMBED_MODEL           Default model for gemini_embed (default: gemini-embedding-001)
+  GEMINI_ALLOWED_MODELS        Comma-separated models callers may request (default: any)
+  GEMINI_MODEL_FALLBACKS       Comma-separated models to retry rate-limited or overloaded calls on
+  GEMINI_MODEL_ALIASES         alias=model pairs callers may pass as 'model' (built in: fast, smart)
+  GEMINI_DEFAULT_SANDBOX       Set to 1 to sandbox calls that omit 'sandbox' (default: off)
+  GEMINI_SANDBOX_BACKEND       docker, podman or sandbox-exec for sandboxed calls that omit 'sandbox_backend'
+  GEMINI_INSTRUCTIONS_OVERSIZE skip, fail, truncate or summarize GEMINI.md files over 100KB (default: skip)
+  GEMINI_APPROVAL_MODE         yolo, auto_edit or default for calls that omit 'approval_mode' (default: yolo)
+  GEMINI_BIN_CHOICES           Named binaries selectable per call, e.g. 'stable=/usr/bin/gemini,next=/opt/gemini'
+  GEMINI_ALLOW_BIN_OVERRIDE    Set to 1 to honor the per-call 'gemini_bin' parameter (default: off)
+  GEMINI_ALLOWED_ENV_KEYS      Comma-separated variables the per-call 'env' parameter may set (default: none)
+  GEMINI_ENV_POLICY            Server variables the CLI inherits: allowlist, denylist or inherit (default: allowlist)
+  GEMINI_ENV_ALLOW             Extra variables passed under the allowlist policy, e.g. 'JAVA_HOME,MY_TOOL_*'
+  GEMINI_ENV_DENY              Variables withheld under the denylist policy (default: common secret names)
+  GEMINI_EXTRA_ARGS            Arguments added to every CLI call, quoted like a shell command line
+  GEMINI_THOUGHTS_ARGS         Arguments added to calls with include_thoughts (default: none)
+  GEMINI_PROXY                 Proxy URL for calls that set no proxy, or off to ignore inherited ones
+  GEMINI_NO_PROXY              Comma-separated hosts reached without the proxy
+  GEMINI_EXTENSIONS            Extensions calls load and may name, or none
+  GEMINI_ALLOWED_MCP_SERVERS   MCP servers the CLI may use and calls may name, or none
+  GEMINI_ALLOW_EXTRA_ARGS      Set to 1 to honor the per-call 'extra_args' parameter (default: off)
+  GEMINI_INCLUDE_ROOT          Directory 'include_directories' entries must be inside (default: working directory)
+  GEMINI_RESPONSE_LANGUAGE     Default reply language when request omits 'response_language'
+  GEMINI_MCP_LOCALE            Language for error messages and labels (en, zh-CN; default: en)
+  GEMINI_LOG                   Diagnostic log filter, e.g. 'debug' or 'info,gemini_mcp_rs=debug' (default: info)
+  GEMINI_LOG_FORMAT            Log format: pretty or json (default: pretty)
+  GEMINI_LOG_FILE              Append logs to this file instead of stderr (same as --log-file)
+  GEMINI_AUDIT_LOG             Append one JSON line per tool call to this file (default: off)
+  GEMINI_PRICING               USD per million tokens, e.g. 'gemini-2.5-pro=1.25:10,*=0.3:2.5'
+  GEMINI_QUOTAS                Per-model requests:tokens per minute, e.g. 'gemini-2.5-pro=5:250000'
+  GEMINI_QUOTA_MAX_WAIT_SECS   Longest a call waits for quota before being rejected (default: 30)
+  GEMINI_BUDGET_CALL_SECS      Longest a single call may run (default: no limit)
+  GEMINI_BUDGET_CALL_TOKENS    Largest estimated prompt a single call may send (default: no limit)
+  GEMINI_BUDGET_HOURLY_SECS    Total call run time allowed per rolling hour (default: no limit)
+  GEMINI_BUDGET_HOURLY_TOKENS  Total tokens calls may use per rolling hour (default: no limit)
+  GEMINI_RATE_LIMIT_RPM        Gemini calls each client may start per minute (default: no limit)
+  GEMINI_RATE_LIMIT_BURST      Calls a client may start back to back (default: the per-minute rate)
+  GEMINI_MAX_CONCURRENCY       Gemini processes running at once across all calls (default: no limit)
+  GEMINI_CONCURRENCY_MAX_WAIT_SECS  Longest a call waits for a free slot before failing as busy (default: 30)
+  GEMINI_STATE_DIR             Directory for state kept across restarts, e.g. jobs, sessions (default: none)
+  GEMINI_MAX_SESSIONS          Sessions kept in the registry, least recently used dropped first (default: 1000)
+  GEMINI_SESSION_TTL_SECS      Expire sessions unused this long, with their transcripts (default: never)
+  GEMINI_REQUIRE_KNOWN_SESSIONS  Only resume sessions in the registry (default: false)
+  GEMINI_TRANSCRIPT_DIR        Session transcripts for get_transcript, or 'off' (default: ~/.local/share/gemini-mcp-rs/transcripts)
+  GEMINI_TRANSCRIPT_RETENTION_DAYS  Remove transcripts idle this long; 0 keeps them (default: 30)
+  GEMINI_SPOOL_DIR             Event traces of spool_messages calls (default: <temp>/gemini-mcp-rs/traces)
+  GEMINI_SPOOL_RETENTION_HOURS Remove traces older than this; 0 keeps them (default: 24)
+  GEMINI_MAX_BACKGROUND_JOBS   Background jobs run at the same time (default: 2)
+  GEMINI_SHUTDOWN_GRACE_SECS   On SIGTERM/SIGINT, how long running calls may finish before being killed (default: 30)
+  GEMINI_HTTP_ALLOWED_HOSTS    Hosts besides loopback that HTTP requests' Host and Origin may name, or '*' (default: none)
+  GEMINI_CACHE_DIR             Also keep cached replies in this directory, across restarts (default: memory only)
+  GEMINI_CACHE_MAX_ENTRIES     Cached replies kept in memory (default: 256)
+  GEMINI_LIVE_IDLE_SECS        Idle time before a gemini_followup process is stopped (default: 600)
+  GEMINI_LIVE_MAX_PROCESSES    gemini_followup processes kept running at once (default: 4)
+  GEMINI_MAX_RESPONSE_BYTES    Default 'max_response_bytes' for the gemini tool (min 1024, default: no limit)
+  GEMINI_MAX_STDOUT_BYTES      CLI output read per call before it is killed (default: 64 MiB, 0: no limit)
+  GEMINI_MAX_STDOUT_LINES      CLI output lines read per call before it is killed (default: no limit)
+  GEMINI_TEXT_RESPONSES        Set to 1 for 'key: value' text results instead of JSON (default: off)
+  GEMINI_USE_PTY               Set to 1 to run the CLI in a pseudo-terminal (needs the 'pty' feature)
+  GEMINI_PROMPT_STDIN_BYTES    Prompts larger than this are written to stdin instead of argv (default: 8192; 0 = always)
+  GEMINI_SCRATCH_ROOT          Root for per-call scratch dirs (default: <temp>/gemini-mcp-rs-scratch)
+  GEMINI_SCRATCH_MAX_AGE_SECS  Remove scratch dirs older than this (default: 86400)
+  GEMINI_SCRATCH_MAX_MB        Remove oldest scratch dirs while the total exceeds this (default: 2048)
+  GEMINI_PROTOCOL_VERSION      MCP protocol version to answer every client with, or 'auto' (same as --protocol-version)
+  GEMINI_PROMPTS_DIR           Directory of <name>.md prompt templates for MCP prompts (default: built-ins only)
+  GEMINI_REDACT                Set to 0 to stop redacting secrets from logged prompts, stderr and errors (default: on)
+  GEMINI_REDACT_PATTERNS       Extra regular expressions to redact, one per line
+  GEMINI_POLICY_MAX_PROMPT_CHARS  Reject prompts longer than this many characters
+  GEMINI_POLICY_MAX_RESPONSE_CHARS  Withhold replies longer than this many characters
+  GEMINI_POLICY_DENY           Regular expressions prompts and replies may not match, one per line (name=pattern)
+  GEMINI_POLICY_COMMAND        Program approving each prompt and reply (text on stdin, exit 0 to accept)
 
 USAGE:
   This server communicates via stdio using the Model Context Protocol (MCP).
   It should be configured in your MCP client (e.g., Claude Desktop) settings.
 
+  '--transport http [--listen 127.0.0.1:8787]' serves streamable HTTP (with SSE
+  streaming) at http://<listen>/mcp instead, so several editors or remote
+  clients can share one server. 'sse' is accepted as an alias. There is no
+  authentication: keep it on loopback or behind an authenticating proxy.
+  Requests whose Host or Origin header names anything but loopback, the listen
+  address or GEMINI_HTTP_ALLOWED_HOSTS are refused, against DNS rebinding.
+  Prometheus metrics (calls, failures, timeouts, retries, durations and running
+  CLI processes) are served at http://<listen>/metrics.
+
+  '--daemon --socket /run/user/1000/gemini-mcp.sock' listens on a UNIX domain
+  socket (a named pipe such as \\\\.\\pipe\\gemini-mcp on Windows) so every MCP
+  client on the machine shares one server, its cache and its sessions. Point
+  clients at 'gemini-mcp-rs connect --socket <path>', which relays stdio to the
+  daemon. The socket is only accessible to the user running the server.
+
+  'serve' runs the server explicitly and takes the same options. 'doctor'
+  checks the Gemini binary and version, configured credentials and write access
+  to the working, scratch, state and audit directories, exiting with 1 when a
+  check fails. 'call --prompt \"...\"' sends one prompt without an MCP client and
+  prints the result as JSON, for smoke tests.
+
+  '--config gemini-mcp.toml' loads settings from a TOML file: gemini_bin, model,
+  timeout_secs, sandbox, sandbox_backend, instructions_oversize, approval_mode,
+  max_concurrency, allowed_models, working_dir, a [model_aliases] table and a
+  [logging] table with audit_log, locale, level, format and file. Environment
+  variables override the file's values. The file is reloaded when it changes or
+  on SIGHUP, without dropping clients; working_dir and [logging] need a restart.
+
+  Diagnostic logs go to stderr (or --log-file). At GEMINI_LOG=debug every tool
+  call logs its span with the CLI's PID, exit code, output bytes and duration.
+
+  'gemini-mcp-rs report [--since 7d] [--json]' summarizes the audit log:
+  calls, tokens, estimated cost, failures by class, top models and busiest hours.
+
   Example MCP client configuration:
     {
       \"mcpServers\": {
@@ -32,24 +163,191 @@ USAGE:
 SUPPORTED PARAMETERS:
   The 'gemini' tool accepts the following parameters:
 
-  PROMPT (required)            Task instruction to send to Gemini
+  PROMPT (required)            Task instruction to send to Gemini (unless 'template' is given)
+  template                     Prompt template to send instead of PROMPT (built-in or GEMINI_PROMPTS_DIR)
+  variables                    Values for the {{name}} placeholders of 'template', or of PROMPT without one;
+                               a missing value is rejected
+  profile                      Config file profile supplying defaults for model, timeout_secs, sandbox,
+                               sandbox_backend and approval_mode
   sandbox                      Run in sandbox mode (default: false)
   SESSION_ID                   Resume an existing session (from previous response)
   return_all_messages          Return all messages including reasoning (default: false)
+  message_filter               Event types to keep in all_messages, e.g. tool_use, tool_result, error
+                               (implies return_all_messages)
+  spool_messages               Write events to a JSONL trace on disk instead of all_messages, uncapped;
+                               returns its path, gemini://traces/ URI and counts per event type
+  include_thoughts             Return the model's reasoning as thoughts, apart from agent_messages
+                               (default: false)
   model                        Model to use (default: GEMINI_FORCE_MODEL or Gemini CLI default)
-  timeout_secs                 Timeout in seconds (1-3600, default: GEMINI_DEFAULT_TIMEOUT or 600)
+  timeout_secs                 Timeout in seconds (1-3600, default: GEMINI_DEFAULT_TIMEOUT or 600). A
+                               timed out call returns its partial reply and SESSION_ID with
+                               timed_out: true
+  idle_timeout_secs            Stop after this many seconds without CLI output, with timeout_secs still
+                               capping the run (default: GEMINI_IDLE_TIMEOUT_SECS; also accepted by
+                               gemini_agent)
+  response_language            Language the reply must be written in (default: GEMINI_RESPONSE_LANGUAGE)
+  system_prompt                Instructions prepended to PROMPT in place of GEMINI.md (also accepted by
+                               gemini_agent)
+  gemini_bin                   Name from GEMINI_BIN_CHOICES (requires GEMINI_ALLOW_BIN_OVERRIDE=1)
+  diff_preview                 Run in a temporary copy and return a unified patch instead (default: false)
+  attachments                  Files to attach: @path references in the working directory, inlined text elsewhere
+                               (max 20 files of 1 MiB each; also accepted by gemini_ask and gemini_agent)
+  images                       Image paths or base64 data URIs (png, jpeg, webp, heic, heif) for the model
+                               to see (max 10 of 20 MiB each; also accepted by gemini_ask and gemini_agent)
+  include_directories          Extra directories Gemini may read, inside GEMINI_INCLUDE_ROOT (max 5; also
+                               accepted by gemini_agent)
+  env                          Environment variables for the CLI process, limited to GEMINI_ALLOWED_ENV_KEYS
+                               (also accepted by gemini_ask and gemini_agent)
+  proxy                        Proxy URL for the CLI's traffic, or off (default: GEMINI_PROXY; also
+                               accepted by gemini_ask and gemini_agent)
+  extensions                   CLI extensions to load, [] for none; limited to GEMINI_EXTENSIONS when set
+                               (also accepted by gemini_agent)
+  allowed_mcp_server_names     CLI MCP servers the agent may use, [] for none; limited to
+                               GEMINI_ALLOWED_MCP_SERVERS when set (also accepted by gemini_agent)
+  extra_args                   Additional CLI arguments, if GEMINI_ALLOW_EXTRA_ARGS is set; flags the server
+                               sets are refused (also accepted by gemini_agent)
+  max_response_bytes           Cap on agent_messages plus all_messages; later events are dropped and the
+                               response reports truncated: true (default: GEMINI_MAX_RESPONSE_BYTES or none)
+  cache_ttl_secs               Reuse a successful reply to an identical call for this long (1-2592000);
+                               cached replies are marked cached: true
+  include_stderr               Return the CLI's stderr as warnings even on success (default: false)
+  label                        Name of the agent or stage making the call, kept with the session and logged
+                               (also accepted by gemini_ask, gemini_chat, gemini_followup, gemini_agent,
+                               gemini_submit and gemini_schedule)
+  tags                         Tags for the call, added to the session's tags and logged (accepted where
+                               label is)
+  response_schema              JSON Schema the reply must satisfy; retried once when invalid, and the
+                               parsed reply is returned as structured_output
+  dry_run                      Return the resolved command line, cwd, env, timeout and GEMINI.md size
+                               instead of running (also accepted by gemini_ask and gemini_agent)
+  approval_mode                yolo, auto_edit (no shell commands) or default (read-only tools only)
+                               (default: GEMINI_APPROVAL_MODE or yolo; also accepted by gemini_ask and gemini_agent)
+  output_format                text (as written), plain (Markdown stripped) or code (fenced code blocks only)
+                               (default: text; also accepted by gemini_ask and gemini_agent)
+
+  The 'gemini_batch' tool runs up to 50 independent 'prompts' concurrently
+  (max_parallel, default 4), each in a new session, sharing model, sandbox and
+  timeout_secs. It returns one result per prompt with its own success and error.
+
+  The 'gemini_compare' tool sends one PROMPT to 2-4 'models' concurrently, each in
+  a new session, and returns the answers side by side with each model's timings,
+  usage and estimated cost, e.g. to weigh flash against pro on a task.
+
+  The 'gemini_health' tool runs 'gemini --version' and reports healthy, version,
+  path, latency_ms and the credentials the CLI would find (auth). Call it at
+  startup to catch a broken install before the first prompt.
+
+  The 'check_auth' tool sends a one-word probe prompt to verify the CLI's
+  credentials, reporting authenticated, unauthenticated or unknown, with a
+  remediation (e.g. 'gemini auth login' or GEMINI_API_KEY) on failure.
+
+  The 'budget_status' tool reports the GEMINI_BUDGET_* limits and how much of
+  the hourly ones is left. Calls beyond a budget fail with budget_exceeded.
+
+  The 'gemini_apply_patch' tool applies a patch from a diff_preview run to the
+  working directory, all or nothing, reporting conflicts if the files changed
+  since. dry_run only checks.
+
+  The 'gemini_patch' tool asks for changes as unified diffs without editing
+  files, checks each file's patch with 'git apply --check', and returns
+  per-file patches plus a combined patch for gemini_apply_patch.
+
+  The 'gemini_review' tool reviews a git ref_range (e.g. main..HEAD) or raw
+  diff text read-only, splitting large diffs by file and hunk, and returns
+  findings (file, line, severity, comment), most severe first.
+
+  The 'gemini_summarize_files' tool summarizes the files matching glob
+  'patterns' map-reduce style: files are chunked, chunks summarized
+  concurrently (max_parallel), and the results merged into per-file digests
+  and one summary.
+
+  The 'gemini_ask' tool is a stateless variant for quick one-shot questions.
+  It accepts PROMPT, model and timeout_secs, skips GEMINI.md, and returns
+  only the answer text (no SESSION_ID).
+
+  The 'gemini_chat' tool takes the conversation as 'messages', an array of
+  {role, content} objects (system, user, assistant) ending with a user message,
+  and returns the assistant's next turn and a SESSION_ID. With SESSION_ID, only
+  the messages after the last assistant message are sent.
+
+  The 'gemini_followup' tool sends PROMPT to a CLI process kept running for the
+  session (gemini --experimental-acp), so follow-ups skip the CLI's startup and
+  session resume. Omit SESSION_ID to start; idle processes are stopped after
+  GEMINI_LIVE_IDLE_SECS and the session is reloaded on the next call.
+
+  The 'gemini_embed' tool returns embedding vectors for 'texts' (1-100) from the
+  Gemini API, as float arrays or base64 little-endian f32 ('encoding'), with an
+  optional model, task_type and dimensions. It needs GEMINI_API_KEY or
+  GOOGLE_API_KEY, since the CLI has no embedding command.
+
+  The 'gemini_agent' tool targets long-running agentic work. It accepts
+  PROMPT, sandbox, SESSION_ID, model and timeout_secs (default: 1800),
+  enables checkpointing, and appends a tool_calls summary to the response.
+
+  The 'gemini_validated' tool checks the reply against a JSON 'schema' and/or
+  a regex 'pattern', retrying in the same session with the validation errors
+  as feedback up to max_retries times (0-5, default: 2).
+
+  The 'gemini_review_loop' tool answers, critiques (optionally with
+  critique_model, focused on criteria) and revises in one session, returning
+  the revised answer and the critique.
+
+  The 'gemini_submit' tool queues a prompt as a background job and returns a
+  job_id at once; 'gemini_job_status' reports its status and reply. With
+  GEMINI_STATE_DIR set, queued jobs survive a restart and jobs interrupted
+  mid-run are marked failed with a resume_hint.
+
+  The 'gemini_schedule' tool runs a prompt later as a background job, once
+  after delay_secs or repeatedly on a UTC cron schedule (e.g. '0 2 * * *').
+  'gemini_cancel_job' cancels a job that hasn't started, ending a recurring
+  schedule when given its pending run.
+
+  When a job finishes, the submitting client receives a logging notification
+  (logger 'gemini-jobs') with the job_id, status and a one-line summary.
+
+RESOURCES:
+  Recent calls are kept in memory (last 200) and exposed as MCP resources:
+  - gemini://sessions/{SESSION_ID}   Calls made in a session, as JSON
+  - gemini://invocations/{id}        A single call: tool, model, prompt
+                                     summary, duration, success, error
+
+  The 'gemini_export' tool exports these calls as JSONL chat records (one per
+  session, optionally just SESSION_ID), with secrets and emails redacted.
+
+  'list_sessions' lists every session the server created or resumed (up to
+  GEMINI_MAX_SESSIONS, saved under GEMINI_STATE_DIR) for resuming; 'delete_session'
+  removes one. Sessions idle longer than GEMINI_SESSION_TTL_SECS expire.
+  'get_transcript' returns a session's stored prompts and replies, kept on disk
+  across restarts (GEMINI_TRANSCRIPT_DIR).
+  'cancel_session' kills the running calls of a session; cancelling an MCP
+  request also kills its Gemini process at once.
 
 GEMINI.md SUPPORT:
-  If a GEMINI.md file exists in the working directory, its content will be
-  automatically prepended to the prompt as a system prompt. This allows you to
-  define project-specific instructions or context for all Gemini invocations.
-  Maximum file size: 100KB
+  GEMINI.md files in the working directory and each parent directory up to the
+  repository root (the nearest one with .git) are prepended to the prompt as a
+  system prompt, outermost first, so you can layer project-wide and
+  directory-specific instructions. A line '@include path/to/file.md' inserts
+  another file (relative to the including file, inside the root, up to 5 levels
+  deep). The files used are listed in 'instruction_files' in the response.
+  Maximum size per file: 100KB. Larger files are skipped, refused, truncated or
+  summarized (and the summary cached) per 'instructions_oversize' or
+  GEMINI_INSTRUCTIONS_OVERSIZE; responses report the strategy and the resulting
+  'instruction_bytes'.
+  The working directory is the MCP client's first file:// workspace root when it
+  reports roots (and is updated when they change), else the server's own.
 
 RETURN STRUCTURE:
-  The tool returns:
+  The tool returns a JSON object (or 'key: value' text with GEMINI_TEXT_RESPONSES=1):
   - success: boolean indicating execution status
   - SESSION_ID: unique identifier for resuming conversations
   - agent_messages: concatenated assistant response text
+  - usage, model_used, finish_reason: token counts, model and stop reason, when reported
+  - fallbacks_attempted: models given up on for rate limits before model_used
+  - tool_invocations: tools the agent ran, with input summary, status and duration
+  - timings: spawn_ms, first_event_ms, first_content_ms and total_ms of the run
+  - structured_output: the reply parsed as JSON when response_schema was given
+  - warnings: the CLI's stderr when include_stderr=true
+  - label, tags: the call's label and tags, when given
   - all_messages: (optional) complete JSON events when return_all_messages=true
   - error: error description when success=false
 
@@ -66,22 +364,395 @@ SECURITY:
 
 For more information, visit: https://github.com/missdeer/gemini-mcp-rs"
 )]
-struct Cli {}
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Without a subcommand the server runs, as with `serve`
+    #[command(flatten)]
+    serve: ServeArgs,
+    /// TOML configuration file; environment variables override its values
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Append diagnostic logs to this file instead of stderr (default: GEMINI_LOG_FILE)
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    /// How clients connect to the server
+    #[arg(long, value_enum, default_value_t = Transport::Stdio)]
+    transport: Transport,
+    /// Address to listen on with the HTTP transport
+    #[arg(long, default_value = DEFAULT_LISTEN)]
+    listen: SocketAddr,
+    /// Serve every MCP client on this machine from one process, listening on --socket
+    /// instead of stdio
+    #[arg(long, requires = "socket", conflicts_with = "transport")]
+    daemon: bool,
+    /// UNIX domain socket (named pipe on Windows, e.g. \\.\pipe\gemini-mcp) for --daemon
+    #[arg(long, value_name = "PATH", requires = "daemon")]
+    socket: Option<PathBuf>,
+    /// MCP protocol version to answer every client with, e.g. 2024-11-05, or 'auto' to
+    /// negotiate it (default: GEMINI_PROTOCOL_VERSION or auto)
+    #[arg(long, value_name = "VERSION", value_parser = parse_protocol_version_arg)]
+    protocol_version: Option<String>,
+}
+
+fn parse_protocol_version_arg(value: &str) -> Result<String, String> {
+    client::parse_protocol_version(value).map(|_| value.trim().to_string())
+}
+
+fn parse_approval_mode_arg(value: &str) -> Result<ApprovalMode, String> {
+    ApprovalMode::parse(value).ok_or_else(|| {
+        format!(
+            "unknown approval mode '{}' (yolo, auto_edit or default)",
+            value
+        )
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Transport {
+    /// A single client over stdin/stdout
+    Stdio,
+    /// Streamable HTTP with SSE streaming, for any number of clients
+    #[value(alias = "sse")]
+    Http,
+}
 
-#[tokio::main]
-async fn main() -> Result<()> {
+#[derive(Subcommand)]
+enum Command {
+    /// Run the MCP server (what happens without a subcommand)
+    Serve(ServeArgs),
+    /// Check the Gemini CLI binary and version, credentials, and write access to the
+    /// directories the server uses
+    Doctor {
+        /// Print JSON instead of a list
+        #[arg(long)]
+        json: bool,
+    },
+    /// Send one prompt to Gemini without an MCP client and print the result as JSON
+    Call(CallArgs),
+    /// Summarize the audit log written when GEMINI_AUDIT_LOG is set
+    Report {
+        /// Only include calls newer than this, e.g. 30m, 12h, 7d or 2w
+        #[arg(long)]
+        since: Option<String>,
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+        /// Audit log to read (default: GEMINI_AUDIT_LOG)
+        #[arg(long)]
+        log: Option<PathBuf>,
+    },
+    /// Relay stdio to a server started with --daemon, for MCP clients that spawn one
+    Connect {
+        /// Socket the daemon listens on
+        #[arg(long, value_name = "PATH")]
+        socket: PathBuf,
+    },
+}
+
+#[derive(Args)]
+struct CallArgs {
+    /// Prompt to send
+    #[arg(long)]
+    prompt: String,
+    /// Model or model alias (default: GEMINI_FORCE_MODEL or the CLI's default)
+    #[arg(long)]
+    model: Option<String>,
+    /// Continue this session instead of starting a new one
+    #[arg(long, value_name = "ID")]
+    session_id: Option<String>,
+    /// Timeout in seconds, 1-3600 (default: GEMINI_DEFAULT_TIMEOUT or 600)
+    #[arg(long, value_name = "SECS")]
+    timeout_secs: Option<u64>,
+    /// Run the CLI sandboxed
+    #[arg(long)]
+    sandbox: bool,
+    /// yolo, auto_edit or default (default: GEMINI_APPROVAL_MODE or yolo)
+    #[arg(long, value_name = "MODE", value_parser = parse_approval_mode_arg)]
+    approval_mode: Option<ApprovalMode>,
+    /// Directory to run Gemini in (default: the current directory)
+    #[arg(long, value_name = "PATH")]
+    working_dir: Option<PathBuf>,
+    /// Include every JSON event the CLI emitted in all_messages
+    #[arg(long)]
+    all_messages: bool,
+}
+
+async fn run_doctor(json: bool) -> Result<()> {
+    let diagnosis = doctor::diagnose().await;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diagnosis)?);
+    } else {
+        print!("{}", doctor::render(&diagnosis));
+    }
+    if !diagnosis.ok {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn run_call(args: CallArgs) -> Result<()> {
+    let opts = Options {
+        prompt: args.prompt,
+        session_id: args.session_id,
+        model: args.model.map(|m| gemini::resolve_model_alias(m.trim())),
+        timeout_secs: args.timeout_secs,
+        sandbox: args.sandbox,
+        approval_mode: args.approval_mode,
+        working_dir: args.working_dir,
+        return_all_messages: args.all_messages,
+        ..Default::default()
+    };
+    let result = gemini::run(opts).await?;
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&server::result_json(&result))?
+    );
+    if !result.success {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_report(since: Option<String>, json: bool, log: Option<PathBuf>) -> Result<()> {
+    let path = log
+        .or_else(audit::configured_path)
+        .context("No audit log configured: set GEMINI_AUDIT_LOG or pass --log <path>")?;
+    let cutoff = match since {
+        Some(value) => {
+            let window = report::parse_since(&value).map_err(anyhow::Error::msg)?;
+            let start = SystemTime::now()
+                .checked_sub(window)
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            Some(format_timestamp(start))
+        }
+        None => None,
+    };
+
+    let records = audit::read_records(&path)
+        .with_context(|| format!("Failed to read audit log {}", path.display()))?;
+    let summary = report::build(&records, cutoff.as_deref(), &PricingTable::from_env());
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    } else {
+        print!("{}", report::render_table(&summary));
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
     // Parse command-line arguments (this will handle -h/--help and --version)
-    let _cli = Cli::parse();
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve(cli.serve));
+
+    // The environment is only changed here, while the process has a single thread
+    if let Some(path) = &cli.config {
+        Config::load(path)?.install()?;
+    }
+    if let Command::Serve(ServeArgs {
+        protocol_version: Some(version),
+        ..
+    }) = &command
+    {
+        std::env::set_var(client::ENV_PROTOCOL_VERSION, version);
+    }
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start the async runtime")?
+        .block_on(run(command, cli.config, cli.log_file))
+}
+
+async fn run(command: Command, config: Option<PathBuf>, log_file: Option<PathBuf>) -> Result<()> {
+    let log_file = log_file.or_else(|| std::env::var_os(logging::ENV_LOG_FILE).map(PathBuf::from));
+    logging::init(log_file.as_deref())?;
+
+    match command {
+        Command::Serve(args) => serve(args, config).await,
+        Command::Doctor { json } => run_doctor(json).await,
+        Command::Call(args) => run_call(args).await,
+        Command::Report { since, json, log } => run_report(since, json, log),
+        Command::Connect { socket } => daemon::relay(&socket).await,
+    }
+}
+
+/// Run the MCP server on the transport `args` selects until the client leaves or the
+/// process is told to stop
+async fn serve(args: ServeArgs, config: Option<PathBuf>) -> Result<()> {
+    if let Ok(version) = std::env::var(client::ENV_PROTOCOL_VERSION) {
+        client::parse_protocol_version(&version).map_err(anyhow::Error::msg)?;
+    }
+    if let Some(version) = client::pinned_protocol_version() {
+        tracing::info!(
+            "Answering every client with MCP protocol version {}",
+            version
+        );
+    }
+
+    if args.transport == Transport::Http {
+        return serve_http(args.listen, config).await;
+    }
+    if let (true, Some(socket)) = (args.daemon, args.socket) {
+        return serve_daemon(socket, config).await;
+    }
 
     // Create an instance of our gemini server with adaptive transport
     // that auto-detects between JSONL and LSP-style message framing
-    let service = GeminiServer::new()
-        .serve(AdaptiveStdio::new())
+    let server = GeminiServer::new();
+    // Run any background jobs restored from GEMINI_STATE_DIR and start scheduled ones when due
+    server.start_scheduler();
+    watch_config(config, &server);
+    let handle = server.clone();
+    let service = server.serve(AdaptiveStdio::new()).await.inspect_err(|e| {
+        tracing::error!("serving error: {:?}", e);
+    })?;
+
+    tokio::select! {
+        result = service.waiting() => {
+            result?;
+        }
+        _ = shutdown_signal() => {
+            tracing::info!("Shutting down...");
+            handle.drain(shutdown_grace()).await;
+        }
+    }
+    Ok(())
+}
+
+/// Resolves on the first SIGINT or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+}
+
+/// How long running calls get to finish on shutdown, from GEMINI_SHUTDOWN_GRACE_SECS
+fn shutdown_grace() -> Duration {
+    std::env::var(ENV_SHUTDOWN_GRACE_SECS)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .map_or(DEFAULT_SHUTDOWN_GRACE, Duration::from_secs)
+}
+
+/// Reload the configuration file, if one was given, whenever it changes
+fn watch_config(path: Option<PathBuf>, server: &GeminiServer) {
+    if let Some(path) = path {
+        let server = server.clone();
+        tokio::spawn(config::watch(path, move |_| server.reload_settings()));
+    }
+}
+
+/// Serve MCP on a local socket until SIGINT or SIGTERM. Every connection gets a handle
+/// to the same server, so the cache, sessions, history and background jobs are shared.
+/// On shutdown, new connections are refused and running calls are drained.
+async fn serve_daemon(socket: PathBuf, config: Option<PathBuf>) -> Result<()> {
+    let server = GeminiServer::new().with_transport(daemon::DAEMON_TRANSPORT);
+    server.start_scheduler();
+    watch_config(config, &server);
+    let handle = server.clone();
+    tokio::select! {
+        result = daemon::serve(&socket, server) => result?,
+        _ = shutdown_signal() => tracing::info!("Shutting down..."),
+    }
+    handle.drain(shutdown_grace()).await;
+    Ok(())
+}
+
+/// Serve MCP over streamable HTTP until SIGINT or SIGTERM. Every client session gets a
+/// handle to the same server, so history, quotas and background jobs are shared.
+/// On shutdown, new connections are refused and running calls are drained.
+async fn serve_http(listen: SocketAddr, config: Option<PathBuf>) -> Result<()> {
+    let server = GeminiServer::new().with_transport(HTTP_TRANSPORT);
+    server.start_scheduler();
+    watch_config(config, &server);
+    let metrics = server.metrics();
+    let handle = server.clone();
+
+    let shutdown = CancellationToken::new();
+    let service = StreamableHttpService::new(
+        move || Ok(server.for_new_client()),
+        LocalSessionManager::default().into(),
+        StreamableHttpServerConfig::default(),
+    );
+    let router = axum::Router::new()
+        .route(
+            METRICS_PATH,
+            axum::routing::get(move || {
+                let body = metrics.render();
+                async move {
+                    (
+                        [(
+                            axum::http::header::CONTENT_TYPE,
+                            "text/plain; version=0.0.4; charset=utf-8",
+                        )],
+                        body,
+                    )
+                }
+            }),
+        )
+        .nest_service(MCP_PATH, service)
+        .layer(axum::middleware::from_fn_with_state(
+            std::sync::Arc::new(origin::AllowedHosts::from_env(listen)),
+            origin::check,
+        ));
+    let listener = tokio::net::TcpListener::bind(listen)
         .await
-        .inspect_err(|e| {
-            eprintln!("serving error: {:?}", e);
-        })?;
+        .with_context(|| format!("Failed to listen on {}", listen))?;
+    if !listen.ip().is_loopback() {
+        tracing::warn!(
+            "Listening on {} without authentication; anyone who can reach it can run Gemini",
+            listen
+        );
+    }
+    tracing::info!(
+        "Serving MCP at http://{}{} and metrics at http://{}{}",
+        listen,
+        MCP_PATH,
+        listen,
+        METRICS_PATH
+    );
+
+    let signal = shutdown.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        tracing::info!("Shutting down...");
+        signal.cancel();
+    });
 
-    service.waiting().await?;
+    let serve = axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+        .into_future();
+    // Open SSE streams can keep a graceful shutdown waiting forever, so once the calls
+    // have drained, connections only get a little longer to deliver their replies
+    let drain = async {
+        shutdown.cancelled().await;
+        handle.drain(shutdown_grace()).await;
+        tokio::time::sleep(CLOSE_GRACE).await;
+    };
+    tokio::select! {
+        result = serve => result.context("HTTP server failed")?,
+        _ = drain => tracing::warn!("Connections still open after shutdown; exiting"),
+    }
+    // Background jobs run without a connection, so the server may have stopped first
+    handle.drain(shutdown_grace()).await;
     Ok(())
 }
@@ -0,0 +1,535 @@
+//! User-facing message catalog.
+//!
+//! Error templates and human-readable response labels live here so they can be
+//! translated. The locale is selected once per process from `GEMINI_MCP_LOCALE`
+//! (`en`, `zh-CN`); unknown values fall back to English.
+//!
+//! Machine-readable response keys (`success`, `SESSION_ID`, `agent_messages`, ...)
+//! are intentionally not translated, since clients parse them.
+
+use std::sync::OnceLock;
+
+pub(crate) const ENV_LOCALE: &str = "GEMINI_MCP_LOCALE";
+
+/// Supported message locales
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    ZhCn,
+}
+
+impl Locale {
+    /// Parse a locale tag such as `en`, `en_US.UTF-8`, `zh-CN` or `zh`
+    pub fn parse(tag: &str) -> Option<Self> {
+        let lang = tag
+            .trim()
+            .split(['.', '@'])
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase()
+            .replace('_', "-");
+        match lang.as_str() {
+            "en" | "en-us" | "en-gb" | "c" | "posix" => Some(Locale::En),
+            "zh" | "zh-cn" | "zh-hans" | "zh-sg" => Some(Locale::ZhCn),
+            _ => None,
+        }
+    }
+
+    pub fn catalog(self) -> &'static Catalog {
+        match self {
+            Locale::En => &EN,
+            Locale::ZhCn => &ZH_CN,
+        }
+    }
+}
+
+/// Message templates. Positional placeholders are written `{0}`, `{1}`, ...
+#[derive(Debug)]
+pub struct Catalog {
+    pub prompt_required: &'static str,
+    pub model_override_empty: &'static str,
+    pub model_not_allowed: &'static str,
+    pub profile_not_found: &'static str,
+    pub timeout_out_of_range: &'static str,
+    pub idle_timeout_out_of_range: &'static str,
+    pub response_language_invalid: &'static str,
+    pub too_many_attachments: &'static str,
+    pub batch_size_invalid: &'static str,
+    pub batch_parallelism_invalid: &'static str,
+    pub compare_models_invalid: &'static str,
+    pub compare_models_duplicate: &'static str,
+    pub compare_model_given: &'static str,
+    pub attachment_not_found: &'static str,
+    pub attachment_too_large: &'static str,
+    pub attachment_not_text: &'static str,
+    pub path_outside_workspace: &'static str,
+    pub too_many_images: &'static str,
+    pub image_not_found: &'static str,
+    pub image_too_large: &'static str,
+    pub image_type_unsupported: &'static str,
+    pub image_data_invalid: &'static str,
+    pub image_stage_failed: &'static str,
+    pub too_many_include_directories: &'static str,
+    pub include_directory_not_found: &'static str,
+    pub include_directory_outside_root: &'static str,
+    pub bin_override_disabled: &'static str,
+    pub bin_choice_unknown: &'static str,
+    pub env_override_disabled: &'static str,
+    pub extra_args_disabled: &'static str,
+    pub extra_arg_reserved: &'static str,
+    pub system_prompt_too_long: &'static str,
+    pub approval_mode_invalid: &'static str,
+    pub proxy_invalid: &'static str,
+    pub passthrough_name_invalid: &'static str,
+    pub passthrough_not_permitted: &'static str,
+    pub label_invalid: &'static str,
+    pub tags_invalid: &'static str,
+    pub sandbox_backend_invalid: &'static str,
+    pub sandbox_backend_unavailable: &'static str,
+    pub sandbox_image_unsupported: &'static str,
+    pub instructions_oversize_invalid: &'static str,
+    pub output_format_invalid: &'static str,
+    pub instructions_too_large: &'static str,
+    pub chat_messages_empty: &'static str,
+    pub chat_too_many_messages: &'static str,
+    pub chat_last_not_user: &'static str,
+    pub chat_message_empty: &'static str,
+    pub embed_texts_empty: &'static str,
+    pub embed_too_many_texts: &'static str,
+    pub embed_text_empty: &'static str,
+    pub embed_no_api_key: &'static str,
+    pub embed_task_type_invalid: &'static str,
+    pub embed_encoding_invalid: &'static str,
+    pub env_key_not_allowed: &'static str,
+    pub max_response_bytes_invalid: &'static str,
+    pub cache_ttl_invalid: &'static str,
+    pub prompt_not_found: &'static str,
+    pub prompt_argument_missing: &'static str,
+    pub template_with_prompt: &'static str,
+    pub template_variable_missing: &'static str,
+    pub template_invalid: &'static str,
+    pub transcript_not_found: &'static str,
+    pub execution_failed: &'static str,
+    pub throttled: &'static str,
+    pub client_rate_limited: &'static str,
+    pub budget_call_tokens: &'static str,
+    pub budget_hourly_secs: &'static str,
+    pub budget_hourly_tokens: &'static str,
+    pub server_busy: &'static str,
+    pub shutting_down: &'static str,
+    pub cancelled: &'static str,
+    pub no_running_call: &'static str,
+    pub resource_not_found: &'static str,
+    pub job_not_found: &'static str,
+    pub job_interrupted: &'static str,
+    pub job_resume_hint: &'static str,
+    pub job_resume_session_hint: &'static str,
+    pub job_not_cancellable: &'static str,
+    pub schedule_required: &'static str,
+    pub schedule_delay_out_of_range: &'static str,
+    pub schedule_invalid: &'static str,
+    pub schedule_never_matches: &'static str,
+    pub validation_required: &'static str,
+    pub validation_invalid: &'static str,
+    pub validation_retries_out_of_range: &'static str,
+    pub validation_failed: &'static str,
+    pub diff_preview_session: &'static str,
+    pub diff_preview_failed: &'static str,
+    pub no_changes: &'static str,
+    pub patch_required: &'static str,
+    pub patch_conflict: &'static str,
+    pub review_source_required: &'static str,
+    pub review_ref_range_invalid: &'static str,
+    pub review_diff_failed: &'static str,
+    pub review_diff_too_large: &'static str,
+    pub summarize_patterns_invalid: &'static str,
+    pub summarize_glob_invalid: &'static str,
+    pub summarize_no_files: &'static str,
+    pub summarize_too_many_files: &'static str,
+    pub summarize_too_large: &'static str,
+    pub auth_fix: &'static str,
+    pub auth_remediation_api_key: &'static str,
+    pub auth_remediation_vertex_ai: &'static str,
+    pub auth_remediation_oauth: &'static str,
+    pub auth_remediation_env_file: &'static str,
+    pub auth_remediation_none: &'static str,
+    pub session_not_recorded: &'static str,
+    pub session_not_found: &'static str,
+    pub session_id_invalid: &'static str,
+    pub session_expired: &'static str,
+    pub policy_violation: &'static str,
+    pub session_in_use: &'static str,
+    pub unknown_error: &'static str,
+    pub events_captured: &'static str,
+    pub full_event_log: &'static str,
+    pub events_before_failure: &'static str,
+    pub events_spooled: &'static str,
+    pub missing_session_id: &'static str,
+    pub missing_agent_messages: &'static str,
+    pub missing_any_messages: &'static str,
+    pub timed_out: &'static str,
+    pub idle_timed_out: &'static str,
+    pub exit_code_failure: &'static str,
+    pub no_valid_json: &'static str,
+    pub stdout_limit_exceeded: &'static str,
+}
+
+static EN: Catalog = Catalog {
+    prompt_required: "PROMPT is required and must be a non-empty, non-whitespace string",
+    model_override_empty:
+        "Model overrides must be explicitly requested as a non-empty, non-whitespace string",
+    model_not_allowed: "Model '{0}' is not allowed on this server. Permitted: {1}",
+    profile_not_found: "Unknown profile '{0}'. Configured profiles: {1}",
+    timeout_out_of_range: "timeout_secs must be between {0} and {1} seconds",
+    idle_timeout_out_of_range: "idle_timeout_secs must be between {0} and {1} seconds",
+    response_language_invalid:
+        "response_language must be a single-line language name of at most {0} characters",
+    too_many_attachments: "At most {0} attachments are allowed per call",
+    batch_size_invalid: "prompts must contain between 1 and {0} prompts",
+    batch_parallelism_invalid: "max_parallel must be between 1 and {0}",
+    compare_models_invalid: "models must list between {0} and {1} models",
+    compare_models_duplicate: "models must be different, but '{0}' is listed more than once",
+    compare_model_given: "gemini_compare takes its models in 'models', not 'model'",
+    attachment_not_found: "Attachment '{0}' does not exist or is not a file",
+    attachment_too_large: "Attachment '{0}' is {1} bytes, larger than the {2}-byte limit",
+    attachment_not_text: "Attachment '{0}' is outside the working directory and is not a text file",
+    path_outside_workspace: "'{0}' is outside the working directory and GEMINI_INCLUDE_ROOT",
+    too_many_images: "At most {0} images are allowed per call",
+    image_not_found: "Image '{0}' does not exist or is not a file",
+    image_too_large: "Image '{0}' is {1} bytes, larger than the {2}-byte limit",
+    image_type_unsupported: "Image '{0}' is not a supported type (png, jpeg, webp, heic, heif)",
+    image_data_invalid: "{0} is not a base64 data URI of a supported image type (png, jpeg, webp, heic, heif)",
+    image_stage_failed: "Could not write {0} to a temporary file: {1}",
+    too_many_include_directories: "At most {0} include directories are allowed per call",
+    include_directory_not_found: "Include directory '{0}' does not exist or is not a directory",
+    include_directory_outside_root: "Include directory '{0}' is outside the allowed root {1}",
+    bin_override_disabled: "gemini_bin is disabled on this server (set GEMINI_ALLOW_BIN_OVERRIDE=1 to enable)",
+    bin_choice_unknown: "Unknown gemini_bin '{0}'. Available: {1}",
+    approval_mode_invalid: "Unknown approval_mode '{0}' (supported: yolo, auto_edit, default)",
+    proxy_invalid: "Invalid proxy '{0}': expected a URL like http://proxy.internal:8080 (http, https, socks4, socks5 or socks5h), or \"off\"",
+    passthrough_name_invalid: "Invalid {0} entry '{1}': names may not be empty, start with '-' or contain spaces or commas",
+    passthrough_not_permitted: "{0} may only name {1} on this server; '{2}' is not permitted",
+    label_invalid: "label must be at most {0} characters, without control characters",
+    tags_invalid: "tags must be at most {0} non-empty entries of up to {1} characters, without control characters",
+    sandbox_backend_invalid: "Unknown sandbox_backend '{0}' (supported: docker, podman, sandbox-exec)",
+    sandbox_backend_unavailable: "Sandbox backend '{0}' is not available on this host (sandbox-exec needs macOS; docker and podman must be on PATH)",
+    sandbox_image_unsupported: "sandbox_image only applies to the docker and podman backends",
+    output_format_invalid: "Unknown output_format '{0}' (supported: text, plain, code)",
+    instructions_oversize_invalid: "Unknown instructions_oversize '{0}' (supported: skip, fail, truncate, summarize)",
+    chat_messages_empty: "messages must contain at least one message",
+    chat_too_many_messages: "messages may contain at most {0} messages",
+    chat_last_not_user: "The last message must have role 'user'",
+    chat_message_empty: "messages[{0}] has empty content",
+    embed_texts_empty: "texts must contain at least one text",
+    embed_too_many_texts: "texts holds more than {0} texts",
+    embed_text_empty: "texts[{0}] is empty",
+    embed_no_api_key: "Embeddings need a Gemini API key: set GEMINI_API_KEY or GOOGLE_API_KEY",
+    embed_task_type_invalid: "Unknown task_type '{0}' (supported: retrieval_query, retrieval_document, semantic_similarity, classification, clustering, question_answering, fact_verification, code_retrieval_query)",
+    embed_encoding_invalid: "Unknown encoding '{0}' (supported: float, base64)",
+    instructions_too_large: "Instruction file {0} exceeds the {1} byte GEMINI.md limit; shorten it or choose another instructions_oversize strategy (skip, truncate, summarize)",
+    env_override_disabled: "env is disabled on this server (set GEMINI_ALLOWED_ENV_KEYS to the variables callers may set)",
+    env_key_not_allowed: "Environment variable '{0}' may not be set per call. Permitted: {1}",
+    extra_args_disabled: "extra_args is disabled on this server (set GEMINI_ALLOW_EXTRA_ARGS=1 to enable)",
+    extra_arg_reserved: "extra_args may not contain {0}, which the server sets itself; use the matching parameter instead",
+    system_prompt_too_long: "system_prompt may be at most {0} bytes",
+    max_response_bytes_invalid: "max_response_bytes must be at least {0}",
+    cache_ttl_invalid: "cache_ttl_secs must be between 1 and {0}",
+    prompt_not_found: "Unknown prompt '{0}'",
+    prompt_argument_missing: "Missing required argument '{0}' for prompt '{1}'",
+    template_with_prompt: "Give either PROMPT or template, not both",
+    template_variable_missing: "Missing variable '{0}' for template '{1}'",
+    template_invalid: "Invalid template: {0}",
+    transcript_not_found: "No transcript stored for session '{0}'",
+    execution_failed: "Failed to execute gemini: {0}",
+    throttled: "Throttled: the quota for model '{0}' would be exceeded, retry in {1}s",
+    client_rate_limited: "Rate limited: this client may start {0} Gemini calls per minute, retry in {1}s",
+    budget_call_tokens: "Budget exceeded: the prompt is about {0} tokens, over the per-call budget of {1}",
+    budget_hourly_secs: "Budget exceeded: calls ran for {0}s of the hourly budget of {1}s, retry in {2}s",
+    budget_hourly_tokens: "Budget exceeded: calls used {0} of the hourly budget of {1} tokens, retry in {2}s",
+    server_busy: "Server busy: {0} Gemini calls are already running, retry shortly",
+    shutting_down: "The server is shutting down and no longer accepts calls",
+    cancelled: "Cancelled: the Gemini process was stopped",
+    no_running_call: "No running call in session '{0}'",
+    resource_not_found: "Unknown resource: {0}",
+    job_not_found: "Unknown job_id '{0}'",
+    job_interrupted: "Interrupted because the server stopped while the job was running",
+    job_resume_hint: "Submit the job again to rerun it",
+    job_resume_session_hint: "Submit the job again, or continue the conversation with SESSION_ID {0}",
+    job_not_cancellable: "Job '{0}' is {1}; only queued jobs can be cancelled",
+    schedule_required: "Provide exactly one of delay_secs or cron",
+    schedule_delay_out_of_range: "delay_secs must be at most {0} seconds",
+    schedule_invalid: "Invalid cron expression '{0}': {1}",
+    schedule_never_matches: "Cron expression '{0}' never matches a date",
+    validation_required: "Provide a schema, a pattern, or both to validate the reply against",
+    validation_invalid: "Invalid validation rule: {0}",
+    validation_retries_out_of_range: "max_retries must be between 0 and {0}",
+    validation_failed: "Reply still failed validation after {0} attempts (SESSION_ID: {1}): {2}",
+    diff_preview_session: "diff_preview cannot be combined with SESSION_ID: previews run in a temporary copy of the workspace",
+    diff_preview_failed: "Diff preview failed: {0}",
+    no_changes: "(no changes)",
+    patch_required: "patch is required and must be a non-empty string",
+    patch_conflict: "Patch does not apply cleanly, so nothing was changed:\n{0}",
+    review_source_required: "Provide exactly one of ref_range or diff",
+    review_ref_range_invalid: "Invalid ref_range '{0}': expected a git revision range such as main..HEAD",
+    review_diff_failed: "git diff {0} failed: {1}",
+    review_diff_too_large: "The diff is {0} bytes and would take {1} review calls, more than the limit of {2}; review a narrower range",
+    summarize_patterns_invalid: "patterns must contain between 1 and {0} glob patterns",
+    summarize_glob_invalid: "Invalid glob pattern '{0}': patterns must be relative to the working directory",
+    summarize_no_files: "No text files in the working directory match {0}",
+    summarize_too_many_files: "{0} files match, more than the limit of {1}; use narrower patterns",
+    summarize_too_large: "The matching files hold more than {0} bytes; use narrower patterns",
+    auth_fix: "To fix: {0}",
+    auth_remediation_api_key: "The API key was rejected. Check GEMINI_API_KEY (or GOOGLE_API_KEY) in the server's environment, or create a new key at https://aistudio.google.com/apikey.",
+    auth_remediation_vertex_ai: "The Vertex AI credentials were rejected. Check GOOGLE_CLOUD_PROJECT, GOOGLE_CLOUD_LOCATION and GOOGLE_API_KEY, or run `gcloud auth application-default login`.",
+    auth_remediation_oauth: "The cached Google login is missing or expired. Run `gemini auth login` (or start `gemini` in a terminal and sign in again), or set GEMINI_API_KEY in the server's environment.",
+    auth_remediation_env_file: "The credentials in ~/.gemini/.env were rejected. Check the GEMINI_API_KEY it sets, or run `gemini auth login`.",
+    auth_remediation_none: "No Gemini credentials were found. Run `gemini auth login` (or start `gemini` in a terminal and sign in with Google), or set GEMINI_API_KEY in the server's environment.",
+    session_not_recorded: "No successful calls recorded for session '{0}'",
+    session_not_found: "Unknown SESSION_ID '{0}'",
+    session_id_invalid: "Invalid SESSION_ID '{0}': expected the id returned by an earlier call (up to 128 letters, digits, '-' or '_')",
+    session_expired: "Session '{0}' no longer exists; it may have expired or been deleted. Call again without SESSION_ID to start a new session",
+    policy_violation: "Rejected by policy rule '{0}' ({1}): {2}",
+    session_in_use: "SESSION_ID '{0}' already has a call running; wait for it to finish or stop it with cancel_session",
+    unknown_error: "Unknown error",
+    events_captured: "{0} events captured",
+    full_event_log: "Full event log:",
+    events_before_failure: "Captured {0} events before failure:",
+    events_spooled: "{0} events spooled to {1} ({2})",
+    missing_session_id: "Failed to get `SESSION_ID` from the gemini session.",
+    missing_agent_messages: "Failed to get `agent_messages` from the gemini session.\nYou can try to set `return_all_messages` to `True` to get the full information.",
+    missing_any_messages: "Failed to get any messages from the gemini session.",
+    timed_out: "Gemini command timed out after {0} seconds",
+    idle_timed_out: "Gemini command was stopped after {0} seconds without output",
+    exit_code_failure: "gemini command failed with exit code: {0}",
+    no_valid_json: "No valid JSON output received from gemini CLI.",
+    stdout_limit_exceeded: "Gemini was stopped after exceeding GEMINI_MAX_STDOUT_BYTES or GEMINI_MAX_STDOUT_LINES; its output is truncated.",
+};
+
+static ZH_CN: Catalog = Catalog {
+    prompt_required: "PROMPT 为必填项，且不能为空或仅包含空白字符",
+    model_override_empty: "指定模型时必须提供非空、非空白的模型名称",
+    model_not_allowed: "此服务器不允许使用模型 '{0}'。允许的模型：{1}",
+    profile_not_found: "未知的配置档 '{0}'。已配置的配置档：{1}",
+    timeout_out_of_range: "timeout_secs 必须在 {0} 到 {1} 秒之间",
+    idle_timeout_out_of_range: "idle_timeout_secs 必须在 {0} 到 {1} 秒之间",
+    response_language_invalid: "response_language 必须是不超过 {0} 个字符的单行语言名称",
+    too_many_attachments: "每次调用最多允许 {0} 个附件",
+    batch_size_invalid: "prompts 必须包含 1 到 {0} 个提示",
+    batch_parallelism_invalid: "max_parallel 必须在 1 到 {0} 之间",
+    compare_models_invalid: "models 必须包含 {0} 到 {1} 个模型",
+    compare_models_duplicate: "models 中的模型不能重复，'{0}' 出现了多次",
+    compare_model_given: "gemini_compare 的模型应通过 'models' 指定，而不是 'model'",
+    attachment_not_found: "附件 '{0}' 不存在或不是文件",
+    attachment_too_large: "附件 '{0}' 大小为 {1} 字节，超过 {2} 字节的上限",
+    attachment_not_text: "附件 '{0}' 位于工作目录之外，且不是文本文件",
+    path_outside_workspace: "'{0}' 不在工作目录或 GEMINI_INCLUDE_ROOT 之内",
+    too_many_images: "每次调用最多允许 {0} 张图片",
+    image_not_found: "图片 '{0}' 不存在或不是文件",
+    image_too_large: "图片 '{0}' 大小为 {1} 字节，超过 {2} 字节的上限",
+    image_type_unsupported: "图片 '{0}' 的类型不受支持（png、jpeg、webp、heic、heif）",
+    image_data_invalid: "{0} 不是受支持图片类型（png、jpeg、webp、heic、heif）的 base64 data URI",
+    image_stage_failed: "无法将 {0} 写入临时文件：{1}",
+    too_many_include_directories: "每次调用最多允许包含 {0} 个目录",
+    include_directory_not_found: "包含目录 '{0}' 不存在或不是目录",
+    include_directory_outside_root: "包含目录 '{0}' 位于允许的根目录 {1} 之外",
+    bin_override_disabled: "此服务器已禁用 gemini_bin（设置 GEMINI_ALLOW_BIN_OVERRIDE=1 以启用）",
+    bin_choice_unknown: "未知的 gemini_bin '{0}'。可用选项：{1}",
+    approval_mode_invalid: "未知的 approval_mode '{0}'（支持：yolo、auto_edit、default）",
+    proxy_invalid: "无效的代理 '{0}'：应为形如 http://proxy.internal:8080 的 URL（http、https、socks4、socks5 或 socks5h），或 \"off\"",
+    passthrough_name_invalid: "无效的 {0} 条目 '{1}'：名称不能为空、不能以 '-' 开头，也不能包含空格或逗号",
+    passthrough_not_permitted: "此服务器上 {0} 只能指定 {1}；不允许 '{2}'",
+    label_invalid: "label 最多 {0} 个字符，且不能包含控制字符",
+    tags_invalid: "tags 最多 {0} 个非空条目，每个最多 {1} 个字符，且不能包含控制字符",
+    sandbox_backend_invalid: "未知的 sandbox_backend '{0}'（支持：docker、podman、sandbox-exec）",
+    sandbox_backend_unavailable: "沙箱后端 '{0}' 在此主机上不可用（sandbox-exec 需要 macOS；docker 和 podman 须在 PATH 中）",
+    sandbox_image_unsupported: "sandbox_image 仅适用于 docker 和 podman 后端",
+    output_format_invalid: "未知的 output_format '{0}'（支持：text、plain、code）",
+    instructions_oversize_invalid: "未知的 instructions_oversize '{0}'（支持：skip、fail、truncate、summarize）",
+    chat_messages_empty: "messages 至少需要包含一条消息",
+    chat_too_many_messages: "messages 最多只能包含 {0} 条消息",
+    chat_last_not_user: "最后一条消息的 role 必须是 'user'",
+    chat_message_empty: "messages[{0}] 的内容为空",
+    embed_texts_empty: "texts 至少需要包含一段文本",
+    embed_too_many_texts: "texts 超过了 {0} 段文本",
+    embed_text_empty: "texts[{0}] 为空",
+    embed_no_api_key: "生成嵌入向量需要 Gemini API 密钥：请设置 GEMINI_API_KEY 或 GOOGLE_API_KEY",
+    embed_task_type_invalid: "未知的 task_type '{0}'（支持：retrieval_query、retrieval_document、semantic_similarity、classification、clustering、question_answering、fact_verification、code_retrieval_query）",
+    embed_encoding_invalid: "未知的 encoding '{0}'（支持：float、base64）",
+    instructions_too_large: "指令文件 {0} 超过 {1} 字节的 GEMINI.md 限制；请缩短该文件或选择其他 instructions_oversize 策略（skip、truncate、summarize）",
+    env_override_disabled: "此服务器已禁用 env（将 GEMINI_ALLOWED_ENV_KEYS 设置为允许调用方设置的变量）",
+    env_key_not_allowed: "不允许按调用设置环境变量 '{0}'。允许的变量：{1}",
+    extra_args_disabled: "此服务器已禁用 extra_args（设置 GEMINI_ALLOW_EXTRA_ARGS=1 以启用）",
+    extra_arg_reserved: "extra_args 不能包含由服务器自行设置的 {0}；请改用对应的参数",
+    system_prompt_too_long: "system_prompt 不能超过 {0} 字节",
+    max_response_bytes_invalid: "max_response_bytes 不能小于 {0}",
+    cache_ttl_invalid: "cache_ttl_secs 必须介于 1 到 {0} 之间",
+    prompt_not_found: "未知的提示词模板 '{0}'",
+    prompt_argument_missing: "提示词模板 '{1}' 缺少必需参数 '{0}'",
+    template_with_prompt: "PROMPT 与 template 只能指定其一",
+    template_variable_missing: "模板 '{1}' 缺少变量 '{0}'",
+    template_invalid: "无效的模板：{0}",
+    transcript_not_found: "未找到会话 '{0}' 的记录",
+    execution_failed: "执行 gemini 失败：{0}",
+    throttled: "已限流：模型 '{0}' 的配额将被超出，请在 {1} 秒后重试",
+    client_rate_limited: "已限流：此客户端每分钟最多发起 {0} 次 Gemini 调用，请在 {1} 秒后重试",
+    budget_call_tokens: "超出预算：提示词约 {0} 个 token，超过单次调用预算 {1}",
+    budget_hourly_secs: "超出预算：调用已运行 {0} 秒，每小时预算为 {1} 秒，请在 {2} 秒后重试",
+    budget_hourly_tokens: "超出预算：调用已使用 {0} 个 token，每小时预算为 {1}，请在 {2} 秒后重试",
+    server_busy: "服务器繁忙：已有 {0} 个 Gemini 调用在运行，请稍后重试",
+    shutting_down: "服务器正在关闭，不再接受调用",
+    cancelled: "已取消：Gemini 进程已停止",
+    no_running_call: "会话 '{0}' 中没有正在运行的调用",
+    resource_not_found: "未知资源：{0}",
+    job_not_found: "未知的 job_id '{0}'",
+    job_interrupted: "任务运行期间服务器已停止，任务被中断",
+    job_resume_hint: "重新提交任务即可再次运行",
+    job_resume_session_hint: "重新提交任务，或使用 SESSION_ID {0} 继续对话",
+    job_not_cancellable: "任务 '{0}' 的状态为 {1}，只能取消排队中的任务",
+    schedule_required: "必须且只能提供 delay_secs 或 cron 之一",
+    schedule_delay_out_of_range: "delay_secs 不能超过 {0} 秒",
+    schedule_invalid: "无效的 cron 表达式 '{0}'：{1}",
+    schedule_never_matches: "cron 表达式 '{0}' 不会匹配任何日期",
+    validation_required: "请提供 schema、pattern 或两者，用于校验回复",
+    validation_invalid: "校验规则无效：{0}",
+    validation_retries_out_of_range: "max_retries 必须在 0 到 {0} 之间",
+    validation_failed: "经过 {0} 次尝试后回复仍未通过校验（SESSION_ID：{1}）：{2}",
+    diff_preview_session: "diff_preview 不能与 SESSION_ID 同时使用：预览在工作区的临时副本中运行",
+    diff_preview_failed: "差异预览失败：{0}",
+    no_changes: "（无更改）",
+    patch_required: "patch 为必填项，且必须是非空字符串",
+    patch_conflict: "补丁无法干净地应用，未做任何更改：\n{0}",
+    review_source_required: "必须且只能提供 ref_range 或 diff 之一",
+    review_ref_range_invalid: "无效的 ref_range '{0}'：应为 git 修订范围，例如 main..HEAD",
+    review_diff_failed: "git diff {0} 失败：{1}",
+    review_diff_too_large: "差异共 {0} 字节，需要 {1} 次审查调用，超过上限 {2}；请缩小审查范围",
+    summarize_patterns_invalid: "patterns 必须包含 1 到 {0} 个 glob 模式",
+    summarize_glob_invalid: "无效的 glob 模式 '{0}'：模式必须相对于工作目录",
+    summarize_no_files: "工作目录中没有与 {0} 匹配的文本文件",
+    summarize_too_many_files: "匹配到 {0} 个文件，超过上限 {1}；请使用更精确的模式",
+    summarize_too_large: "匹配文件的总大小超过 {0} 字节；请使用更精确的模式",
+    auth_fix: "解决方法：{0}",
+    auth_remediation_api_key: "API 密钥被拒绝。请检查服务器环境中的 GEMINI_API_KEY（或 GOOGLE_API_KEY），或在 https://aistudio.google.com/apikey 创建新密钥。",
+    auth_remediation_vertex_ai: "Vertex AI 凭据被拒绝。请检查 GOOGLE_CLOUD_PROJECT、GOOGLE_CLOUD_LOCATION 和 GOOGLE_API_KEY，或运行 `gcloud auth application-default login`。",
+    auth_remediation_oauth: "缓存的 Google 登录缺失或已过期。请运行 `gemini auth login`（或在终端中启动 `gemini` 重新登录），或在服务器环境中设置 GEMINI_API_KEY。",
+    auth_remediation_env_file: "~/.gemini/.env 中的凭据被拒绝。请检查其中设置的 GEMINI_API_KEY，或运行 `gemini auth login`。",
+    auth_remediation_none: "未找到 Gemini 凭据。请运行 `gemini auth login`（或在终端中启动 `gemini` 并使用 Google 登录），或在服务器环境中设置 GEMINI_API_KEY。",
+    session_not_recorded: "没有会话 '{0}' 的成功调用记录",
+    session_not_found: "未知的 SESSION_ID '{0}'",
+    session_id_invalid: "无效的 SESSION_ID '{0}'：应为之前调用返回的 ID（最多 128 个字母、数字、'-' 或 '_'）",
+    session_expired: "会话 '{0}' 已不存在，可能已过期或被删除。请不带 SESSION_ID 重新调用以开始新会话",
+    policy_violation: "被策略规则 '{0}' 拒绝（{1}）：{2}",
+    session_in_use: "SESSION_ID '{0}' 已有正在运行的调用；请等待其完成，或使用 cancel_session 停止它",
+    unknown_error: "未知错误",
+    events_captured: "已捕获 {0} 个事件",
+    full_event_log: "完整事件日志：",
+    events_before_failure: "失败前捕获了 {0} 个事件：",
+    events_spooled: "已将 {0} 个事件写入 {1}（{2}）",
+    missing_session_id: "未能从 gemini 会话中获取 `SESSION_ID`。",
+    missing_agent_messages: "未能从 gemini 会话中获取 `agent_messages`。\n可以尝试将 `return_all_messages` 设置为 `True` 以获取完整信息。",
+    missing_any_messages: "未能从 gemini 会话中获取任何消息。",
+    timed_out: "Gemini 命令在 {0} 秒后超时",
+    idle_timed_out: "Gemini 命令连续 {0} 秒没有输出，已被终止",
+    exit_code_failure: "gemini 命令执行失败，退出码：{0}",
+    no_valid_json: "未从 gemini CLI 收到有效的 JSON 输出。",
+    stdout_limit_exceeded: "Gemini 的输出超过 GEMINI_MAX_STDOUT_BYTES 或 GEMINI_MAX_STDOUT_LINES 后已被终止，输出不完整。",
+};
+
+/// Locale selected for this process
+pub fn locale() -> Locale {
+    static LOCALE: OnceLock<Locale> = OnceLock::new();
+    *LOCALE.get_or_init(|| {
+        std::env::var(ENV_LOCALE)
+            .ok()
+            .and_then(|v| Locale::parse(&v))
+            .unwrap_or_default()
+    })
+}
+
+/// Message catalog for the active locale
+pub fn catalog() -> &'static Catalog {
+    locale().catalog()
+}
+
+/// Substitute positional `{N}` placeholders in a template. The template is scanned
+/// once, so placeholders inside an argument's value are left as they are; placeholders
+/// without an argument are kept verbatim
+pub fn render(template: &str, args: &[&dyn std::fmt::Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let arg = tail.find('}').and_then(|end| {
+            let index: usize = tail[1..end].parse().ok()?;
+            args.get(index).map(|arg| (arg, end))
+        });
+        match arg {
+            Some((arg, end)) => {
+                out.push_str(&arg.to_string());
+                rest = &tail[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_parse() {
+        assert_eq!(Locale::parse("en"), Some(Locale::En));
+        assert_eq!(Locale::parse("en_US.UTF-8"), Some(Locale::En));
+        assert_eq!(Locale::parse("zh-CN"), Some(Locale::ZhCn));
+        assert_eq!(Locale::parse(" zh_CN.UTF-8 "), Some(Locale::ZhCn));
+        assert_eq!(Locale::parse("fr"), None);
+        assert_eq!(Locale::parse(""), None);
+    }
+
+    #[test]
+    fn test_render_positional_placeholders() {
+        assert_eq!(
+            render(EN.timeout_out_of_range, &[&1, &3600]),
+            "timeout_secs must be between 1 and 3600 seconds"
+        );
+        assert_eq!(render("no placeholders", &[&42]), "no placeholders");
+        assert_eq!(render("{0} and {2}", &[&"a"]), "a and {2}");
+    }
+
+    #[test]
+    fn test_render_leaves_placeholders_in_arguments_alone() {
+        assert_eq!(
+            render("prompt {0} in {1}", &[&"uses {1} literally", &"src"]),
+            "prompt uses {1} literally in src"
+        );
+        assert_eq!(render("{{0}}", &[&"x"]), "{x}");
+    }
+
+    #[test]
+    fn test_catalogs_keep_placeholders() {
+        for catalog in [&EN, &ZH_CN] {
+            assert!(catalog.timeout_out_of_range.contains("{0}"));
+            assert!(catalog.timeout_out_of_range.contains("{1}"));
+            assert!(catalog.timeout_out_of_range.contains("timeout_secs"));
+            assert!(catalog.execution_failed.contains("{0}"));
+            assert!(catalog.events_captured.contains("{0}"));
+            assert!(catalog.timed_out.contains("{0}"));
+            assert!(catalog.idle_timed_out.contains("{0}"));
+            assert!(catalog
+                .idle_timeout_out_of_range
+                .contains("idle_timeout_secs"));
+            assert!(catalog.throttled.contains("{0}"));
+            assert!(catalog.throttled.contains("{1}"));
+            assert!(catalog.budget_hourly_secs.contains("{2}"));
+            assert!(catalog.budget_hourly_tokens.contains("{2}"));
+            assert!(catalog.server_busy.contains("{0}"));
+        }
+    }
+}
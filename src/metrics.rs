@@ -0,0 +1,280 @@
+//! Call metrics served at `/metrics` in the Prometheus text format.
+//!
+//! Only the HTTP transport exposes the endpoint, for deployments shared by a team.
+//! Every metric is labelled with the tool that made the call:
+//!
+//! - `gemini_mcp_calls_total`: Gemini CLI calls, successful or not
+//! - `gemini_mcp_call_failures_total`: failed calls, also labelled with the failure `kind`
+//! - `gemini_mcp_call_timeouts_total`: calls killed for running past their timeout
+//! - `gemini_mcp_retries_total`: calls made to retry an earlier one (`gemini_validated`)
+//! - `gemini_mcp_call_duration_seconds`: histogram of call durations
+//!
+//! plus `gemini_mcp_child_processes`, the number of Gemini CLI processes running now.
+
+use crate::gemini::FailureKind;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Upper bounds of the duration histogram's buckets, in seconds
+const DURATION_BUCKETS: [f64; 10] = [
+    1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0,
+];
+
+#[derive(Debug, Default)]
+struct ToolMetrics {
+    calls: u64,
+    failures: BTreeMap<&'static str, u64>,
+    timeouts: u64,
+    retries: u64,
+    /// Calls per duration bucket, not cumulative; the last slot is `+Inf`
+    buckets: [u64; DURATION_BUCKETS.len() + 1],
+    duration_sum: f64,
+}
+
+/// Counters shared by every client session
+#[derive(Debug, Default)]
+pub struct Metrics {
+    tools: Mutex<BTreeMap<String, ToolMetrics>>,
+    child_processes: Arc<AtomicUsize>,
+}
+
+/// Counts a CLI process as running until dropped
+#[derive(Debug)]
+pub struct ProcessGuard(Arc<AtomicUsize>);
+
+impl Drop for ProcessGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a finished call; `failure` is `None` when it succeeded
+    pub fn record_call(&self, tool: &str, duration: Duration, failure: Option<FailureKind>) {
+        self.update(tool, |m| {
+            m.calls += 1;
+            if let Some(kind) = failure {
+                *m.failures.entry(kind.as_str()).or_default() += 1;
+                if kind == FailureKind::Timeout {
+                    m.timeouts += 1;
+                }
+            }
+            let secs = duration.as_secs_f64();
+            let bucket = DURATION_BUCKETS
+                .iter()
+                .position(|bound| secs <= *bound)
+                .unwrap_or(DURATION_BUCKETS.len());
+            m.buckets[bucket] += 1;
+            m.duration_sum += secs;
+        });
+    }
+
+    /// Record that `tool` is calling the CLI again to retry an earlier attempt
+    pub fn record_retry(&self, tool: &str) {
+        self.update(tool, |m| m.retries += 1);
+    }
+
+    /// Count a CLI process as running for as long as the guard lives
+    pub fn process_started(&self) -> ProcessGuard {
+        self.child_processes.fetch_add(1, Ordering::Relaxed);
+        ProcessGuard(Arc::clone(&self.child_processes))
+    }
+
+    fn update(&self, tool: &str, f: impl FnOnce(&mut ToolMetrics)) {
+        let mut tools = self.tools.lock().unwrap_or_else(|e| e.into_inner());
+        f(tools.entry(tool.to_string()).or_default());
+    }
+
+    /// All metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let tools = self.tools.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "gemini_mcp_calls_total",
+            "counter",
+            "Gemini CLI calls",
+        );
+        for (tool, m) in tools.iter() {
+            sample(
+                &mut out,
+                "gemini_mcp_calls_total",
+                &[("tool", tool)],
+                m.calls,
+            );
+        }
+        header(
+            &mut out,
+            "gemini_mcp_call_failures_total",
+            "counter",
+            "Failed Gemini CLI calls by failure kind",
+        );
+        for (tool, m) in tools.iter() {
+            for (kind, count) in &m.failures {
+                sample(
+                    &mut out,
+                    "gemini_mcp_call_failures_total",
+                    &[("tool", tool), ("kind", kind)],
+                    count,
+                );
+            }
+        }
+        header(
+            &mut out,
+            "gemini_mcp_call_timeouts_total",
+            "counter",
+            "Gemini CLI calls killed for running past their timeout",
+        );
+        for (tool, m) in tools.iter() {
+            sample(
+                &mut out,
+                "gemini_mcp_call_timeouts_total",
+                &[("tool", tool)],
+                m.timeouts,
+            );
+        }
+        header(
+            &mut out,
+            "gemini_mcp_retries_total",
+            "counter",
+            "Gemini CLI calls made to retry an earlier attempt",
+        );
+        for (tool, m) in tools.iter() {
+            sample(
+                &mut out,
+                "gemini_mcp_retries_total",
+                &[("tool", tool)],
+                m.retries,
+            );
+        }
+
+        header(
+            &mut out,
+            "gemini_mcp_call_duration_seconds",
+            "histogram",
+            "Duration of Gemini CLI calls",
+        );
+        for (tool, m) in tools.iter() {
+            let mut cumulative = 0;
+            let bounds = DURATION_BUCKETS
+                .iter()
+                .map(|b| b.to_string())
+                .chain(std::iter::once("+Inf".to_string()));
+            for (bound, count) in bounds.zip(m.buckets) {
+                cumulative += count;
+                sample(
+                    &mut out,
+                    "gemini_mcp_call_duration_seconds_bucket",
+                    &[("tool", tool), ("le", &bound)],
+                    cumulative,
+                );
+            }
+            sample(
+                &mut out,
+                "gemini_mcp_call_duration_seconds_sum",
+                &[("tool", tool)],
+                m.duration_sum,
+            );
+            sample(
+                &mut out,
+                "gemini_mcp_call_duration_seconds_count",
+                &[("tool", tool)],
+                m.calls,
+            );
+        }
+
+        header(
+            &mut out,
+            "gemini_mcp_child_processes",
+            "gauge",
+            "Gemini CLI processes currently running",
+        );
+        sample(
+            &mut out,
+            "gemini_mcp_child_processes",
+            &[],
+            self.child_processes.load(Ordering::Relaxed),
+        );
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_counts_calls_failures_and_durations() {
+        let metrics = Metrics::new();
+        metrics.record_call("gemini", Duration::from_millis(500), None);
+        metrics.record_call(
+            "gemini",
+            Duration::from_secs(20),
+            Some(FailureKind::Timeout),
+        );
+        metrics.record_retry("gemini_validated");
+        let _running = metrics.process_started();
+
+        let text = metrics.render();
+        for line in [
+            "gemini_mcp_calls_total{tool=\"gemini\"} 2",
+            "gemini_mcp_call_failures_total{tool=\"gemini\",kind=\"timeout\"} 1",
+            "gemini_mcp_call_timeouts_total{tool=\"gemini\"} 1",
+            "gemini_mcp_retries_total{tool=\"gemini_validated\"} 1",
+            "gemini_mcp_call_duration_seconds_bucket{tool=\"gemini\",le=\"1\"} 1",
+            "gemini_mcp_call_duration_seconds_bucket{tool=\"gemini\",le=\"15\"} 1",
+            "gemini_mcp_call_duration_seconds_bucket{tool=\"gemini\",le=\"30\"} 2",
+            "gemini_mcp_call_duration_seconds_bucket{tool=\"gemini\",le=\"+Inf\"} 2",
+            "gemini_mcp_call_duration_seconds_sum{tool=\"gemini\"} 20.5",
+            "gemini_mcp_child_processes 1",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing {}\n{}",
+                line,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_process_guard_decrements_gauge() {
+        let metrics = Metrics::new();
+        drop(metrics.process_started());
+        assert!(metrics
+            .render()
+            .contains("\ngemini_mcp_child_processes 0\n"));
+    }
+}
@@ -0,0 +1,181 @@
+//! DNS-rebinding protection for the HTTP transport.
+//!
+//! A web page can point a hostname it controls at 127.0.0.1 and then talk to a server
+//! listening there from the victim's browser. The browser still sends the attacker's
+//! hostname as `Host`, and its page as `Origin`, so [`check`] refuses requests naming a
+//! host that isn't loopback, the address the server listens on, or one listed in
+//! `GEMINI_HTTP_ALLOWED_HOSTS`. Requests without the headers aren't from a browser page
+//! and pass.
+
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+/// Comma-separated extra hosts `Host` and `Origin` may name, or `*` for any
+pub const ENV_ALLOWED_HOSTS: &str = "GEMINI_HTTP_ALLOWED_HOSTS";
+
+/// Hosts HTTP requests may be addressed to or sent from, besides loopback
+#[derive(Debug, Clone, Default)]
+pub struct AllowedHosts {
+    hosts: Vec<String>,
+    any: bool,
+}
+
+impl AllowedHosts {
+    /// The hosts in `list`, comma separated, with `*` allowing any host
+    pub fn parse(list: &str) -> Self {
+        let mut allowed = Self::default();
+        for host in list.split(',').map(str::trim).filter(|h| !h.is_empty()) {
+            if host == "*" {
+                allowed.any = true;
+            } else {
+                allowed.hosts.push(host_name(host).to_ascii_lowercase());
+            }
+        }
+        allowed
+    }
+
+    /// The hosts in `GEMINI_HTTP_ALLOWED_HOSTS`, plus the address of `listen` when it
+    /// names one interface
+    pub fn from_env(listen: SocketAddr) -> Self {
+        let mut allowed = Self::parse(&std::env::var(ENV_ALLOWED_HOSTS).unwrap_or_default());
+        if !listen.ip().is_unspecified() {
+            allowed.hosts.push(listen.ip().to_string());
+        }
+        allowed
+    }
+
+    /// Whether a `Host` header value, with or without a port, is acceptable
+    pub fn allows_host(&self, host: &str) -> bool {
+        let name = host_name(host);
+        if self.any || is_loopback(name) {
+            return true;
+        }
+        self.hosts.iter().any(|h| h.eq_ignore_ascii_case(name))
+    }
+
+    /// Whether an `Origin` header value such as `http://localhost:3000` is acceptable
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        if self.any {
+            return true;
+        }
+        let Some((_, rest)) = origin.trim().split_once("://") else {
+            // Opaque origins, sent as `null`, can't be checked
+            return false;
+        };
+        let authority = rest.split('/').next().unwrap_or(rest);
+        self.allows_host(authority)
+    }
+}
+
+/// Middleware refusing requests whose `Host` or `Origin` isn't allowed
+pub async fn check(
+    State(allowed): State<Arc<AllowedHosts>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let headers = request.headers();
+    let host = headers
+        .get(header::HOST)
+        .map(|v| v.to_str().unwrap_or_default());
+    let origin = headers
+        .get(header::ORIGIN)
+        .map(|v| v.to_str().unwrap_or_default());
+    if let Some(host) = host.filter(|h| !allowed.allows_host(h)) {
+        tracing::warn!("Refused an HTTP request for host '{}'", host);
+        return (StatusCode::FORBIDDEN, "Host not allowed").into_response();
+    }
+    if let Some(origin) = origin.filter(|o| !allowed.allows_origin(o)) {
+        tracing::warn!("Refused an HTTP request from origin '{}'", origin);
+        return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+    }
+    next.run(request).await
+}
+
+/// The host of `host[:port]` or `[ipv6][:port]`, without brackets
+fn host_name(authority: &str) -> &str {
+    let authority = authority.trim();
+    match authority.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        None => authority.split(':').next().unwrap_or(authority),
+    }
+}
+
+fn is_loopback(name: &str) -> bool {
+    name.eq_ignore_ascii_case("localhost")
+        || name.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loopback_hosts_are_allowed() {
+        let allowed = AllowedHosts::default();
+        for host in [
+            "localhost",
+            "LOCALHOST:8787",
+            "127.0.0.1:8787",
+            "[::1]:8787",
+            "::1",
+        ] {
+            assert!(allowed.allows_host(host), "{}", host);
+        }
+        assert!(allowed.allows_origin("http://localhost:3000"));
+        assert!(allowed.allows_origin("https://127.0.0.1"));
+
+        assert!(!allowed.allows_host("evil.example:8787"));
+        assert!(!allowed.allows_host("localhost.evil.example"));
+        assert!(!allowed.allows_origin("http://evil.example"));
+        assert!(!allowed.allows_origin("null"));
+    }
+
+    #[test]
+    fn test_listed_hosts_and_listen_address_are_allowed() {
+        std::env::set_var(ENV_ALLOWED_HOSTS, " mcp.internal , Build-Box:9000 ");
+        let allowed = AllowedHosts::from_env("192.168.1.5:8787".parse().unwrap());
+        std::env::remove_var(ENV_ALLOWED_HOSTS);
+
+        assert!(allowed.allows_host("mcp.internal:8787"));
+        assert!(allowed.allows_host("build-box"));
+        assert!(allowed.allows_host("192.168.1.5:8787"));
+        assert!(allowed.allows_origin("https://MCP.internal/app"));
+        assert!(!allowed.allows_host("192.168.1.6:8787"));
+
+        let any = AllowedHosts::parse("*");
+        assert!(any.allows_host("anything.example") && any.allows_origin("null"));
+    }
+
+    #[tokio::test]
+    async fn test_check_refuses_rebound_requests() {
+        let allowed = Arc::new(AllowedHosts::default());
+        let router = axum::Router::new()
+            .route("/mcp", axum::routing::get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(allowed, check));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = reqwest::Client::new();
+        let status = |request: reqwest::RequestBuilder| async move {
+            request.send().await.unwrap().status().as_u16()
+        };
+        assert_eq!(status(client.get(&url)).await, 200);
+        assert_eq!(
+            status(client.get(&url).header("Origin", "http://localhost:5173")).await,
+            200
+        );
+        assert_eq!(
+            status(client.get(&url).header("Origin", "http://evil.example")).await,
+            403
+        );
+        assert_eq!(
+            status(client.get(&url).header("Host", "evil.example:8787")).await,
+            403
+        );
+    }
+}
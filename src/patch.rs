@@ -0,0 +1,360 @@
+//! Unified diffs pulled out of replies, for `gemini_patch`.
+//!
+//! Gemini is asked to answer with its changes as unified diffs in ```` ```diff ````
+//! fences. [`extract`] reads the fenced blocks (or the whole reply when there are
+//! none) and splits them into one [`FilePatch`] per file, with the file's path, what
+//! kind of change it is and its line counts. Text outside file headers and hunks is
+//! ignored, so explanations around the diffs do no harm. [`check`] then runs
+//! `git apply --check` on each file's patch against the working tree.
+
+use crate::scratch;
+use serde::Serialize;
+use std::path::Path;
+
+/// Info strings of code fences that hold diffs
+const DIFF_FENCES: &[&str] = &["diff", "patch", "udiff"];
+
+/// Instructions appended to the task so the reply carries machine-readable changes
+pub const PATCH_INSTRUCTIONS: &str = "Do not modify any files. Instead, reply with every change \
+as a unified diff (as produced by `git diff`) in a ```diff fenced code block, with paths \
+relative to the current directory and `a/` and `b/` prefixes, three lines of context, and \
+`/dev/null` for created or deleted files. Keep explanations short and outside the code blocks.";
+
+/// What a patch does to its file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+}
+
+impl FileStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Deleted => "deleted",
+            Self::Modified => "modified",
+            Self::Renamed => "renamed",
+        }
+    }
+}
+
+/// The part of a diff that changes one file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FilePatch {
+    /// The file's path after the change, or before it for deleted files
+    pub path: String,
+    /// Path before the change, when it differs from `path`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    pub status: FileStatus,
+    pub hunks: usize,
+    pub additions: usize,
+    pub deletions: usize,
+    /// The file's headers and hunks, ready for `git apply`
+    pub patch: String,
+}
+
+/// A file's patch and whether it applies to the working tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckedPatch {
+    #[serde(flatten)]
+    pub file: FilePatch,
+    pub applies: bool,
+    /// Why the patch doesn't apply, as reported by git
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<String>,
+}
+
+/// Check each patch against the files in `dir` without changing them
+pub fn check(dir: &Path, files: Vec<FilePatch>) -> anyhow::Result<Vec<CheckedPatch>> {
+    files
+        .into_iter()
+        .map(|file| {
+            let conflict = scratch::check_patch(dir, &file.patch)?.err();
+            Ok(CheckedPatch {
+                file,
+                applies: conflict.is_none(),
+                conflict,
+            })
+        })
+        .collect()
+}
+
+/// Per-file patches in `text`, in the order they appear
+pub fn extract(text: &str) -> Vec<FilePatch> {
+    let blocks = fenced_blocks(text);
+    if blocks.is_empty() {
+        return parse(text);
+    }
+    blocks.into_iter().flat_map(parse).collect()
+}
+
+/// Contents of fenced code blocks marked as diffs, or unmarked ones that start like one
+fn fenced_blocks(text: &str) -> Vec<&str> {
+    let mut blocks = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("```") {
+        let after = &rest[start + 3..];
+        let Some(line_end) = after.find('\n') else {
+            break;
+        };
+        let info = after[..line_end].trim();
+        let body = &after[line_end + 1..];
+        let (content, next) = match body.find("\n```") {
+            Some(end) => (&body[..end + 1], &body[end + 4..]),
+            None => (body, ""),
+        };
+        let looks_like_diff =
+            info.is_empty() && (content.starts_with("diff --git ") || content.starts_with("--- "));
+        if DIFF_FENCES.contains(&info.to_ascii_lowercase().as_str()) || looks_like_diff {
+            blocks.push(content);
+        }
+        // Skip the rest of the closing fence line
+        rest = next.find('\n').map_or("", |i| &next[i..]);
+    }
+    blocks
+}
+
+/// A file being read: header lines, then hunks
+#[derive(Default)]
+struct Builder {
+    lines: Vec<String>,
+    /// Paths from the `---`/`+++` headers; `Some(None)` is `/dev/null`
+    old_path: Option<Option<String>>,
+    new_path: Option<Option<String>>,
+    git_paths: Option<(String, String)>,
+    created: bool,
+    deleted: bool,
+    renamed: bool,
+    hunks: usize,
+    additions: usize,
+    deletions: usize,
+}
+
+impl Builder {
+    fn finish(self) -> Option<FilePatch> {
+        let (git_old, git_new) = self.git_paths.unzip();
+        let old_path = self.old_path.unwrap_or(git_old);
+        let new_path = self.new_path.unwrap_or(git_new);
+        let status = if self.created || (old_path.is_none() && new_path.is_some()) {
+            FileStatus::Added
+        } else if self.deleted || (new_path.is_none() && old_path.is_some()) {
+            FileStatus::Deleted
+        } else if self.renamed || old_path != new_path {
+            FileStatus::Renamed
+        } else {
+            FileStatus::Modified
+        };
+        let path = match status {
+            FileStatus::Deleted => old_path.clone()?,
+            _ => new_path?,
+        };
+        if self.hunks == 0 && status == FileStatus::Modified {
+            return None;
+        }
+        let mut patch = self.lines.join("\n");
+        patch.push('\n');
+        Some(FilePatch {
+            old_path: old_path.filter(|old| *old != path),
+            path,
+            status,
+            hunks: self.hunks,
+            additions: self.additions,
+            deletions: self.deletions,
+            patch,
+        })
+    }
+}
+
+/// Split raw diff text into per-file patches
+fn parse(text: &str) -> Vec<FilePatch> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut files = Vec::new();
+    let mut current: Option<Builder> = None;
+    // Lines still expected in the current hunk: (old side, new side)
+    let mut remaining = (0usize, 0usize);
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].strip_suffix('\r').unwrap_or(lines[i]);
+        let in_hunk = remaining != (0, 0);
+
+        let starts_git_file = line.starts_with("diff --git ");
+        // Inside a hunk a `---` line is a removal, unless a hunk header follows the pair
+        // (the hunk's counts were wrong)
+        let starts_plain_file = line.starts_with("--- ")
+            && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ "))
+            && (!in_hunk || lines.get(i + 2).is_some_and(|l| l.starts_with("@@")));
+        // A `diff --git` header is followed by its own `---`/`+++` pair
+        let awaits_headers = current.as_ref().is_some_and(|f| {
+            f.git_paths.is_some() && f.hunks == 0 && f.old_path.is_none() && f.new_path.is_none()
+        });
+        if starts_git_file || (starts_plain_file && !awaits_headers) {
+            files.extend(current.take().and_then(Builder::finish));
+            current = Some(Builder::default());
+            remaining = (0, 0);
+        }
+        let Some(file) = current.as_mut() else {
+            i += 1;
+            continue;
+        };
+
+        if in_hunk && !starts_git_file && !starts_plain_file {
+            match line.chars().next() {
+                Some('+') => {
+                    file.additions += 1;
+                    remaining.1 = remaining.1.saturating_sub(1);
+                }
+                Some('-') => {
+                    file.deletions += 1;
+                    remaining.0 = remaining.0.saturating_sub(1);
+                }
+                Some(' ') | None => {
+                    remaining.0 = remaining.0.saturating_sub(1);
+                    remaining.1 = remaining.1.saturating_sub(1);
+                }
+                Some('\\') => {}
+                // Prose after a hunk whose counts were off ends the file
+                _ => {
+                    files.extend(current.take().and_then(Builder::finish));
+                    remaining = (0, 0);
+                    i += 1;
+                    continue;
+                }
+            }
+            file.lines.push(line.to_string());
+        } else if let Some(paths) = line.strip_prefix("diff --git ") {
+            file.git_paths = split_git_paths(paths);
+            file.lines.push(line.to_string());
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            file.old_path = Some(header_path(path));
+            file.lines.push(line.to_string());
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            file.new_path = Some(header_path(path));
+            file.lines.push(line.to_string());
+        } else if line.starts_with("@@") {
+            remaining = hunk_lengths(line).unwrap_or((usize::MAX, usize::MAX));
+            file.hunks += 1;
+            file.lines.push(line.to_string());
+        } else if file.hunks == 0 && is_extended_header(line) {
+            file.created |= line.starts_with("new file mode");
+            file.deleted |= line.starts_with("deleted file mode");
+            file.renamed |= line.starts_with("rename ");
+            file.lines.push(line.to_string());
+        } else if file.hunks > 0 || file.git_paths.is_none() {
+            // Anything else between files is commentary
+            files.extend(current.take().and_then(Builder::finish));
+        }
+        i += 1;
+    }
+    files.extend(current.and_then(Builder::finish));
+    files
+}
+
+/// Git's extended header lines between `diff --git` and the first hunk
+fn is_extended_header(line: &str) -> bool {
+    [
+        "index ",
+        "old mode ",
+        "new mode ",
+        "new file mode ",
+        "deleted file mode ",
+        "similarity index ",
+        "dissimilarity index ",
+        "rename from ",
+        "rename to ",
+        "copy from ",
+        "copy to ",
+        "Binary files ",
+    ]
+    .iter()
+    .any(|prefix| line.starts_with(prefix))
+}
+
+/// Old and new line counts from a `@@ -a,b +c,d @@` header
+fn hunk_lengths(line: &str) -> Option<(usize, usize)> {
+    let mut ranges = line.strip_prefix("@@ ")?.split_whitespace();
+    let old = ranges.next()?.strip_prefix('-')?;
+    let new = ranges.next()?.strip_prefix('+')?;
+    let length = |range: &str| match range.split_once(',') {
+        Some((_, length)) => length.parse().ok(),
+        None => Some(1),
+    };
+    Some((length(old)?, length(new)?))
+}
+
+/// Path from a `---`/`+++` header without its `a/`/`b/` prefix or timestamp;
+/// `None` for `/dev/null`
+fn header_path(value: &str) -> Option<String> {
+    let path = value.split('\t').next().unwrap_or(value).trim();
+    if path == "/dev/null" {
+        return None;
+    }
+    Some(strip_prefix(path).to_string())
+}
+
+/// Paths from `diff --git a/x b/x`
+fn split_git_paths(value: &str) -> Option<(String, String)> {
+    let (old, new) = value.split_once(" b/")?;
+    Some((strip_prefix(old).to_string(), new.to_string()))
+}
+
+fn strip_prefix(path: &str) -> &str {
+    path.strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_fenced_diffs() {
+        let reply = "Here is the fix:\n\n```diff\ndiff --git a/src/lib.rs b/src/lib.rs\nindex 1..2 100644\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,2 +1,2 @@\n fn main() {\n-    old();\n+    new();\n@@ -10 +10,2 @@\n-x\n+y\n+z\n```\n\nAnd a new file:\n\n```diff\n--- /dev/null\n+++ b/NOTES.md\n@@ -0,0 +1 @@\n+notes\n```\nDone.";
+        let files = extract(reply);
+        assert_eq!(files.len(), 2);
+
+        assert_eq!(files[0].path, "src/lib.rs");
+        assert_eq!(files[0].status, FileStatus::Modified);
+        assert_eq!(
+            (files[0].hunks, files[0].additions, files[0].deletions),
+            (2, 3, 2)
+        );
+        assert!(files[0].patch.starts_with("diff --git"));
+        assert!(files[0].patch.ends_with("+z\n"));
+
+        assert_eq!(files[1].path, "NOTES.md");
+        assert_eq!(files[1].status, FileStatus::Added);
+        assert_eq!(files[1].old_path, None);
+    }
+
+    #[test]
+    fn test_extract_unfenced_diff_with_commentary() {
+        let reply = "Change two files.\n--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-a\n+b\n--- a/b.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-gone\nThat's all.\n";
+        let files = extract(reply);
+        let summary: Vec<_> = files.iter().map(|f| (f.path.as_str(), f.status)).collect();
+        assert_eq!(
+            summary,
+            [
+                ("a.txt", FileStatus::Modified),
+                ("b.txt", FileStatus::Deleted)
+            ]
+        );
+        assert!(!files[1].patch.contains("That's all"));
+    }
+
+    #[test]
+    fn test_extract_rename_and_no_diff() {
+        let reply = "```diff\ndiff --git a/old.rs b/new.rs\nsimilarity index 100%\nrename from old.rs\nrename to new.rs\n```";
+        let files = extract(reply);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].status, FileStatus::Renamed);
+        assert_eq!(files[0].old_path.as_deref(), Some("old.rs"));
+        assert_eq!(files[0].path, "new.rs");
+
+        assert!(extract("No changes are needed.\n```rust\nfn main() {}\n```").is_empty());
+    }
+}
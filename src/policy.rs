@@ -0,0 +1,379 @@
+//! Policy filters on prompts and replies.
+//!
+//! Every call's prompt is checked before the CLI starts, and its reply
+//! (`agent_messages`) before it is returned. The first [`PolicyFilter`] to object
+//! rejects the call with a [`Violation`] naming the rule that matched; a rejected reply
+//! is withheld. The built-in filters are configured through the environment:
+//!
+//! - GEMINI_POLICY_MAX_PROMPT_CHARS / GEMINI_POLICY_MAX_RESPONSE_CHARS cap the length
+//!   of prompts and replies (rule `max_length`)
+//! - GEMINI_POLICY_DENY holds regular expressions no prompt or reply may match, one
+//!   per line, each optionally named as `name=pattern` (else `deny_pattern_<line>`)
+//! - GEMINI_POLICY_COMMAND runs a program for every check, with the text on stdin and
+//!   `prompt` or `response` in GEMINI_POLICY_STAGE. Exiting 0 accepts the text; any
+//!   other status rejects it, with the rule name on the first line of stdout and the
+//!   reason on stderr. A command that can't run or takes over 30 seconds rejects
+//!
+//! Programs embedding the server add their own filters with
+//! [`GeminiServer::with_policy_filter`](crate::server::GeminiServer::with_policy_filter).
+
+use regex::Regex;
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const ENV_MAX_PROMPT_CHARS: &str = "GEMINI_POLICY_MAX_PROMPT_CHARS";
+const ENV_MAX_RESPONSE_CHARS: &str = "GEMINI_POLICY_MAX_RESPONSE_CHARS";
+const ENV_DENY: &str = "GEMINI_POLICY_DENY";
+const ENV_COMMAND: &str = "GEMINI_POLICY_COMMAND";
+/// Tells the policy command what it is checking
+const ENV_STAGE: &str = "GEMINI_POLICY_STAGE";
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+const RULE_MAX_LENGTH: &str = "max_length";
+const RULE_COMMAND: &str = "command";
+
+/// What a filter is checking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// The prompt, before the CLI starts
+    Prompt,
+    /// The reply, before it is returned
+    Response,
+}
+
+impl Stage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Stage::Prompt => "prompt",
+            Stage::Response => "response",
+        }
+    }
+}
+
+/// Why a filter rejected a text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Name of the rule that matched, reported to the client
+    pub rule: String,
+    pub reason: String,
+}
+
+impl Violation {
+    pub fn new(rule: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            rule: rule.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// A check on prompts and replies. Filters run on a blocking thread, so they may do
+/// blocking work such as running a program
+pub trait PolicyFilter: Send + Sync {
+    /// `Err` rejects `text`
+    fn check(&self, stage: Stage, text: &str) -> Result<(), Violation>;
+}
+
+/// The filters every call goes through, in order
+#[derive(Clone, Default)]
+pub struct Policy {
+    filters: Vec<Arc<dyn PolicyFilter>>,
+}
+
+impl std::fmt::Debug for Policy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Policy")
+            .field("filters", &self.filters.len())
+            .finish()
+    }
+}
+
+impl Policy {
+    /// The built-in filters configured through the environment
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+        let limit = |name: &str| var(name).and_then(|v| v.trim().parse::<usize>().ok());
+        let mut policy = Self::default();
+        let (prompt, response) = (limit(ENV_MAX_PROMPT_CHARS), limit(ENV_MAX_RESPONSE_CHARS));
+        if prompt.is_some() || response.is_some() {
+            policy.push(Arc::new(MaxLength { prompt, response }));
+        }
+        if let Some(deny) = var(ENV_DENY) {
+            policy.push(Arc::new(DenyPatterns::parse(&deny)));
+        }
+        if let Some(command) = var(ENV_COMMAND) {
+            match CommandHook::parse(&command) {
+                Some(hook) => policy.push(Arc::new(hook)),
+                None => tracing::warn!("Ignoring {}: not a valid command line", ENV_COMMAND),
+            }
+        }
+        policy
+    }
+
+    /// Add a filter, checked after the ones already there
+    pub fn push(&mut self, filter: Arc<dyn PolicyFilter>) {
+        self.filters.push(filter);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// The first filter's objection to `text`, if any
+    pub fn check(&self, stage: Stage, text: &str) -> Result<(), Violation> {
+        self.filters
+            .iter()
+            .try_for_each(|filter| filter.check(stage, text))
+    }
+}
+
+/// Caps the length of prompts and replies, in characters
+#[derive(Debug, Clone, Default)]
+pub struct MaxLength {
+    pub prompt: Option<usize>,
+    pub response: Option<usize>,
+}
+
+impl PolicyFilter for MaxLength {
+    fn check(&self, stage: Stage, text: &str) -> Result<(), Violation> {
+        let max = match stage {
+            Stage::Prompt => self.prompt,
+            Stage::Response => self.response,
+        };
+        match max {
+            Some(max) if text.chars().count() > max => Err(Violation::new(
+                RULE_MAX_LENGTH,
+                format!("{} is longer than {} characters", stage.as_str(), max),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Rejects prompts and replies matching any of its named regular expressions
+#[derive(Debug, Clone, Default)]
+pub struct DenyPatterns {
+    rules: Vec<(String, Regex)>,
+}
+
+impl DenyPatterns {
+    /// One pattern per line, each optionally prefixed with `name=`. Blank lines and
+    /// lines starting with `#` are skipped, and so are invalid patterns, with a warning
+    pub fn parse(text: &str) -> Self {
+        let mut rules = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, pattern) = match line.split_once('=') {
+                Some((name, pattern)) if is_rule_name(name.trim()) => {
+                    (name.trim().to_string(), pattern.trim())
+                }
+                _ => (format!("deny_pattern_{}", index + 1), line),
+            };
+            match Regex::new(pattern) {
+                Ok(regex) => rules.push((name, regex)),
+                Err(e) => tracing::warn!("Ignoring {} rule '{}': {}", ENV_DENY, name, e),
+            }
+        }
+        Self { rules }
+    }
+}
+
+impl PolicyFilter for DenyPatterns {
+    fn check(&self, stage: Stage, text: &str) -> Result<(), Violation> {
+        match self.rules.iter().find(|(_, regex)| regex.is_match(text)) {
+            // The match itself stays out of the reason, since it may be what is sensitive
+            Some((name, _)) => Err(Violation::new(
+                name.clone(),
+                format!("{} matches a denied pattern", stage.as_str()),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+fn is_rule_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Asks an external program about each prompt and reply
+#[derive(Debug, Clone)]
+pub struct CommandHook {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl CommandHook {
+    /// A command line split like a shell's; `None` if it is empty or doesn't parse
+    pub fn parse(command: &str) -> Option<Self> {
+        let mut words = shell_words::split(command).ok()?.into_iter();
+        Some(Self {
+            program: words.next()?,
+            args: words.collect(),
+            timeout: COMMAND_TIMEOUT,
+        })
+    }
+
+    fn run(&self, stage: Stage, text: &str) -> std::io::Result<(bool, String, String)> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .env(ENV_STAGE, stage.as_str())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // Feed and drain the pipes on their own threads so a chatty hook can't stall
+        let stdin = child.stdin.take().map(|mut stdin| {
+            let text = text.to_string();
+            std::thread::spawn(move || {
+                let _ = stdin.write_all(text.as_bytes());
+            })
+        });
+        let read = |pipe: Option<Box<dyn Read + Send>>| {
+            pipe.map(|mut pipe| {
+                std::thread::spawn(move || {
+                    let mut output = String::new();
+                    let _ = pipe.read_to_string(&mut output);
+                    output
+                })
+            })
+        };
+        let stdout = read(
+            child
+                .stdout
+                .take()
+                .map(|p| Box::new(p) as Box<dyn Read + Send>),
+        );
+        let stderr = read(
+            child
+                .stderr
+                .take()
+                .map(|p| Box::new(p) as Box<dyn Read + Send>),
+        );
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("timed out after {}s", self.timeout.as_secs()),
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        };
+        if let Some(stdin) = stdin {
+            let _ = stdin.join();
+        }
+        let output =
+            |reader: Option<std::thread::JoinHandle<String>>| reader.and_then(|r| r.join().ok());
+        Ok((
+            status.success(),
+            output(stdout).unwrap_or_default(),
+            output(stderr).unwrap_or_default(),
+        ))
+    }
+}
+
+impl PolicyFilter for CommandHook {
+    fn check(&self, stage: Stage, text: &str) -> Result<(), Violation> {
+        let first_line = |text: &str| {
+            text.lines()
+                .map(str::trim)
+                .find(|l| !l.is_empty())
+                .map(String::from)
+        };
+        match self.run(stage, text) {
+            Ok((true, _, _)) => Ok(()),
+            Ok((false, stdout, stderr)) => Err(Violation::new(
+                first_line(&stdout).unwrap_or_else(|| RULE_COMMAND.to_string()),
+                first_line(&stderr)
+                    .unwrap_or_else(|| format!("{} rejected by {}", stage.as_str(), self.program)),
+            )),
+            // Fail closed: a guardrail that can't run mustn't let everything through
+            Err(e) => Err(Violation::new(
+                RULE_COMMAND,
+                format!("policy command {} failed: {}", self.program, e),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_built_in_filters_name_the_rule_that_matched() {
+        let mut policy = Policy::default();
+        policy.push(Arc::new(MaxLength {
+            prompt: Some(10),
+            response: None,
+        }));
+        policy.push(Arc::new(DenyPatterns::parse(
+            "# secrets\nproject_x=(?i)project\\s+x\n\n[invalid\nACME-[0-9]{6}",
+        )));
+
+        assert_eq!(policy.check(Stage::Prompt, "hello"), Ok(()));
+        assert_eq!(
+            policy
+                .check(Stage::Prompt, "a much longer prompt")
+                .unwrap_err()
+                .rule,
+            "max_length"
+        );
+        assert_eq!(
+            policy
+                .check(Stage::Response, "About PROJECT X")
+                .unwrap_err()
+                .rule,
+            "project_x"
+        );
+        assert_eq!(
+            policy
+                .check(Stage::Response, "id ACME-123456")
+                .unwrap_err()
+                .rule,
+            "deny_pattern_5"
+        );
+        assert!(policy
+            .check(Stage::Response, "ACME-123456")
+            .unwrap_err()
+            .reason
+            .starts_with("response"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_hook_reports_rule_and_fails_closed() {
+        let hook = CommandHook::parse(
+            r#"sh -c 'if grep -q forbidden; then echo no_forbidden; echo "says forbidden" >&2; exit 1; fi'"#,
+        )
+        .unwrap();
+        assert_eq!(hook.check(Stage::Prompt, "fine"), Ok(()));
+        assert_eq!(
+            hook.check(Stage::Response, "forbidden words"),
+            Err(Violation::new("no_forbidden", "says forbidden"))
+        );
+
+        let missing = CommandHook::parse("/nonexistent/policy-hook").unwrap();
+        assert_eq!(
+            missing.check(Stage::Prompt, "fine").unwrap_err().rule,
+            "command"
+        );
+        assert!(CommandHook::parse("").is_none());
+    }
+}
@@ -0,0 +1,305 @@
+//! Post-processing of replies for the `output_format` parameter.
+//!
+//! - `text` leaves the reply as Gemini wrote it
+//! - `plain` strips Markdown formatting: heading and quote markers, emphasis, inline
+//!   code and link syntax, rules and code fences, keeping the text and code itself
+//! - `code` keeps only the contents of fenced code blocks, or the whole reply when it
+//!   has none
+//!
+//! Fences follow CommonMark: a block opened with three or more backticks or tildes
+//! is closed only by a fence of the same character at least as long, so a longer
+//! fence can hold shorter ones (e.g. a Markdown example containing a code block).
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Plain,
+    Code,
+}
+
+impl OutputFormat {
+    /// Parse a format name. `raw` is accepted for `text`, `markdown-stripped` for
+    /// `plain` and `code_blocks` for `code`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "text" | "raw" => Some(Self::Text),
+            "plain" | "markdown_stripped" => Some(Self::Plain),
+            "code" | "code_blocks" => Some(Self::Code),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Plain => "plain",
+            Self::Code => "code",
+        }
+    }
+}
+
+/// A fenced code block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CodeBlock {
+    /// The fence's info string, e.g. `rust`; empty when there is none
+    pub info: String,
+    pub code: String,
+}
+
+/// `text` in the given format
+pub fn apply(format: OutputFormat, text: &str) -> String {
+    match format {
+        OutputFormat::Text => text.to_string(),
+        OutputFormat::Plain => strip_markdown(text),
+        OutputFormat::Code => {
+            let blocks = code_blocks(text);
+            if blocks.is_empty() {
+                return text.trim().to_string();
+            }
+            blocks
+                .iter()
+                .map(|b| b.code.trim_end())
+                .collect::<Vec<_>>()
+                .join("\n\n")
+        }
+    }
+}
+
+/// The outermost fenced code blocks of `text`, in order
+pub fn code_blocks(text: &str) -> Vec<CodeBlock> {
+    segments(text)
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Code(block) => Some(block),
+            Segment::Line(_) => None,
+        })
+        .collect()
+}
+
+/// `text` with Markdown formatting removed. Code blocks keep their contents verbatim
+pub fn strip_markdown(text: &str) -> String {
+    let lines: Vec<String> = segments(text)
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Code(block) => Some(block.code.trim_end_matches('\n').to_string()),
+            Segment::Line(line) => strip_line(line),
+        })
+        .collect();
+    lines.join("\n").trim().to_string()
+}
+
+enum Segment<'a> {
+    Line(&'a str),
+    Code(CodeBlock),
+}
+
+/// An opening or closing fence: its character and length, and the rest of the line
+fn fence(line: &str) -> Option<(char, usize, &str)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let line = &line[indent..];
+    let ch = line.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let len = line.len() - line.trim_start_matches(ch).len();
+    if len < 3 {
+        return None;
+    }
+    let rest = &line[len..];
+    // A backtick fence's info string can't contain backticks, or it would be inline code
+    if ch == '`' && rest.contains('`') {
+        return None;
+    }
+    Some((ch, len, rest.trim()))
+}
+
+/// Split `text` into lines outside code blocks and whole blocks. An unclosed block
+/// runs to the end of the text
+fn segments(text: &str) -> Vec<Segment<'_>> {
+    let mut out = Vec::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let Some((ch, len, info)) = fence(line) else {
+            out.push(Segment::Line(line));
+            continue;
+        };
+        let mut code = String::new();
+        for line in lines.by_ref() {
+            if matches!(fence(line), Some((c, l, rest)) if c == ch && l >= len && rest.is_empty()) {
+                break;
+            }
+            code.push_str(line);
+            code.push('\n');
+        }
+        out.push(Segment::Code(CodeBlock {
+            info: info.to_string(),
+            code,
+        }));
+    }
+    out
+}
+
+/// A line outside code blocks without its Markdown syntax, `None` for rules
+fn strip_line(line: &str) -> Option<String> {
+    let compact: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    if compact.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|r| compact.iter().all(|c| c == r))
+    {
+        return None;
+    }
+
+    // List items keep their indentation; quotes and headings lose it with their markers
+    let mut indent = &line[..line.len() - line.trim_start().len()];
+    let mut body = line.trim_start();
+    while let Some(rest) = body.strip_prefix('>') {
+        body = rest.trim_start();
+        indent = "";
+    }
+    let hashes = body.len() - body.trim_start_matches('#').len();
+    if (1..=6).contains(&hashes) && body[hashes..].starts_with([' ', '\t']) {
+        body = body[hashes..].trim_start();
+        indent = "";
+    }
+    let stripped = match ["* ", "+ "].iter().find_map(|b| body.strip_prefix(b)) {
+        Some(item) => format!("{}- {}", indent, strip_inline(item)),
+        None => format!("{}{}", indent, strip_inline(body)),
+    };
+    Some(stripped.trim_end().to_string())
+}
+
+/// `text` without emphasis markers, inline code backticks and link syntax
+fn strip_inline(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '`' => {
+                // Inline code is kept verbatim
+                let ticks = chars[i..].iter().take_while(|c| **c == '`').count();
+                let close = (i + ticks..chars.len()).find(|&j| {
+                    chars[j..].iter().take_while(|c| **c == '`').count() == ticks
+                        && chars[..j].last() != Some(&'`')
+                });
+                match close {
+                    Some(j) => {
+                        out.extend(&chars[i + ticks..j]);
+                        i = j + ticks;
+                    }
+                    None => {
+                        out.extend(&chars[i..i + ticks]);
+                        i += ticks;
+                    }
+                }
+            }
+            '!' if chars.get(i + 1) == Some(&'[') => i += 1,
+            '[' => match link_end(&chars, i) {
+                Some((label_end, end)) => {
+                    out.push_str(&strip_inline(
+                        &chars[i + 1..label_end].iter().collect::<String>(),
+                    ));
+                    i = end;
+                }
+                None => {
+                    out.push('[');
+                    i += 1;
+                }
+            },
+            '~' if chars.get(i + 1) == Some(&'~') => i += 2,
+            '*' | '_' if is_emphasis(&chars, i) => i += 1,
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// For a link starting at `open` (`[label](url)`), the index of `]` and the index
+/// just past `)`
+fn link_end(chars: &[char], open: usize) -> Option<(usize, usize)> {
+    let label_end = (open + 1..chars.len()).find(|&j| chars[j] == ']')?;
+    if chars.get(label_end + 1) != Some(&'(') {
+        return None;
+    }
+    let close = (label_end + 2..chars.len()).find(|&j| chars[j] == ')')?;
+    Some((label_end, close + 1))
+}
+
+/// Whether the `*` or `_` at `i` marks emphasis rather than standing alone (`2 * 3`)
+/// or joining a word (`snake_case`)
+fn is_emphasis(chars: &[char], i: usize) -> bool {
+    let before = i.checked_sub(1).and_then(|j| chars.get(j));
+    let after = chars.get(i + 1);
+    let space = |c: Option<&char>| c.copied().unwrap_or(' ').is_whitespace();
+    if space(before) && space(after) {
+        return false;
+    }
+    let word = |c: Option<&char>| c.is_some_and(|c| c.is_alphanumeric() || *c == '_');
+    chars[i] == '*' || !(word(before) && word(after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_blocks_respect_nested_fences() {
+        let text = "Here is a README snippet:\n\n````markdown\n# Usage\n```rust\nfn main() {}\n```\n````\n\nAnd a script:\n~~~sh\necho ```\n~~~\n";
+        let blocks = code_blocks(text);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].info, "markdown");
+        assert_eq!(blocks[0].code, "# Usage\n```rust\nfn main() {}\n```\n");
+        assert_eq!(blocks[1].info, "sh");
+        assert_eq!(blocks[1].code, "echo ```\n");
+    }
+
+    #[test]
+    fn test_closing_fence_must_match() {
+        // A shorter fence, a different character or an info string doesn't close it
+        let text = "````\n```\n~~~~\n```` rust\ncode\n`````\nafter\n";
+        let blocks = code_blocks(text);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].code, "```\n~~~~\n```` rust\ncode\n");
+        // Unclosed blocks run to the end
+        assert_eq!(code_blocks("```py\nx = 1\n")[0].code, "x = 1\n");
+        // Inline code isn't a fence
+        assert!(code_blocks("use ```a``` inline\n").is_empty());
+    }
+
+    #[test]
+    fn test_code_format_joins_blocks_or_keeps_reply() {
+        let text = "First:\n```rust\nlet a = 1;\n```\nSecond:\n```\nlet b = 2;\n```\n";
+        assert_eq!(apply(OutputFormat::Code, text), "let a = 1;\n\nlet b = 2;");
+        assert_eq!(apply(OutputFormat::Code, " no code \n"), "no code");
+        assert_eq!(apply(OutputFormat::Text, text), text);
+    }
+
+    #[test]
+    fn test_strip_markdown() {
+        let text = "# Title\n\nSome **bold**, *italic* and `code` with a [link](https://example.com).\n\n> Quoted _note_ about snake_case\n\n* item one\n  + nested ~~old~~\n\n---\n\n```rust\nlet x = *y;\n```";
+        assert_eq!(strip_markdown("2 * 3 in ~/bin"), "2 * 3 in ~/bin");
+        assert_eq!(
+            strip_markdown(text),
+            "Title\n\nSome bold, italic and code with a link.\n\nQuoted note about snake_case\n\n- item one\n  - nested old\n\n\nlet x = *y;"
+        );
+    }
+
+    #[test]
+    fn test_parse_output_format() {
+        assert_eq!(OutputFormat::parse("raw"), Some(OutputFormat::Text));
+        assert_eq!(
+            OutputFormat::parse("Markdown-Stripped"),
+            Some(OutputFormat::Plain)
+        );
+        assert_eq!(OutputFormat::parse("code_blocks"), Some(OutputFormat::Code));
+        assert_eq!(OutputFormat::parse("html"), None);
+    }
+}
@@ -0,0 +1,158 @@
+//! Model price table for estimating the cost of calls from token usage.
+//!
+//! Prices come from `GEMINI_PRICING`, a comma-separated list of
+//! `model=input:output` entries in USD per million tokens, for example
+//! `gemini-2.5-pro=1.25:10,gemini-2.5-flash=0.3:2.5,*=1.25:10`. A model uses the
+//! entry with the same name, else the longest entry that is a prefix of it
+//! (so `gemini-2.5-pro` also prices `gemini-2.5-pro-preview`), else `*`.
+//! Without a matching entry no estimate is made. The same matching rules apply to
+//! the quota table (see [`crate::quota`]).
+
+use crate::gemini::TokenUsage;
+
+pub const ENV_PRICING: &str = "GEMINI_PRICING";
+const WILDCARD: &str = "*";
+const TOKENS_PER_UNIT: f64 = 1_000_000.0;
+
+/// USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Price {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl Price {
+    /// Estimated USD cost of the given usage
+    pub fn cost(&self, usage: TokenUsage) -> f64 {
+        (usage.input_tokens as f64 * self.input + usage.output_tokens as f64 * self.output)
+            / TOKENS_PER_UNIT
+    }
+}
+
+/// Configured model prices
+#[derive(Debug, Clone, Default)]
+pub struct PricingTable {
+    entries: Vec<(String, Price)>,
+}
+
+impl PricingTable {
+    /// Parse `model=input:output` entries separated by commas, skipping malformed ones
+    pub fn parse(value: &str) -> Self {
+        let entries = value
+            .split(',')
+            .filter_map(|entry| {
+                let (model, prices) = entry.split_once('=')?;
+                let (input, output) = prices.split_once(':')?;
+                let model = model.trim();
+                let price = Price {
+                    input: input.trim().parse().ok()?,
+                    output: output.trim().parse().ok()?,
+                };
+                let valid = !model.is_empty()
+                    && price.input.is_finite()
+                    && price.output.is_finite()
+                    && price.input >= 0.0
+                    && price.output >= 0.0;
+                valid.then(|| (model.to_string(), price))
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Table configured through `GEMINI_PRICING` (empty when unset)
+    pub fn from_env() -> Self {
+        std::env::var(ENV_PRICING)
+            .map(|v| Self::parse(&v))
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Configured model names, in table order
+    pub fn models(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(model, _)| model.as_str())
+    }
+
+    /// Price for a model; see the module docs for the matching rules
+    pub fn price(&self, model: Option<&str>) -> Option<Price> {
+        lookup_model(&self.entries, model.unwrap_or("")).copied()
+    }
+
+    /// Estimated USD cost of a call, when usage is known and the model is priced
+    pub fn estimate(&self, model: Option<&str>, usage: Option<TokenUsage>) -> Option<f64> {
+        Some(self.price(model)?.cost(usage?))
+    }
+}
+
+/// Entry for a model: exact name, else longest prefix, else `*`
+pub(crate) fn lookup_model<'a, T>(entries: &'a [(String, T)], model: &str) -> Option<&'a T> {
+    let exact = entries.iter().find(|(name, _)| name == model);
+    let prefix = || {
+        entries
+            .iter()
+            .filter(|(name, _)| name != WILDCARD && model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+    };
+    let wildcard = || entries.iter().find(|(name, _)| name == WILDCARD);
+    exact
+        .or_else(prefix)
+        .or_else(wildcard)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(input_tokens: u64, output_tokens: u64) -> TokenUsage {
+        TokenUsage {
+            input_tokens,
+            output_tokens,
+            total_tokens: input_tokens + output_tokens,
+        }
+    }
+
+    #[test]
+    fn test_parse_skips_malformed_entries() {
+        let table = PricingTable::parse(
+            "gemini-2.5-pro=1.25:10, bad, flash=x:1, neg=-1:1, gemini-2.5-flash = 0.3 : 2.5",
+        );
+        assert_eq!(table.entries.len(), 2);
+        assert_eq!(
+            table.price(Some("gemini-2.5-flash")),
+            Some(Price {
+                input: 0.3,
+                output: 2.5
+            })
+        );
+    }
+
+    #[test]
+    fn test_price_matching_order() {
+        let table = PricingTable::parse("gemini-2.5=1:1,gemini-2.5-pro=2:2,*=9:9");
+        assert_eq!(table.price(Some("gemini-2.5-pro")).unwrap().input, 2.0);
+        assert_eq!(
+            table.price(Some("gemini-2.5-pro-preview")).unwrap().input,
+            2.0
+        );
+        assert_eq!(table.price(Some("gemini-2.5-flash")).unwrap().input, 1.0);
+        assert_eq!(table.price(Some("other")).unwrap().input, 9.0);
+        assert_eq!(table.price(None).unwrap().input, 9.0);
+
+        let no_wildcard = PricingTable::parse("gemini-2.5-pro=2:2");
+        assert!(no_wildcard.price(Some("other")).is_none());
+    }
+
+    #[test]
+    fn test_estimate() {
+        let table = PricingTable::parse("gemini-2.5-pro=1.25:10");
+        let cost = table
+            .estimate(Some("gemini-2.5-pro"), Some(usage(1_000_000, 100_000)))
+            .unwrap();
+        assert!((cost - 2.25).abs() < 1e-9);
+        assert!(table.estimate(Some("gemini-2.5-pro"), None).is_none());
+        assert!(table.estimate(Some("unknown"), Some(usage(1, 1))).is_none());
+    }
+}
@@ -0,0 +1,184 @@
+//! Killing a CLI call's whole process tree.
+//!
+//! The Gemini CLI starts processes of its own (a Node runtime behind a shim, shell
+//! commands run by its tools), and killing only the direct child leaves those
+//! running after a timeout or cancellation. [`ProcessTree`] groups everything the
+//! child starts so it can be stopped at once:
+//!
+//! - on Unix the child leads a new process group (set up by [`configure`]), and the
+//!   group is sent `SIGKILL`;
+//! - on Windows the child is assigned to a Job Object, which is terminated. Processes
+//!   the child starts before it is assigned, right after spawning, escape the job.
+//!
+//! Like `kill_on_drop`, dropping a tree that wasn't [released](ProcessTree::release)
+//! kills it, so a call abandoned mid-flight doesn't leave processes behind.
+
+use tokio::process::{Child, Command};
+
+/// Prepare `cmd` so the process it starts heads its own tree
+pub fn configure(cmd: &mut Command) {
+    #[cfg(unix)]
+    cmd.process_group(0);
+    #[cfg(not(unix))]
+    let _ = cmd;
+}
+
+/// The processes started by one child
+#[derive(Debug)]
+pub struct ProcessTree {
+    #[cfg(unix)]
+    pgid: Option<i32>,
+    #[cfg(windows)]
+    job: Option<windows::Job>,
+}
+
+impl ProcessTree {
+    /// Track the tree of a child spawned from a [`configure`]d command. Failures are
+    /// logged and leave only the direct child to be killed
+    pub fn attach(child: &Child) -> Self {
+        #[cfg(not(any(unix, windows)))]
+        let _ = child;
+        Self {
+            #[cfg(unix)]
+            pgid: child.id().and_then(|pid| i32::try_from(pid).ok()),
+            #[cfg(windows)]
+            job: child.raw_handle().and_then(|handle| {
+                windows::Job::assign(handle)
+                    .map_err(|e| tracing::warn!("Failed to create a job object for gemini: {}", e))
+                    .ok()
+            }),
+        }
+    }
+
+    /// Kill every process in the tree, the direct child included
+    pub fn kill(&mut self) {
+        #[cfg(unix)]
+        if let Some(pgid) = self.pgid.take() {
+            // SAFETY: kill has no memory-safety preconditions; a negative pid
+            // addresses the process group
+            let killed = unsafe { libc::kill(-pgid, libc::SIGKILL) } == 0;
+            tracing::debug!(pgid, killed, "killed gemini process group");
+        }
+        #[cfg(windows)]
+        if let Some(job) = self.job.take() {
+            job.terminate();
+        }
+    }
+
+    /// Stop tracking the tree without killing it, once the child has exited on its own.
+    /// Processes it left running on purpose keep running
+    pub fn release(mut self) {
+        #[cfg(unix)]
+        {
+            self.pgid = None;
+        }
+        #[cfg(windows)]
+        if let Some(job) = self.job.take() {
+            job.release();
+        }
+    }
+}
+
+impl Drop for ProcessTree {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::io;
+    use std::os::windows::io::RawHandle;
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+        SetInformationJobObject, TerminateJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    };
+
+    /// A Job Object handle. Closing it kills the processes in it unless released
+    #[derive(Debug)]
+    pub struct Job(isize);
+
+    impl Job {
+        pub fn assign(process: RawHandle) -> io::Result<Self> {
+            // SAFETY: all pointers passed are either null or point to live locals, and
+            // the job handle is owned by the returned value, which closes it on drop
+            unsafe {
+                let handle = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+                if handle.is_null() {
+                    return Err(io::Error::last_os_error());
+                }
+                let job = Job(handle as isize);
+                job.set_limit_flags(JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE)?;
+                if AssignProcessToJobObject(handle, process as _) == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(job)
+            }
+        }
+
+        pub fn terminate(self) {
+            // SAFETY: the handle is a job object owned by self
+            unsafe {
+                TerminateJobObject(self.0 as _, 1);
+            }
+        }
+
+        /// Close the handle without killing the processes in the job
+        pub fn release(self) {
+            let _ = self.set_limit_flags(0);
+        }
+
+        fn set_limit_flags(&self, flags: u32) -> io::Result<()> {
+            // SAFETY: info is a zeroed plain-old-data struct of the size passed
+            unsafe {
+                let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+                info.BasicLimitInformation.LimitFlags = flags;
+                let ok = SetInformationJobObject(
+                    self.0 as _,
+                    JobObjectExtendedLimitInformation,
+                    &info as *const _ as *const _,
+                    std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+                );
+                if ok == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            // SAFETY: the handle is owned by self and closed only here
+            unsafe {
+                CloseHandle(self.0 as _);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_kill_reaches_grandchildren() {
+        let marker = std::env::temp_dir().join(format!("gemini-mcp-tree-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg(format!("(sleep 1; touch '{}') & wait", marker.display()));
+        configure(&mut cmd);
+        let mut child = cmd.spawn().unwrap();
+        let mut tree = ProcessTree::attach(&child);
+
+        tree.kill();
+        let _ = child.wait().await;
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        assert!(!marker.exists(), "grandchild outlived the kill");
+    }
+}
@@ -0,0 +1,330 @@
+//! Prompt templates served through the MCP prompts capability.
+//!
+//! Two templates are built in (`code-review` and `summarize-diff`). More can be put
+//! in the directory named by `GEMINI_PROMPTS_DIR`: every `<name>.md` file there is a
+//! template called `<name>`, replacing a built-in of the same name. A template may
+//! start with TOML front matter between `+++` lines:
+//!
+//! ```text
+//! +++
+//! description = "Explain an error message"
+//!
+//! [[arguments]]
+//! name = "error"
+//! description = "The error text"
+//!
+//! [[arguments]]
+//! name = "context"
+//! required = false
+//! +++
+//! Explain this error and how to fix it:
+//!
+//! {{error}}
+//!
+//! {{context}}
+//! ```
+//!
+//! `{{name}}` placeholders are replaced by the arguments the client passes to
+//! prompts/get. Placeholders not declared in the front matter are required
+//! arguments; an optional argument that is left out renders as nothing.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+pub const ENV_PROMPTS_DIR: &str = "GEMINI_PROMPTS_DIR";
+const FRONT_MATTER_DELIMITER: &str = "+++";
+const TEMPLATE_EXTENSION: &str = "md";
+
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "code-review",
+        r#"+++
+description = "Review code for bugs, security issues and readability"
+
+[[arguments]]
+name = "code"
+description = "Code or diff to review"
+
+[[arguments]]
+name = "focus"
+description = "What to pay most attention to, e.g. security or performance"
+required = false
++++
+Review the following code. Report bugs, security issues and readability problems,
+most severe first, each with the location and a suggested fix. Say so if you find
+nothing worth changing. {{focus}}
+
+{{code}}
+"#,
+    ),
+    (
+        "summarize-diff",
+        r#"+++
+description = "Summarize a diff as a commit or pull request description"
+
+[[arguments]]
+name = "diff"
+description = "Unified diff to summarize"
++++
+Summarize the following diff for a reviewer: one line saying what the change does,
+then a short list of the notable changes and anything that needs a careful look.
+
+{{diff}}
+"#,
+    ),
+];
+
+/// An argument a template accepts
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateArgument {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default = "default_required")]
+    pub required: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FrontMatter {
+    description: Option<String>,
+    #[serde(default)]
+    arguments: Vec<TemplateArgument>,
+}
+
+/// A named prompt with placeholders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub description: Option<String>,
+    pub arguments: Vec<TemplateArgument>,
+    body: String,
+}
+
+impl PromptTemplate {
+    /// Parse a template file's text
+    pub fn parse(name: &str, text: &str) -> Result<Self> {
+        let text = text.replace("\r\n", "\n");
+        let (front, body) = match text.strip_prefix(FRONT_MATTER_DELIMITER) {
+            Some(rest) => match rest.split_once(&format!("\n{}", FRONT_MATTER_DELIMITER)) {
+                Some((front, body)) => (
+                    toml::from_str::<FrontMatter>(front).context("Invalid front matter")?,
+                    body.strip_prefix('\n').unwrap_or(body),
+                ),
+                None => bail!("Front matter is not closed with {}", FRONT_MATTER_DELIMITER),
+            },
+            None => (FrontMatter::default(), text.as_str()),
+        };
+
+        let mut arguments = front.arguments;
+        for placeholder in placeholders(body) {
+            if !arguments.iter().any(|a| a.name == placeholder) {
+                arguments.push(TemplateArgument {
+                    name: placeholder.to_string(),
+                    description: None,
+                    required: true,
+                });
+            }
+        }
+        Ok(Self {
+            name: name.to_string(),
+            description: front.description,
+            arguments,
+            body: body.trim().to_string(),
+        })
+    }
+
+    /// The prompt with placeholders replaced by `values`. `Err` names the first
+    /// required argument that is missing
+    pub fn render(&self, values: &HashMap<String, String>) -> Result<String, String> {
+        if let Some(missing) = self
+            .arguments
+            .iter()
+            .find(|a| a.required && !values.get(&a.name).is_some_and(|v| !v.trim().is_empty()))
+        {
+            return Err(missing.name.clone());
+        }
+        let mut out = String::with_capacity(self.body.len());
+        let mut rest = self.body.as_str();
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            match after.find("}}").map(|end| (after[..end].trim(), end)) {
+                Some((key, end)) if is_placeholder_name(key) => {
+                    out.push_str(values.get(key).map_or("", String::as_str));
+                    rest = &after[end + 2..];
+                }
+                _ => {
+                    out.push_str("{{");
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        Ok(out.trim().to_string())
+    }
+}
+
+/// Placeholder names in `body`, in order of first use
+fn placeholders(body: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        match after.find("}}") {
+            Some(end) => {
+                let key = after[..end].trim();
+                if is_placeholder_name(key) && !names.contains(&key) {
+                    names.push(key);
+                }
+                rest = &after[end + 2..];
+            }
+            None => break,
+        }
+    }
+    names
+}
+
+fn is_placeholder_name(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Templates by name
+#[derive(Debug, Clone, Default)]
+pub struct PromptLibrary {
+    templates: BTreeMap<String, PromptTemplate>,
+}
+
+impl PromptLibrary {
+    /// The built-in templates
+    pub fn builtin() -> Self {
+        let templates = BUILTIN_TEMPLATES
+            .iter()
+            .map(|(name, text)| {
+                let template =
+                    PromptTemplate::parse(name, text).expect("built-in templates are valid");
+                (name.to_string(), template)
+            })
+            .collect();
+        Self { templates }
+    }
+
+    /// The built-in templates plus those in `dir`. Files that fail to parse are
+    /// skipped with a warning
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut library = Self::builtin();
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read prompts directory {}", dir.display()))?;
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.extension().and_then(|e| e.to_str()) != Some(TEMPLATE_EXTENSION) {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let parsed = std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|text| PromptTemplate::parse(name, &text));
+            match parsed {
+                Ok(template) => {
+                    library.templates.insert(name.to_string(), template);
+                }
+                Err(e) => tracing::warn!("Skipping prompt template {}: {:#}", path.display(), e),
+            }
+        }
+        Ok(library)
+    }
+
+    /// Templates from `GEMINI_PROMPTS_DIR`, or only the built-in ones when it is
+    /// unset or can't be read
+    pub fn from_env() -> Self {
+        let Some(dir) = std::env::var_os(ENV_PROMPTS_DIR).filter(|d| !d.is_empty()) else {
+            return Self::builtin();
+        };
+        Self::load(Path::new(&dir)).unwrap_or_else(|e| {
+            tracing::warn!("{:#}; serving built-in prompts only", e);
+            Self::builtin()
+        })
+    }
+
+    /// Templates sorted by name
+    pub fn templates(&self) -> impl Iterator<Item = &PromptTemplate> {
+        self.templates.values()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PromptTemplate> {
+        self.templates.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_front_matter_and_infer_arguments() {
+        let template = PromptTemplate::parse(
+            "explain",
+            "+++\ndescription = \"Explain\"\n[[arguments]]\nname = \"context\"\nrequired = false\n+++\nExplain {{ error }}.\n{{context}}\n",
+        )
+        .unwrap();
+        assert_eq!(template.description.as_deref(), Some("Explain"));
+        let names: Vec<_> = template.arguments.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["context", "error"]);
+        assert!(template.arguments[1].required);
+
+        assert_eq!(
+            template.render(&values(&[("error", "E0382")])).unwrap(),
+            "Explain E0382."
+        );
+        assert_eq!(template.render(&values(&[])).unwrap_err(), "error");
+    }
+
+    #[test]
+    fn test_render_leaves_other_braces_alone() {
+        let template = PromptTemplate::parse("t", "Use {{ x }} in {{not a key}} {{").unwrap();
+        assert_eq!(
+            template.render(&values(&[("x", "json")])).unwrap(),
+            "Use json in {{not a key}} {{"
+        );
+        assert!(PromptTemplate::parse("t", "+++\ndescription = \"open\"\n").is_err());
+    }
+
+    #[test]
+    fn test_directory_overrides_builtin() {
+        let dir = std::env::temp_dir().join(format!("gemini-mcp-prompts-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("summarize-diff.md"), "Short summary of {{diff}}").unwrap();
+        std::fs::write(dir.join("broken.md"), "+++\nunknown = 1\n+++\nx").unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+        let library = PromptLibrary::load(&dir);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let library = library.unwrap();
+        let names: Vec<_> = library.templates().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["code-review", "summarize-diff"]);
+        let rendered = library
+            .get("summarize-diff")
+            .unwrap()
+            .render(&values(&[("diff", "+a")]))
+            .unwrap();
+        assert_eq!(rendered, "Short summary of +a");
+    }
+}
@@ -0,0 +1,211 @@
+//! Pseudo-terminal execution for Gemini CLI versions that behave differently, or
+//! refuse to run, without a TTY.
+//!
+//! With `GEMINI_USE_PTY=1` and the `pty` feature enabled, the CLI runs attached to a
+//! pseudo-terminal instead of pipes. A terminal merges stdout and stderr into one
+//! stream and may decorate it with colors, cursor movement and title updates, so each
+//! line is passed through [`strip_control_sequences`] before the stream-json parser
+//! sees it.
+
+use std::sync::Once;
+
+pub const ENV_USE_PTY: &str = "GEMINI_USE_PTY";
+
+/// Whether calls should run in a pseudo-terminal
+pub fn pty_requested() -> bool {
+    let requested = crate::gemini::env_flag(ENV_USE_PTY).unwrap_or(false);
+    if requested && !cfg!(feature = "pty") {
+        static WARN: Once = Once::new();
+        WARN.call_once(|| {
+            tracing::warn!(
+                "{} is set but this build lacks the 'pty' feature; using pipes",
+                ENV_USE_PTY
+            );
+        });
+        return false;
+    }
+    requested
+}
+
+/// Remove terminal escape sequences (CSI, OSC and two-byte escapes), carriage
+/// returns and other C0 control characters except tabs from one line of output
+pub fn strip_control_sequences(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' => match chars.next() {
+                // CSI: parameters and intermediates up to a final byte in @..~
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: up to BEL or ST (ESC \)
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\u{7}' {
+                            break;
+                        }
+                        if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                // Character set selection takes one more byte, e.g. ESC ( B
+                Some('(' | ')') => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            '\t' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(feature = "pty")]
+pub use imp::PtyChild;
+
+#[cfg(feature = "pty")]
+mod imp {
+    use anyhow::{Context, Result};
+    use portable_pty::{native_pty_system, Child, ChildKiller, CommandBuilder, MasterPty, PtySize};
+    use std::io::{BufRead, BufReader};
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    /// Wide enough that the CLI doesn't hard-wrap JSON lines to the terminal width
+    const PTY_COLS: u16 = 4096;
+    const PTY_ROWS: u16 = 50;
+    const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// A process running in a pseudo-terminal; killed on drop
+    pub struct PtyChild {
+        child: Box<dyn Child + Send + Sync>,
+        lines: mpsc::Receiver<Vec<u8>>,
+        // Closing the master hangs up the terminal, so keep it open while running
+        _master: Box<dyn MasterPty + Send>,
+    }
+
+    impl PtyChild {
+        /// Spawn the program, arguments, directory and environment of `cmd` in a new
+        /// pseudo-terminal
+        pub fn spawn(cmd: &std::process::Command) -> Result<Self> {
+            let pair = native_pty_system()
+                .openpty(PtySize {
+                    rows: PTY_ROWS,
+                    cols: PTY_COLS,
+                    pixel_width: 0,
+                    pixel_height: 0,
+                })
+                .context("Failed to open a pseudo-terminal")?;
+
+            let mut builder = CommandBuilder::new(cmd.get_program());
+            builder.args(cmd.get_args());
+            if let Some(dir) = cmd.get_current_dir() {
+                builder.cwd(dir);
+            }
+            for (key, value) in cmd.get_envs() {
+                match value {
+                    Some(value) => builder.env(key, value),
+                    None => builder.env_remove(key),
+                }
+            }
+            // A dumb terminal still counts as a TTY but keeps decoration to a minimum
+            if std::env::var_os("TERM").is_none() {
+                builder.env("TERM", "dumb");
+            }
+
+            let child = pair
+                .slave
+                .spawn_command(builder)
+                .context("Failed to spawn gemini in a pseudo-terminal")?;
+            // Only the child may hold the terminal, so reads end when it exits
+            drop(pair.slave);
+
+            let reader = pair
+                .master
+                .try_clone_reader()
+                .context("Failed to read from the pseudo-terminal")?;
+            let (tx, lines) = mpsc::channel(256);
+            std::thread::spawn(move || {
+                // Linux reports EIO instead of EOF once the child hangs up
+                for line in BufReader::new(reader).split(b'\n') {
+                    let Ok(line) = line else { break };
+                    if tx.blocking_send(line).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            Ok(Self {
+                child,
+                lines,
+                _master: pair.master,
+            })
+        }
+
+        /// Next raw output line, or `None` once the terminal is closed
+        pub async fn next_line(&mut self) -> Option<Vec<u8>> {
+            self.lines.recv().await
+        }
+
+        /// Wait for the process to exit, returning whether it succeeded and its exit code
+        pub async fn wait(&mut self) -> Result<(bool, Option<i32>)> {
+            loop {
+                if let Some(status) = self
+                    .child
+                    .try_wait()
+                    .context("Failed to wait for gemini command")?
+                {
+                    return Ok((status.success(), i32::try_from(status.exit_code()).ok()));
+                }
+                tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+            }
+        }
+
+        pub fn pid(&self) -> Option<u32> {
+            self.child.process_id()
+        }
+
+        pub fn kill(&mut self) {
+            let _ = self.child.kill();
+        }
+    }
+
+    impl Drop for PtyChild {
+        fn drop(&mut self) {
+            if matches!(self.child.try_wait(), Ok(None)) {
+                self.kill();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_control_sequences() {
+        assert_eq!(
+            strip_control_sequences("\u{1b}[32m{\"type\":\"init\"}\u{1b}[0m\r"),
+            "{\"type\":\"init\"}"
+        );
+        assert_eq!(
+            strip_control_sequences("\u{1b}]0;gemini\u{7}\u{1b}[?25lok\u{1b}(B"),
+            "ok"
+        );
+        assert_eq!(
+            strip_control_sequences("\u{1b}]8;;http://x\u{1b}\\link\u{8}\tend"),
+            "link\tend"
+        );
+        assert_eq!(strip_control_sequences("plain 中文"), "plain 中文");
+    }
+}
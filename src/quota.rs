@@ -0,0 +1,311 @@
+//! Per-model quota tracking with preemptive throttling.
+//!
+//! Limits come from `GEMINI_QUOTAS`, a comma-separated list of
+//! `model=requests:tokens` entries counted over a sliding one-minute window, e.g.
+//! `gemini-2.5-pro=5:250000,*=60:`. Either limit may be left empty (or `0`) for
+//! no limit, and models are matched like the pricing table (exact, longest
+//! prefix, then `*`). Each model gets its own window, even when matched by `*`.
+//!
+//! A call that would exceed its quota waits until the window frees up, as long as
+//! the wait fits in `GEMINI_QUOTA_MAX_WAIT_SECS` (default 30). Otherwise it is
+//! rejected up front with the time to retry, instead of failing mid-run when the
+//! API rejects it.
+
+use crate::pricing::lookup_model;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const ENV_QUOTAS: &str = "GEMINI_QUOTAS";
+pub const ENV_QUOTA_MAX_WAIT: &str = "GEMINI_QUOTA_MAX_WAIT_SECS";
+const DEFAULT_MAX_WAIT_SECS: u64 = 30;
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Limits for one model over a one-minute window; `None` means unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub requests_per_minute: Option<u64>,
+    pub tokens_per_minute: Option<u64>,
+}
+
+/// One admitted call within the window
+#[derive(Debug)]
+struct Usage {
+    id: u64,
+    at: Instant,
+    tokens: u64,
+}
+
+#[derive(Debug, Default)]
+struct Windows {
+    by_model: HashMap<String, VecDeque<Usage>>,
+    next_id: u64,
+}
+
+/// A call admitted under a quota; report its token usage with [`QuotaTracker::record_tokens`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Admission {
+    model: String,
+    id: u64,
+}
+
+/// Tracks recent usage per model against the configured limits
+#[derive(Debug, Default)]
+pub struct QuotaTracker {
+    limits: Vec<(String, Limits)>,
+    max_wait: Duration,
+    windows: Mutex<Windows>,
+}
+
+impl QuotaTracker {
+    pub fn new(limits: Vec<(String, Limits)>, max_wait: Duration) -> Self {
+        Self {
+            limits,
+            max_wait,
+            windows: Mutex::default(),
+        }
+    }
+
+    /// Tracker configured through `GEMINI_QUOTAS` and `GEMINI_QUOTA_MAX_WAIT_SECS`
+    pub fn from_env() -> Self {
+        let limits = std::env::var(ENV_QUOTAS)
+            .map(|v| parse_limits(&v))
+            .unwrap_or_default();
+        let max_wait = std::env::var(ENV_QUOTA_MAX_WAIT)
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_MAX_WAIT_SECS);
+        Self::new(limits, Duration::from_secs(max_wait))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.limits.is_empty()
+    }
+
+    /// Configured limits per model, in table order
+    pub fn limits(&self) -> &[(String, Limits)] {
+        &self.limits
+    }
+
+    /// Admit a call now if its model's quota allows it, otherwise return how long to wait.
+    ///
+    /// Returns `Ok(None)` for models without a quota.
+    pub fn try_admit(&self, model: &str, now: Instant) -> Result<Option<Admission>, Duration> {
+        let Some(limits) = lookup_model(&self.limits, model).copied() else {
+            return Ok(None);
+        };
+
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let window = windows.by_model.entry(model.to_string()).or_default();
+        while window
+            .front()
+            .is_some_and(|u| now.duration_since(u.at) >= WINDOW)
+        {
+            window.pop_front();
+        }
+
+        let wait = required_wait(window, limits, now);
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+
+        windows.next_id += 1;
+        let id = windows.next_id;
+        windows
+            .by_model
+            .entry(model.to_string())
+            .or_default()
+            .push_back(Usage {
+                id,
+                at: now,
+                tokens: 0,
+            });
+        Ok(Some(Admission {
+            model: model.to_string(),
+            id,
+        }))
+    }
+
+    /// Wait for the quota to allow a call, up to the configured maximum wait.
+    ///
+    /// On rejection returns the time after which a retry could succeed.
+    pub async fn acquire(&self, model: &str) -> Result<Option<Admission>, Duration> {
+        let mut waited = Duration::ZERO;
+        loop {
+            match self.try_admit(model, Instant::now()) {
+                Ok(admission) => return Ok(admission),
+                Err(wait) if waited + wait <= self.max_wait => {
+                    tokio::time::sleep(wait).await;
+                    waited += wait;
+                }
+                Err(wait) => return Err(wait),
+            }
+        }
+    }
+
+    /// Attribute the tokens a call actually used to its window entry
+    pub fn record_tokens(&self, admission: &Admission, tokens: u64) {
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(usage) = windows
+            .by_model
+            .get_mut(&admission.model)
+            .and_then(|w| w.iter_mut().find(|u| u.id == admission.id))
+        {
+            usage.tokens = tokens;
+        }
+    }
+}
+
+/// Time until the window has room for one more call under `limits`
+fn required_wait(window: &VecDeque<Usage>, limits: Limits, now: Instant) -> Duration {
+    let expires = |usage: &Usage| (usage.at + WINDOW).saturating_duration_since(now);
+    let mut wait = Duration::ZERO;
+
+    if let Some(rpm) = limits.requests_per_minute {
+        let len = window.len() as u64;
+        if len >= rpm {
+            // The oldest `len - rpm + 1` calls must leave the window
+            wait = wait.max(expires(&window[(len - rpm) as usize]));
+        }
+    }
+
+    if let Some(tpm) = limits.tokens_per_minute {
+        let mut used: u64 = window.iter().map(|u| u.tokens).sum();
+        for usage in window {
+            if used < tpm {
+                break;
+            }
+            used -= usage.tokens;
+            wait = wait.max(expires(usage));
+        }
+    }
+
+    wait
+}
+
+/// Parse `model=requests:tokens` entries separated by commas, skipping malformed ones
+pub fn parse_limits(value: &str) -> Vec<(String, Limits)> {
+    let limit = |s: &str| -> Option<Option<u64>> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Some(None);
+        }
+        s.parse::<u64>().ok().map(|n| (n > 0).then_some(n))
+    };
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let (model, limits) = entry.split_once('=')?;
+            let (requests, tokens) = limits.split_once(':').unwrap_or((limits, ""));
+            let model = model.trim();
+            let limits = Limits {
+                requests_per_minute: limit(requests)?,
+                tokens_per_minute: limit(tokens)?,
+            };
+            (!model.is_empty()).then(|| (model.to_string(), limits))
+        })
+        .collect()
+}
+
+/// Whole seconds to advertise for a retry, rounded up
+pub fn retry_secs(wait: Duration) -> u64 {
+    wait.as_secs() + u64::from(wait.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(spec: &str) -> QuotaTracker {
+        QuotaTracker::new(parse_limits(spec), Duration::from_secs(5))
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let limits = parse_limits("pro=5:250000, flash=60, tokens-only=:1000, bad, x=a:1");
+        assert_eq!(
+            limits,
+            vec![
+                (
+                    "pro".to_string(),
+                    Limits {
+                        requests_per_minute: Some(5),
+                        tokens_per_minute: Some(250_000),
+                    }
+                ),
+                (
+                    "flash".to_string(),
+                    Limits {
+                        requests_per_minute: Some(60),
+                        tokens_per_minute: None,
+                    }
+                ),
+                (
+                    "tokens-only".to_string(),
+                    Limits {
+                        requests_per_minute: None,
+                        tokens_per_minute: Some(1000),
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unconfigured_model_is_not_tracked() {
+        let quotas = tracker("pro=1:");
+        assert_eq!(quotas.try_admit("flash", Instant::now()), Ok(None));
+    }
+
+    #[test]
+    fn test_request_limit_throttles_until_window_frees() {
+        let quotas = tracker("pro=2:");
+        let start = Instant::now();
+        assert!(quotas.try_admit("pro", start).unwrap().is_some());
+        assert!(quotas
+            .try_admit("pro", start + Duration::from_secs(10))
+            .unwrap()
+            .is_some());
+
+        let wait = quotas
+            .try_admit("pro", start + Duration::from_secs(20))
+            .unwrap_err();
+        assert_eq!(wait, Duration::from_secs(40));
+
+        // Once the first call leaves the window there is room again
+        assert!(quotas
+            .try_admit("pro", start + Duration::from_secs(60))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_token_limit_uses_recorded_usage() {
+        let quotas = tracker("pro=:1000");
+        let start = Instant::now();
+        let first = quotas.try_admit("pro", start).unwrap().unwrap();
+        quotas.record_tokens(&first, 1200);
+
+        let wait = quotas
+            .try_admit("pro", start + Duration::from_secs(15))
+            .unwrap_err();
+        assert_eq!(wait, Duration::from_secs(45));
+    }
+
+    #[test]
+    fn test_wildcard_tracks_models_separately() {
+        let quotas = tracker("*=1:");
+        let now = Instant::now();
+        assert!(quotas.try_admit("pro", now).unwrap().is_some());
+        assert!(quotas.try_admit("flash", now).unwrap().is_some());
+        assert!(quotas.try_admit("pro", now).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_acquire_rejects_waits_beyond_maximum() {
+        let quotas = tracker("pro=1:");
+        assert!(quotas.acquire("pro").await.unwrap().is_some());
+        let wait = quotas.acquire("pro").await.unwrap_err();
+        assert!(wait > Duration::from_secs(5));
+        assert_eq!(retry_secs(Duration::from_millis(59_001)), 60);
+    }
+}
@@ -0,0 +1,195 @@
+//! Per-client request rate limiting.
+//!
+//! `GEMINI_RATE_LIMIT_RPM` sets how many Gemini calls each client may start per
+//! minute (unset or `0` for no limit). Each client has a token bucket holding up to
+//! `GEMINI_RATE_LIMIT_BURST` calls (default: the per-minute rate) that refills
+//! continuously. Over stdio the server has a single client, so the limit is global;
+//! over HTTP every MCP session counts as its own client.
+//!
+//! A call finding its bucket empty is rejected at once with [`RateLimited`], which
+//! says when the next call would be allowed, so a runaway agent loop is pushed back
+//! instead of piling up calls against the Gemini API.
+
+use crate::messages::{catalog, render};
+use crate::quota::retry_secs;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const ENV_RATE_LIMIT_RPM: &str = "GEMINI_RATE_LIMIT_RPM";
+pub const ENV_RATE_LIMIT_BURST: &str = "GEMINI_RATE_LIMIT_BURST";
+/// Key of the single client of a stdio server
+pub const GLOBAL_CLIENT: &str = "global";
+
+/// Calls per minute and the most that may be made back to back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub per_minute: u32,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// The limit configured through `GEMINI_RATE_LIMIT_*`, `None` when there is none
+    pub fn from_env() -> Option<Self> {
+        let number = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<u32>().ok())
+                .filter(|n| *n > 0)
+        };
+        let per_minute = number(ENV_RATE_LIMIT_RPM)?;
+        Some(Self {
+            per_minute,
+            burst: number(ENV_RATE_LIMIT_BURST).unwrap_or(per_minute),
+        })
+    }
+
+    fn per_sec(self) -> f64 {
+        f64::from(self.per_minute) / 60.0
+    }
+}
+
+/// Why a call was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimited {
+    pub client: String,
+    pub limit: RateLimit,
+    pub retry_after: Duration,
+}
+
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&render(
+            catalog().client_rate_limited,
+            &[&self.limit.per_minute, &retry_secs(self.retry_after)],
+        ))
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+impl RateLimited {
+    /// Error data for clients: the limit and how long to wait before retrying
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "error": "rate_limited",
+            "scope": "client",
+            "client": self.client,
+            "limit_per_minute": self.limit.per_minute,
+            "burst": self.limit.burst,
+            "retry_after_secs": retry_secs(self.retry_after),
+        })
+    }
+}
+
+/// Calls left in a client's bucket as of `updated`
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets per client
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    limit: Option<RateLimit>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: Option<RateLimit>) -> Self {
+        Self {
+            limit,
+            buckets: Mutex::default(),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(RateLimit::from_env())
+    }
+
+    pub fn limit(&self) -> Option<RateLimit> {
+        self.limit
+    }
+
+    /// Take one call from `client`'s bucket, or say how long until one is available
+    pub fn try_acquire(&self, client: &str, now: Instant) -> Result<(), RateLimited> {
+        let Some(limit) = self.limit else {
+            return Ok(());
+        };
+        let burst = f64::from(limit.burst);
+        let refilled = |bucket: &Bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            (bucket.tokens + elapsed * limit.per_sec()).min(burst)
+        };
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        // A full bucket is the same as none, so idle clients don't accumulate
+        buckets.retain(|key, bucket| key == client || refilled(bucket) < burst);
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        bucket.tokens = refilled(bucket);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(RateLimited {
+            client: client.to_string(),
+            limit,
+            retry_after: Duration::from_secs_f64((1.0 - bucket.tokens) / limit.per_sec()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(per_minute: u32, burst: u32) -> RateLimiter {
+        RateLimiter::new(Some(RateLimit { per_minute, burst }))
+    }
+
+    #[test]
+    fn test_bucket_allows_burst_then_refills() {
+        let limiter = limiter(6, 2);
+        let start = Instant::now();
+        assert!(limiter.try_acquire("a", start).is_ok());
+        assert!(limiter.try_acquire("a", start).is_ok());
+
+        let rejected = limiter.try_acquire("a", start).unwrap_err();
+        assert_eq!(retry_secs(rejected.retry_after), 10);
+        assert_eq!(rejected.to_json()["retry_after_secs"], 10);
+        assert_eq!(rejected.to_json()["client"], "a");
+
+        assert!(limiter
+            .try_acquire("a", start + Duration::from_secs(5))
+            .is_err());
+        assert!(limiter
+            .try_acquire("a", start + Duration::from_secs(10))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_clients_have_separate_buckets() {
+        let limiter = limiter(1, 1);
+        let now = Instant::now();
+        assert!(limiter.try_acquire("a", now).is_ok());
+        assert!(limiter.try_acquire("a", now).is_err());
+        assert!(limiter.try_acquire("b", now).is_ok());
+
+        // Full buckets are dropped once their clients go idle
+        let later = now + Duration::from_secs(120);
+        assert!(limiter.try_acquire("c", later).is_ok());
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_no_limit_admits_everything() {
+        let limiter = RateLimiter::new(None);
+        let now = Instant::now();
+        assert!((0..1000).all(|_| limiter.try_acquire(GLOBAL_CLIENT, now).is_ok()));
+    }
+}
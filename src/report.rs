@@ -0,0 +1,301 @@
+//! Usage report over the audit log, printed by `gemini-mcp-rs report`.
+
+use crate::audit::AuditRecord;
+use crate::pricing::PricingTable;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+const TOP_N: usize = 5; // Rows shown in each ranked section
+
+/// Aggregated view of the audit log
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    /// Earliest timestamp included, when the report is limited with `--since`
+    pub since: Option<String>,
+    pub calls: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    /// Sum of estimated costs in USD, when any call could be priced
+    pub estimated_cost_usd: Option<f64>,
+    pub cost_by_model: Vec<ModelCost>,
+    pub failures_by_class: Vec<Count>,
+    pub top_models: Vec<Count>,
+    /// Hours of the day (UTC) with the most calls
+    pub busiest_hours: Vec<Count>,
+}
+
+/// A labelled call count
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub calls: usize,
+}
+
+/// Estimated spend attributed to one model
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelCost {
+    pub model: String,
+    pub cost_usd: f64,
+}
+
+/// Parse a relative duration such as `90s`, `30m`, `12h`, `7d` or `2w`
+pub fn parse_since(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let amount: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration '{}': expected e.g. 12h, 7d or 2w", value))?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "" | "d" => 86_400,
+        "w" => 7 * 86_400,
+        _ => {
+            return Err(format!(
+                "Invalid duration unit '{}': use s, m, h, d or w",
+                unit
+            ))
+        }
+    };
+    amount
+        .checked_mul(unit_secs)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Duration '{}' is too large", value))
+}
+
+/// Aggregate records, keeping only those at or after `since` (an RFC 3339 UTC timestamp).
+///
+/// Records logged before pricing was configured are priced with `pricing`.
+pub fn build(records: &[AuditRecord], since: Option<&str>, pricing: &PricingTable) -> Report {
+    let mut report = Report {
+        since: since.map(String::from),
+        ..Default::default()
+    };
+    let mut failures: HashMap<String, usize> = HashMap::new();
+    let mut models: HashMap<String, usize> = HashMap::new();
+    let mut hours: HashMap<String, usize> = HashMap::new();
+    let mut costs: HashMap<String, f64> = HashMap::new();
+
+    // RFC 3339 UTC timestamps compare correctly as strings
+    for record in records
+        .iter()
+        .filter(|r| since.map_or(true, |s| r.timestamp.as_str() >= s))
+    {
+        report.calls += 1;
+        if record.success {
+            report.succeeded += 1;
+        } else {
+            report.failed += 1;
+            let class = record.failure.map_or("unknown", |f| f.as_str());
+            *failures.entry(class.to_string()).or_default() += 1;
+        }
+        if let Some(usage) = record.usage {
+            report.input_tokens += usage.input_tokens;
+            report.output_tokens += usage.output_tokens;
+            report.total_tokens += usage.total_tokens;
+        }
+        let model = record.model.as_deref().unwrap_or("(default)");
+        *models.entry(model.to_string()).or_default() += 1;
+        let cost = record
+            .cost_usd
+            .or_else(|| pricing.estimate(record.model.as_deref(), record.usage));
+        if let Some(cost) = cost {
+            *report.estimated_cost_usd.get_or_insert(0.0) += cost;
+            *costs.entry(model.to_string()).or_default() += cost;
+        }
+        if let Some(hour) = record.timestamp.get(11..13) {
+            *hours.entry(format!("{}:00", hour)).or_default() += 1;
+        }
+    }
+
+    report.failures_by_class = ranked(failures, usize::MAX);
+    report.top_models = ranked(models, TOP_N);
+    report.busiest_hours = ranked(hours, TOP_N);
+
+    let mut cost_by_model: Vec<ModelCost> = costs
+        .into_iter()
+        .map(|(model, cost_usd)| ModelCost { model, cost_usd })
+        .collect();
+    cost_by_model.sort_by(|a, b| {
+        b.cost_usd
+            .total_cmp(&a.cost_usd)
+            .then_with(|| a.model.cmp(&b.model))
+    });
+    cost_by_model.truncate(TOP_N);
+    report.cost_by_model = cost_by_model;
+    report
+}
+
+/// Sort counts by calls (descending), then name, and keep the first `limit`
+fn ranked(counts: HashMap<String, usize>, limit: usize) -> Vec<Count> {
+    let mut rows: Vec<Count> = counts
+        .into_iter()
+        .map(|(name, calls)| Count { name, calls })
+        .collect();
+    rows.sort_by(|a, b| b.calls.cmp(&a.calls).then_with(|| a.name.cmp(&b.name)));
+    rows.truncate(limit);
+    rows
+}
+
+/// Render the report as a plain-text table
+pub fn render_table(report: &Report) -> String {
+    let mut out = match &report.since {
+        Some(since) => format!("Usage report (since {})\n\n", since),
+        None => "Usage report (all time)\n\n".to_string(),
+    };
+
+    let row = |out: &mut String, name: &str, value: &dyn std::fmt::Display| {
+        writeln!(out, "  {:<28} {:>10}", name, value).ok();
+    };
+    row(&mut out, "Calls", &report.calls);
+    row(&mut out, "Succeeded", &report.succeeded);
+    row(&mut out, "Failed", &report.failed);
+    row(&mut out, "Input tokens", &report.input_tokens);
+    row(&mut out, "Output tokens", &report.output_tokens);
+    row(&mut out, "Total tokens", &report.total_tokens);
+    if let Some(cost) = report.estimated_cost_usd {
+        row(&mut out, "Estimated cost (USD)", &format!("{:.4}", cost));
+    }
+
+    if !report.cost_by_model.is_empty() {
+        writeln!(out, "\nEstimated cost by model (USD)").ok();
+        for entry in &report.cost_by_model {
+            row(&mut out, &entry.model, &format!("{:.4}", entry.cost_usd));
+        }
+    }
+
+    for (title, rows) in [
+        ("Failures by class", &report.failures_by_class),
+        ("Top models", &report.top_models),
+        ("Busiest hours (UTC)", &report.busiest_hours),
+    ] {
+        if rows.is_empty() {
+            continue;
+        }
+        writeln!(out, "\n{}", title).ok();
+        for count in rows {
+            row(&mut out, &count.name, &count.calls);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini::{FailureKind, TokenUsage};
+    use crate::sessions::Attribution;
+
+    fn record(timestamp: &str, model: Option<&str>, failure: Option<FailureKind>) -> AuditRecord {
+        AuditRecord {
+            timestamp: timestamp.to_string(),
+            tool: "gemini".to_string(),
+            session_id: None,
+            model: model.map(String::from),
+            success: failure.is_none(),
+            failure,
+            duration_ms: 100,
+            usage: Some(TokenUsage {
+                input_tokens: 10,
+                output_tokens: 5,
+                total_tokens: 15,
+            }),
+            cost_usd: None,
+            attribution: Attribution::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_since("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_since("12h"), Ok(Duration::from_secs(12 * 3600)));
+        assert_eq!(parse_since("7d"), Ok(Duration::from_secs(7 * 86_400)));
+        assert_eq!(parse_since("7"), Ok(Duration::from_secs(7 * 86_400)));
+        assert_eq!(parse_since("2w"), Ok(Duration::from_secs(14 * 86_400)));
+        assert!(parse_since("d").is_err());
+        assert!(parse_since("7y").is_err());
+    }
+
+    #[test]
+    fn test_build_aggregates_records() {
+        let records = vec![
+            record("2024-03-01T09:10:00Z", Some("gemini-2.5-pro"), None),
+            record("2024-03-02T14:00:00Z", Some("gemini-2.5-pro"), None),
+            record(
+                "2024-03-02T14:30:00Z",
+                Some("gemini-2.5-flash"),
+                Some(FailureKind::Timeout),
+            ),
+            record("2024-03-03T14:45:00Z", None, Some(FailureKind::ExitCode)),
+        ];
+
+        let report = build(&records, None, &PricingTable::default());
+        assert_eq!(report.calls, 4);
+        assert_eq!(report.succeeded, 2);
+        assert_eq!(report.failed, 2);
+        assert_eq!(report.total_tokens, 60);
+        assert_eq!(
+            report.top_models[0],
+            Count {
+                name: "gemini-2.5-pro".to_string(),
+                calls: 2
+            }
+        );
+        assert_eq!(report.busiest_hours[0].name, "14:00");
+        assert_eq!(report.busiest_hours[0].calls, 3);
+        assert_eq!(report.failures_by_class.len(), 2);
+        assert!(report.estimated_cost_usd.is_none());
+
+        let recent = build(
+            &records,
+            Some("2024-03-02T14:15:00Z"),
+            &PricingTable::default(),
+        );
+        assert_eq!(recent.calls, 2);
+        assert_eq!(recent.succeeded, 0);
+    }
+
+    #[test]
+    fn test_build_prices_unpriced_records() {
+        let records = vec![
+            AuditRecord {
+                cost_usd: Some(1.0),
+                ..record("2024-03-01T09:10:00Z", Some("gemini-2.5-pro"), None)
+            },
+            // 10 input + 5 output tokens at 100_000 USD per million each
+            record("2024-03-01T10:10:00Z", Some("gemini-2.5-flash"), None),
+            record("2024-03-01T11:10:00Z", Some("unpriced"), None),
+        ];
+        let pricing = PricingTable::parse("gemini-2.5-flash=100000:100000");
+
+        let report = build(&records, None, &pricing);
+        let total = report.estimated_cost_usd.unwrap();
+        assert!((total - 2.5).abs() < 1e-9);
+        assert_eq!(report.cost_by_model.len(), 2);
+        assert_eq!(report.cost_by_model[0].model, "gemini-2.5-flash");
+        assert!(render_table(&report).contains("Estimated cost by model"));
+    }
+
+    #[test]
+    fn test_render_table_lists_sections() {
+        let records = vec![record(
+            "2024-03-01T09:10:00Z",
+            Some("gemini-2.5-pro"),
+            Some(FailureKind::Timeout),
+        )];
+        let table = render_table(&build(&records, None, &PricingTable::default()));
+        assert!(table.starts_with("Usage report (all time)"));
+        assert!(table.contains("Failures by class"));
+        assert!(table.contains("timeout"));
+        assert!(table.contains("09:00"));
+    }
+}
@@ -0,0 +1,274 @@
+//! Code review of git diffs, for `gemini_review`.
+//!
+//! A diff too large for one prompt is split by [`chunk`] into pieces of whole files, or
+//! of whole hunks under the file's headers for a file too large on its own. Each piece
+//! is reviewed in its own call with [`REVIEW_INSTRUCTIONS`] as the system prompt, and
+//! [`parse_findings`] reads the findings from the JSON each reply carries.
+
+use crate::validate;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Largest piece of diff sent in one call
+pub const MAX_CHUNK_BYTES: usize = 96 * 1024;
+/// Most calls one review may take, which bounds the diff to about 1 MiB
+pub const MAX_CHUNKS: usize = 12;
+
+/// System prompt for every review call
+pub const REVIEW_INSTRUCTIONS: &str = "You are a meticulous senior engineer reviewing a \
+code change given as a unified diff. Report real problems in the changed lines: bugs, \
+security issues, race conditions, error handling gaps, performance traps, API misuse and \
+missing tests, plus readability issues worth fixing. Do not praise, summarize or restate \
+the change, and do not report problems outside the diff. You may read other files in the \
+repository for context but must not modify anything. Reply with only a JSON array, one \
+object per finding: {\"file\": path as in the diff, \"line\": line number in the new \
+version of the file (null if not about one line), \"severity\": \"critical\", \"major\", \
+\"minor\" or \"nit\", \"comment\": the problem and how to fix it}. Reply with [] when \
+there is nothing to report.";
+
+/// How serious a finding is, most serious first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Bugs, security holes, data loss
+    Critical,
+    /// Likely problems that should be fixed before merging
+    Major,
+    Minor,
+    /// Style and naming
+    Nit,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Critical => "critical",
+            Self::Major => "major",
+            Self::Minor => "minor",
+            Self::Nit => "nit",
+        }
+    }
+
+    /// Read a severity, accepting the scales models commonly use instead; anything
+    /// unrecognized is minor
+    fn parse(value: &str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "critical" | "blocker" | "high" | "error" => Self::Critical,
+            "major" | "medium" | "warning" => Self::Major,
+            "nit" | "nitpick" | "style" | "info" | "trivial" => Self::Nit,
+            _ => Self::Minor,
+        }
+    }
+}
+
+/// One problem the review found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub file: String,
+    /// Line in the new version of the file, when the finding is about one line
+    pub line: Option<u64>,
+    pub severity: Severity,
+    pub comment: String,
+}
+
+/// Whether `range` can be passed to `git diff` as a revision range. Options and
+/// whitespace are refused so the range can't change what git does
+pub fn is_valid_ref_range(range: &str) -> bool {
+    !range.is_empty()
+        && !range.starts_with('-')
+        && !range.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+/// Split a unified diff into pieces of at most `max_bytes` where possible, keeping
+/// files whole and, for files that don't fit, hunks whole
+pub fn chunk(diff: &str, max_bytes: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for section in file_sections(diff) {
+        let pieces = if section.len() > max_bytes {
+            split_hunks(section, max_bytes)
+        } else {
+            vec![section.to_string()]
+        };
+        for piece in pieces {
+            if !current.is_empty() && current.len() + piece.len() > max_bytes {
+                chunks.push(std::mem::take(&mut current));
+            }
+            current.push_str(&piece);
+        }
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// The prompt reviewing one piece of the diff
+pub fn prompt(diff: &str, focus: Option<&str>, part: usize, parts: usize) -> String {
+    let mut prompt = String::from("Review this change.");
+    if parts > 1 {
+        prompt.push_str(&format!(
+            " This is part {} of {} of the diff; review only this part.",
+            part, parts
+        ));
+    }
+    if let Some(focus) = focus.map(str::trim).filter(|f| !f.is_empty()) {
+        prompt.push_str(&format!(" Focus especially on: {}", focus));
+    }
+    format!("{}\n\n```diff\n{}\n```", prompt, diff.trim_end())
+}
+
+/// The findings in a review reply: a JSON array of findings, or an object holding one
+/// under `findings`, possibly with text around it. Entries without a comment are
+/// skipped, and field names and severities are read leniently
+pub fn parse_findings(reply: &str) -> Result<Vec<Finding>, String> {
+    let value = find_json(reply).ok_or_else(|| "no JSON found in the reply".to_string())?;
+    let entries = match &value {
+        Value::Array(entries) => entries,
+        Value::Object(object) => match object.get("findings") {
+            Some(Value::Array(entries)) => entries,
+            _ => return Err("expected a JSON array of findings".to_string()),
+        },
+        _ => return Err("expected a JSON array of findings".to_string()),
+    };
+    let text = |entry: &Value, keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| entry.get(*k).and_then(Value::as_str))
+            .map(|s| s.trim().to_string())
+    };
+    Ok(entries
+        .iter()
+        .filter_map(|entry| {
+            let comment =
+                text(entry, &["comment", "message", "description"]).filter(|c| !c.is_empty())?;
+            let line = entry.get("line").and_then(|line| match line {
+                Value::Number(n) => n.as_u64(),
+                Value::String(s) => s.trim().parse().ok(),
+                _ => None,
+            });
+            Some(Finding {
+                file: text(entry, &["file", "path"]).unwrap_or_default(),
+                line,
+                severity: Severity::parse(&text(entry, &["severity"]).unwrap_or_default()),
+                comment,
+            })
+        })
+        .collect())
+}
+
+/// The JSON document in a reply: all of it, or else the text from the first `[` or
+/// `{` to the last matching bracket
+fn find_json(reply: &str) -> Option<Value> {
+    if let Ok(value) = serde_json::from_str(validate::extract_json(reply)) {
+        return Some(value);
+    }
+    [('[', ']'), ('{', '}')].iter().find_map(|&(open, close)| {
+        let start = reply.find(open)?;
+        let end = reply.rfind(close).filter(|&end| end > start)?;
+        serde_json::from_str(&reply[start..=end]).ok()
+    })
+}
+
+/// The diff split before each `diff --git` header, or whole when it has none
+fn file_sections(diff: &str) -> Vec<&str> {
+    let mut starts: Vec<usize> = line_starts(diff)
+        .filter(|&i| diff[i..].starts_with("diff --git "))
+        .collect();
+    if starts.first() != Some(&0) {
+        starts.insert(0, 0);
+    }
+    starts
+        .iter()
+        .zip(starts.iter().skip(1).chain([&diff.len()]))
+        .map(|(&start, &end)| &diff[start..end])
+        .filter(|section| !section.is_empty())
+        .collect()
+}
+
+/// One file's diff as pieces of whole hunks of at most `max_bytes` where possible,
+/// each led by the file's headers
+fn split_hunks(section: &str, max_bytes: usize) -> Vec<String> {
+    let starts: Vec<usize> = line_starts(section)
+        .filter(|&i| section[i..].starts_with("@@"))
+        .collect();
+    let Some(&first) = starts.first() else {
+        return vec![section.to_string()];
+    };
+    let header = &section[..first];
+    let mut pieces = Vec::new();
+    let mut current = header.to_string();
+    for (&start, &end) in starts
+        .iter()
+        .zip(starts.iter().skip(1).chain([&section.len()]))
+    {
+        let hunk = &section[start..end];
+        if current.len() > header.len() && current.len() + hunk.len() > max_bytes {
+            pieces.push(std::mem::replace(&mut current, header.to_string()));
+        }
+        current.push_str(hunk);
+    }
+    pieces.push(current);
+    pieces
+}
+
+/// Byte offsets at which the lines of `text` start
+fn line_starts(text: &str) -> impl Iterator<Item = usize> + '_ {
+    std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_FILES: &str = "diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-a\n+b\n@@ -9 +9 @@\n-c\n+d\ndiff --git a/b.rs b/b.rs\n--- a/b.rs\n+++ b/b.rs\n@@ -1 +1 @@\n-x\n+y\n";
+
+    #[test]
+    fn test_chunk_keeps_files_and_hunks_whole() {
+        assert_eq!(chunk(TWO_FILES, 1024), vec![TWO_FILES.to_string()]);
+
+        let chunks = chunk(TWO_FILES, 60);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[0].starts_with("diff --git a/a.rs") && chunks[0].contains("+b\n"));
+        assert!(chunks[1].starts_with("diff --git a/a.rs") && chunks[1].ends_with("+d\n"));
+        assert!(!chunks[1].contains("+b\n"));
+        assert!(chunks[2].starts_with("diff --git a/b.rs"));
+        assert_eq!(chunk("  \n", 60), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_parse_findings_reads_loose_replies() {
+        let reply = "Here you go:\n```json\n{\"findings\": [\
+            {\"path\": \"src/a.rs\", \"line\": \"12\", \"severity\": \"HIGH\", \"message\": \"Unchecked unwrap\"},\
+            {\"file\": \"src/b.rs\", \"line\": null, \"severity\": \"nit\", \"comment\": \"Rename x\"},\
+            {\"file\": \"src/c.rs\", \"comment\": \"\"}]}\n```";
+        let findings = parse_findings(reply).unwrap();
+        assert_eq!(
+            findings,
+            vec![
+                Finding {
+                    file: "src/a.rs".to_string(),
+                    line: Some(12),
+                    severity: Severity::Critical,
+                    comment: "Unchecked unwrap".to_string(),
+                },
+                Finding {
+                    file: "src/b.rs".to_string(),
+                    line: None,
+                    severity: Severity::Nit,
+                    comment: "Rename x".to_string(),
+                },
+            ]
+        );
+        assert_eq!(parse_findings("[]").unwrap(), vec![]);
+        assert!(parse_findings("Looks good to me").is_err());
+    }
+
+    #[test]
+    fn test_ref_range_validation() {
+        assert!(is_valid_ref_range("main..HEAD"));
+        assert!(is_valid_ref_range("origin/main...feature/x"));
+        assert!(!is_valid_ref_range("--output=/tmp/x"));
+        assert!(!is_valid_ref_range("main HEAD"));
+        assert!(!is_valid_ref_range(""));
+    }
+}
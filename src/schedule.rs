@@ -0,0 +1,205 @@
+//! Cron-like schedules for recurring background jobs.
+//!
+//! Expressions use the five standard cron fields, evaluated in UTC:
+//! `minute hour day-of-month month day-of-week`. Each field accepts `*`, numbers,
+//! ranges (`1-5`), lists (`1,15`) and steps (`*/15`, `0-30/10`); day-of-week runs
+//! from 0 (Sunday) to 6, with 7 also meaning Sunday. As in classic cron, when both
+//! day fields are restricted a day matching either one qualifies. The shortcuts
+//! `@hourly`, `@daily` (or `@nightly`, `@midnight`), `@weekly`, `@monthly` and
+//! `@yearly` are also accepted.
+
+use crate::history::civil_from_days;
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 3600;
+const DAY: u64 = 86_400;
+const SEARCH_LIMIT_DAYS: u64 = 5 * 366; // Give up on expressions that never match (e.g. Feb 31)
+
+/// A parsed cron expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    day_of_month_any: bool,
+    day_of_week_any: bool,
+}
+
+impl Schedule {
+    /// Parse a five-field cron expression or `@` shortcut
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@nightly" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields[..] else {
+            return Err(format!(
+                "Invalid schedule '{}': expected 5 fields (minute hour day-of-month month day-of-week)",
+                expression.trim()
+            ));
+        };
+
+        let mut days_of_week = parse_field(dow, 0, 7)?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week |= 1; // 7 is Sunday too
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days_of_month: parse_field(dom, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            days_of_week,
+            day_of_month_any: dom == "*",
+            day_of_week_any: dow == "*",
+        })
+    }
+
+    /// First matching minute strictly after `after` (Unix seconds), if any
+    pub fn next_after(&self, after: u64) -> Option<u64> {
+        let mut t = (after / MINUTE + 1) * MINUTE;
+        let limit = after + SEARCH_LIMIT_DAYS * DAY;
+        while t <= limit {
+            let days = t / DAY;
+            if !self.matches_day(days) {
+                t = (days + 1) * DAY;
+                continue;
+            }
+            let hour = (t % DAY) / HOUR;
+            if !bit(self.hours, hour) {
+                t = (t / HOUR + 1) * HOUR;
+                continue;
+            }
+            if bit(self.minutes, (t % HOUR) / MINUTE) {
+                return Some(t);
+            }
+            t += MINUTE;
+        }
+        None
+    }
+
+    fn matches_day(&self, days: u64) -> bool {
+        let (_, month, day) = civil_from_days(days as i64);
+        if !bit(self.months, u64::from(month)) {
+            return false;
+        }
+        let weekday = (days + 4) % 7; // 1970-01-01 was a Thursday
+        let dom = bit(self.days_of_month, u64::from(day));
+        let dow = bit(self.days_of_week, weekday);
+        match (self.day_of_month_any, self.day_of_week_any) {
+            (true, true) => true,
+            (false, true) => dom,
+            (true, false) => dow,
+            (false, false) => dom || dow,
+        }
+    }
+}
+
+fn bit(mask: u64, value: u64) -> bool {
+    mask & (1 << value) != 0
+}
+
+/// Parse one field into a bit mask of allowed values within `min..=max`
+fn parse_field(field: &str, min: u64, max: u64) -> Result<u64, String> {
+    let invalid = || {
+        format!(
+            "Invalid schedule field '{}' (allowed {}-{})",
+            field, min, max
+        )
+    };
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u64>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                start.parse().map_err(|_| invalid())?,
+                end.parse().map_err(|_| invalid())?,
+            )
+        } else {
+            let value: u64 = range.parse().map_err(|_| invalid())?;
+            // `5/10` means "from 5 every 10"
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-03-01T00:00:00Z, a Friday
+    const MARCH_1_2024: u64 = 1_709_251_200;
+
+    #[test]
+    fn test_parse_rejects_invalid_expressions() {
+        assert!(Schedule::parse("* * * *").is_err());
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+        assert!(Schedule::parse("5-1 * * * *").is_err());
+        assert!(Schedule::parse("a * * * *").is_err());
+        assert!(Schedule::parse("@daily").is_ok());
+    }
+
+    #[test]
+    fn test_next_after_daily() {
+        let nightly = Schedule::parse("30 2 * * *").unwrap();
+        assert_eq!(
+            nightly.next_after(MARCH_1_2024),
+            Some(MARCH_1_2024 + 2 * HOUR + 30 * MINUTE)
+        );
+        // Strictly after: a call at the exact match time moves to the next day
+        assert_eq!(
+            nightly.next_after(MARCH_1_2024 + 2 * HOUR + 30 * MINUTE),
+            Some(MARCH_1_2024 + DAY + 2 * HOUR + 30 * MINUTE)
+        );
+    }
+
+    #[test]
+    fn test_next_after_steps_and_weekdays() {
+        let quarter_hours = Schedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            quarter_hours.next_after(MARCH_1_2024 + 16 * MINUTE),
+            Some(MARCH_1_2024 + 30 * MINUTE)
+        );
+
+        // Mondays at 09:00; March 1 2024 is a Friday, so the next is March 4
+        let monday = Schedule::parse("0 9 * * 1").unwrap();
+        assert_eq!(
+            monday.next_after(MARCH_1_2024),
+            Some(MARCH_1_2024 + 3 * DAY + 9 * HOUR)
+        );
+
+        let sunday = Schedule::parse("0 0 * * 7").unwrap();
+        assert_eq!(
+            sunday.next_after(MARCH_1_2024),
+            Some(MARCH_1_2024 + 2 * DAY)
+        );
+    }
+
+    #[test]
+    fn test_next_after_impossible_date() {
+        let never = Schedule::parse("0 0 31 2 *").unwrap();
+        assert_eq!(never.next_after(MARCH_1_2024), None);
+    }
+}
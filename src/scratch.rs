@@ -0,0 +1,561 @@
+//! Scratch copies of a workspace for diff-preview runs, and applying their patches.
+//!
+//! A preview copies the working tree into a temporary directory, records the copy as
+//! a baseline commit in a throwaway git repository, lets the CLI edit the copy, and
+//! returns the changes as a unified patch. The real tree is never touched until the
+//! patch is applied with [`apply_patch`]. Requires `git` on PATH.
+//!
+//! Inside a git repository only tracked and untracked-but-not-ignored files are
+//! copied, so build output and dependencies stay behind. Elsewhere everything except
+//! `.git` is copied, up to [`MAX_COPY_BYTES`].
+//!
+//! Each call gets its own directory under `GEMINI_SCRATCH_ROOT` (default: a
+//! `gemini-mcp-rs-scratch` directory in the system temp dir), which is kept after the
+//! call so it can be inspected. [`ScratchRoot::collect_garbage`] removes directories
+//! older than `GEMINI_SCRATCH_MAX_AGE_SECS` (default 1 day), then the oldest ones
+//! while the total exceeds `GEMINI_SCRATCH_MAX_MB` (default 2048). Directories of
+//! calls still running are never removed.
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Largest working tree a preview will copy
+pub const MAX_COPY_BYTES: u64 = 512 * 1024 * 1024;
+
+pub const ENV_SCRATCH_ROOT: &str = "GEMINI_SCRATCH_ROOT";
+pub const ENV_SCRATCH_MAX_AGE: &str = "GEMINI_SCRATCH_MAX_AGE_SECS";
+pub const ENV_SCRATCH_MAX_MB: &str = "GEMINI_SCRATCH_MAX_MB";
+const DEFAULT_SCRATCH_DIR: &str = "gemini-mcp-rs-scratch";
+const DEFAULT_MAX_AGE_SECS: u64 = 24 * 3600;
+const DEFAULT_MAX_MB: u64 = 2048;
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// Directory holding per-call scratch directories, and its cleanup policy
+#[derive(Debug)]
+pub struct ScratchRoot {
+    root: PathBuf,
+    max_age: Duration,
+    max_bytes: u64,
+    /// Directories of calls still running, which garbage collection must skip
+    active: Mutex<HashSet<PathBuf>>,
+    next_id: AtomicU64,
+}
+
+/// What a garbage collection pass removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    pub removed: usize,
+    pub freed_bytes: u64,
+}
+
+impl ScratchRoot {
+    pub fn new(root: PathBuf, max_age: Duration, max_bytes: u64) -> Self {
+        Self {
+            root,
+            max_age,
+            max_bytes,
+            active: Mutex::default(),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Root and limits configured through the `GEMINI_SCRATCH_*` variables
+    pub fn from_env() -> Self {
+        let root = std::env::var_os(ENV_SCRATCH_ROOT)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::temp_dir().join(DEFAULT_SCRATCH_DIR));
+        let number = |name: &str, default: u64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        Self::new(
+            root,
+            Duration::from_secs(number(ENV_SCRATCH_MAX_AGE, DEFAULT_MAX_AGE_SECS)),
+            number(ENV_SCRATCH_MAX_MB, DEFAULT_MAX_MB).saturating_mul(BYTES_PER_MB),
+        )
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Create a fresh directory for one call, protected from garbage collection
+    /// until released
+    fn allocate(&self, label: &str) -> std::io::Result<PathBuf> {
+        let secs = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let dir = self.root.join(format!(
+            "{}-{}-{}-{}",
+            label,
+            secs,
+            std::process::id(),
+            self.next_id.fetch_add(1, Ordering::Relaxed)
+        ));
+        self.active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(dir.clone());
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            self.release(&dir);
+            return Err(e);
+        }
+        Ok(dir)
+    }
+
+    fn release(&self, dir: &Path) {
+        self.active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(dir);
+    }
+
+    /// Remove expired directories, then the oldest ones while over the size limit
+    pub fn collect_garbage(&self, now: SystemTime) -> GcStats {
+        let Ok(entries) = std::fs::read_dir(&self.root) else {
+            return GcStats::default();
+        };
+        let active = self
+            .active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let mut dirs: Vec<(SystemTime, u64, PathBuf)> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir() && !active.contains(path))
+            .filter_map(|path| {
+                let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+                Some((modified, dir_size(&path), path))
+            })
+            .collect();
+        dirs.sort_by_key(|(modified, _, _)| *modified);
+
+        let mut total: u64 = dirs.iter().map(|(_, size, _)| size).sum();
+        let mut stats = GcStats::default();
+        for (modified, size, path) in dirs {
+            let expired = now.duration_since(modified).unwrap_or_default() > self.max_age;
+            if !expired && total <= self.max_bytes {
+                continue;
+            }
+            if std::fs::remove_dir_all(&path).is_ok() {
+                total = total.saturating_sub(size);
+                stats.removed += 1;
+                stats.freed_bytes += size;
+            }
+        }
+        stats
+    }
+}
+
+/// Total size of the files under `path`, not following symlinks
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            _ => entry.metadata().map_or(0, |m| m.len()),
+        })
+        .sum()
+}
+
+/// A copy of a working tree in a per-call scratch directory. The directory stays on
+/// disk after drop until garbage collection removes it
+#[derive(Debug)]
+pub struct Scratch {
+    dir: PathBuf,
+    root: Arc<ScratchRoot>,
+}
+
+impl Scratch {
+    /// Copy `source` into a new scratch directory and commit it as the baseline
+    pub fn create(root: &Arc<ScratchRoot>, source: &Path) -> Result<Self> {
+        let files = list_files(source)?;
+        let total: u64 = files
+            .iter()
+            .filter_map(|f| std::fs::symlink_metadata(source.join(f)).ok())
+            .map(|m| m.len())
+            .sum();
+        if total > MAX_COPY_BYTES {
+            bail!(
+                "Working tree is too large to preview ({} bytes, limit {})",
+                total,
+                MAX_COPY_BYTES
+            );
+        }
+
+        let dir = root.allocate("preview").with_context(|| {
+            format!("Failed to create a directory in {}", root.root().display())
+        })?;
+        let scratch = Self {
+            dir,
+            root: Arc::clone(root),
+        };
+        if let Err(e) = scratch.populate(source, &files) {
+            let _ = std::fs::remove_dir_all(&scratch.dir);
+            return Err(e);
+        }
+        Ok(scratch)
+    }
+
+    fn populate(&self, source: &Path, files: &[PathBuf]) -> Result<()> {
+        for file in files {
+            copy_entry(&source.join(file), &self.dir.join(file))
+                .with_context(|| format!("Failed to copy {}", file.display()))?;
+        }
+        self.git(&["init", "-q"])?;
+        self.git(&["add", "-A"])?;
+        self.git(&[
+            "commit",
+            "-q",
+            "--allow-empty",
+            "--no-verify",
+            "-m",
+            "baseline",
+        ])?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Unified diff of every change made since the baseline, including new and
+    /// deleted files. Binary changes are included in git's binary patch format
+    pub fn diff(&self) -> Result<String> {
+        self.git(&["add", "-A"])?;
+        self.git(&[
+            "diff",
+            "--cached",
+            "--binary",
+            "--no-color",
+            "--no-ext-diff",
+        ])
+    }
+
+    fn git(&self, args: &[&str]) -> Result<String> {
+        let output = git(&self.dir, args, None)?;
+        if !output.status.success() {
+            bail!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Apply a patch from a diff preview to `dir`, all or nothing.
+///
+/// The patch is checked first; if any hunk conflicts with the current files, nothing
+/// is changed and `Ok(Err(..))` carries git's report of the conflicts. With `dry_run`
+/// only the check runs. On success returns a per-file summary of the changes.
+pub fn apply_patch(dir: &Path, patch: &str, dry_run: bool) -> Result<Result<String, String>> {
+    if let Err(conflicts) = check_patch(dir, patch)? {
+        return Ok(Err(conflicts));
+    }
+    let args = apply_args(dir);
+    let mut args: Vec<&str> = args.iter().map(String::as_str).collect();
+    args.push("--stat");
+    let stat = git(dir, &args, Some(patch))?;
+    args.pop();
+    let stat = String::from_utf8_lossy(&stat.stdout).trim_end().to_string();
+    if dry_run {
+        return Ok(Ok(stat));
+    }
+
+    let applied = git(dir, &args, Some(patch))?;
+    if !applied.status.success() {
+        bail!(
+            "git apply failed: {}",
+            String::from_utf8_lossy(&applied.stderr).trim()
+        );
+    }
+    Ok(Ok(stat))
+}
+
+/// Check that a patch applies cleanly to `dir` without changing anything
+/// (`git apply --check`). `Ok(Err(..))` carries git's report of the conflicts
+pub fn check_patch(dir: &Path, patch: &str) -> Result<Result<(), String>> {
+    let mut args = apply_args(dir);
+    args.push("--check".to_string());
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let check = git(dir, &args, Some(patch))?;
+    if !check.status.success() {
+        return Ok(Err(String::from_utf8_lossy(&check.stderr)
+            .trim()
+            .to_string()));
+    }
+    Ok(Ok(()))
+}
+
+/// Unified diff of a revision range in the repository at `dir`, e.g. `main..HEAD`, or
+/// of the working tree against a single revision. `Ok(Err(..))` carries git's report
+/// when it can't resolve the range
+pub fn diff_range(dir: &Path, range: &str) -> Result<Result<String, String>> {
+    let output = git(
+        dir,
+        &["diff", "--no-color", "--no-ext-diff", range, "--"],
+        None,
+    )?;
+    if !output.status.success() {
+        return Ok(Err(String::from_utf8_lossy(&output.stderr)
+            .trim()
+            .to_string()));
+    }
+    Ok(Ok(String::from_utf8_lossy(&output.stdout).into_owned()))
+}
+
+/// `git apply` arguments for patches relative to `dir`
+fn apply_args(dir: &Path) -> Vec<String> {
+    // Patches are relative to `dir`; inside a repository git resolves them from the top
+    // level, so point it back at the subdirectory
+    let prefix = git(dir, &["rev-parse", "--show-prefix"], None)
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_default();
+    let mut args = vec!["apply".to_string(), "--whitespace=nowarn".to_string()];
+    if !prefix.is_empty() {
+        args.push(format!("--directory={}", prefix));
+    }
+    args
+}
+
+/// Run git in `dir`, feeding `stdin` to it when given
+fn git(dir: &Path, args: &[&str], stdin: Option<&str>) -> Result<Output> {
+    let mut child = Command::new("git")
+        .args([
+            "-c",
+            "user.name=gemini-mcp-rs",
+            "-c",
+            "user.email=gemini-mcp-rs@localhost",
+            "-c",
+            "commit.gpgsign=false",
+            "-c",
+            "core.autocrlf=false",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run git")?;
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(input.as_bytes())
+            .context("Failed to pass input to git")?;
+    }
+    child.wait_with_output().context("Failed to run git")
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        self.root.release(&self.dir);
+    }
+}
+
+/// Relative paths of the files in `source`, leaving out the ones git ignores when it
+/// is a repository
+pub fn list_files(source: &Path) -> Result<Vec<PathBuf>> {
+    let listed = Command::new("git")
+        .args([
+            "ls-files",
+            "-z",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+        ])
+        .current_dir(source)
+        .output();
+    match listed {
+        Ok(output) if output.status.success() => Ok(output
+            .stdout
+            .split(|&b| b == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| PathBuf::from(String::from_utf8_lossy(name).into_owned()))
+            // Files deleted but not yet staged are still listed as cached
+            .filter(|name| std::fs::symlink_metadata(source.join(name)).is_ok())
+            .collect()),
+        _ => {
+            let mut files = Vec::new();
+            walk(source, Path::new(""), &mut files)
+                .with_context(|| format!("Failed to read {}", source.display()))?;
+            Ok(files)
+        }
+    }
+}
+
+fn walk(root: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(root.join(relative))? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            walk(root, &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn copy_entry(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let metadata = std::fs::symlink_metadata(from)?;
+    if metadata.file_type().is_symlink() {
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(std::fs::read_link(from)?, to)?;
+        return Ok(());
+    }
+    if metadata.is_file() {
+        std::fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unique_dir() -> PathBuf {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        std::env::temp_dir().join(format!(
+            "gemini-mcp-scratch-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    fn scratch_root(max_age: Duration, max_bytes: u64) -> Arc<ScratchRoot> {
+        Arc::new(ScratchRoot::new(unique_dir(), max_age, max_bytes))
+    }
+
+    #[test]
+    fn test_gc_skips_active_and_removes_expired() {
+        let root = scratch_root(Duration::from_secs(60), u64::MAX);
+        let active = root.allocate("a").unwrap();
+        let finished = root.allocate("b").unwrap();
+        root.release(&finished);
+
+        let later = SystemTime::now() + Duration::from_secs(120);
+        let stats = root.collect_garbage(later);
+        let (active_left, finished_left) = (active.exists(), finished.exists());
+        let _ = std::fs::remove_dir_all(root.root());
+
+        assert_eq!(stats.removed, 1);
+        assert!(active_left);
+        assert!(!finished_left);
+    }
+
+    #[test]
+    fn test_gc_removes_oldest_over_size_limit() {
+        let root = scratch_root(Duration::from_secs(3600), 10);
+        let old = root.allocate("old").unwrap();
+        std::fs::write(old.join("data"), [0u8; 8]).unwrap();
+        root.release(&old);
+        std::thread::sleep(Duration::from_millis(20));
+        let new = root.allocate("new").unwrap();
+        std::fs::write(new.join("data"), [0u8; 8]).unwrap();
+        root.release(&new);
+
+        let stats = root.collect_garbage(SystemTime::now());
+        let (old_left, new_left) = (old.exists(), new.exists());
+        let _ = std::fs::remove_dir_all(root.root());
+
+        assert_eq!(
+            stats,
+            GcStats {
+                removed: 1,
+                freed_bytes: 8
+            }
+        );
+        assert!(!old_left);
+        assert!(new_left);
+    }
+
+    #[test]
+    fn test_preview_diff_leaves_source_untouched() {
+        let source = unique_dir();
+        std::fs::create_dir_all(source.join("src")).unwrap();
+        std::fs::write(source.join("src/lib.rs"), "fn a() {}\n").unwrap();
+        std::fs::write(source.join("old.txt"), "old\n").unwrap();
+
+        let root = scratch_root(Duration::ZERO, u64::MAX);
+        let Ok(scratch) = Scratch::create(&root, &source) else {
+            // git is not available on this machine
+            let _ = std::fs::remove_dir_all(&source);
+            let _ = std::fs::remove_dir_all(root.root());
+            return;
+        };
+        std::fs::write(scratch.path().join("src/lib.rs"), "fn b() {}\n").unwrap();
+        std::fs::remove_file(scratch.path().join("old.txt")).unwrap();
+        std::fs::write(scratch.path().join("new.txt"), "new\n").unwrap();
+        let patch = scratch.diff().unwrap();
+        let scratch_dir = scratch.path().to_path_buf();
+        drop(scratch);
+        // Kept after the call until collected
+        let kept = scratch_dir.exists();
+        root.collect_garbage(SystemTime::now() + Duration::from_secs(1));
+
+        let original = std::fs::read_to_string(source.join("src/lib.rs")).unwrap();
+        let _ = std::fs::remove_dir_all(&source);
+        let _ = std::fs::remove_dir_all(root.root());
+
+        assert!(patch.contains("diff --git a/src/lib.rs b/src/lib.rs"));
+        assert!(patch.contains("-fn a() {}\n+fn b() {}"));
+        assert!(patch.contains("deleted file mode"));
+        assert!(patch.contains("+++ b/new.txt"));
+        assert_eq!(original, "fn a() {}\n");
+        assert!(kept);
+        assert!(!scratch_dir.exists());
+    }
+
+    #[test]
+    fn test_apply_patch_checks_before_applying() {
+        let target = unique_dir();
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(target.join("a.txt"), "one\n").unwrap();
+        let patch =
+            "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-one\n+two\n";
+
+        let Ok(dry_run) = apply_patch(&target, patch, true) else {
+            // git is not available on this machine
+            let _ = std::fs::remove_dir_all(&target);
+            return;
+        };
+        let after_dry_run = std::fs::read_to_string(target.join("a.txt")).unwrap();
+        let applied = apply_patch(&target, patch, false).unwrap();
+        let after_apply = std::fs::read_to_string(target.join("a.txt")).unwrap();
+        // The file no longer matches the patch's preimage
+        let conflict = apply_patch(&target, patch, false).unwrap();
+        let _ = std::fs::remove_dir_all(&target);
+
+        assert!(dry_run.unwrap().contains("a.txt"));
+        assert_eq!(after_dry_run, "one\n");
+        assert!(applied.is_ok());
+        assert_eq!(after_apply, "two\n");
+        assert!(conflict.unwrap_err().contains("a.txt"));
+    }
+}
@@ -0,0 +1,392 @@
+//! Registry of the Gemini CLI sessions this server has created or resumed.
+//!
+//! Unlike the invocation history, which only keeps the most recent calls, the
+//! registry keeps one entry per SESSION_ID, so agents can find and resume earlier
+//! conversations with `list_sessions`. When a state directory is configured (see
+//! [`crate::state`]) it is saved to `sessions.json` after every change and survives
+//! restarts.
+//!
+//! The registry holds at most `GEMINI_MAX_SESSIONS` sessions (default
+//! [`MAX_SESSIONS`]), dropping the least recently used first. With
+//! `GEMINI_SESSION_TTL_SECS` set, sessions idle for longer are expired by
+//! [`SessionStore::expire`], which the server runs periodically.
+//!
+//! Calls may carry a `label` and `tags` ([`Attribution`]) naming the agent or stage
+//! that made them. A session keeps the latest label and every tag its calls used, so
+//! multi-agent systems can tell which sessions belong to whom.
+
+use crate::history::{format_timestamp, summarize_prompt};
+use crate::state::{state_dir, write_atomic};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Default for `GEMINI_MAX_SESSIONS`
+pub const MAX_SESSIONS: usize = 1000;
+pub const ENV_MAX_SESSIONS: &str = "GEMINI_MAX_SESSIONS";
+pub const ENV_SESSION_TTL: &str = "GEMINI_SESSION_TTL_SECS";
+pub const ENV_REQUIRE_KNOWN: &str = "GEMINI_REQUIRE_KNOWN_SESSIONS";
+const SESSIONS_FILE: &str = "sessions.json";
+
+/// Whether calls may only resume sessions in the registry (GEMINI_REQUIRE_KNOWN_SESSIONS).
+/// Off by default, since sessions started outside this server, or before a restart
+/// without a state directory, are missing from it
+pub fn require_known() -> bool {
+    crate::gemini::env_flag(ENV_REQUIRE_KNOWN).unwrap_or(false)
+}
+
+/// Caller-chosen names for a call, so logs and the session registry show which agent
+/// or stage made it. Never passed to the CLI
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attribution {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Attribution {
+    pub fn is_empty(&self) -> bool {
+        self.label.is_none() && self.tags.is_empty()
+    }
+
+    /// Take `other`'s label, if it has one, and add the tags not already present
+    fn merge(&mut self, other: &Attribution) {
+        if other.label.is_some() {
+            self.label = other.label.clone();
+        }
+        for tag in &other.tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+    }
+}
+
+/// What the server knows about one session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionRecord {
+    #[serde(rename = "SESSION_ID")]
+    pub session_id: String,
+    /// RFC 3339 UTC timestamp of the call that created the session
+    pub created_at: String,
+    /// RFC 3339 UTC timestamp of the latest call in the session
+    pub last_used_at: String,
+    /// Tool that created the session
+    pub tool: String,
+    /// Model of the latest call, when known
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Summary of the prompt that started the session
+    pub prompt_summary: String,
+    /// Summary of the latest prompt
+    pub last_prompt_summary: String,
+    pub calls: u64,
+    /// Label of the latest call that gave one, and the tags of every call
+    #[serde(flatten)]
+    pub attribution: Attribution,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionFile {
+    sessions: Vec<SessionRecord>,
+}
+
+/// Thread-safe session registry, optionally persisted to disk
+#[derive(Debug)]
+pub struct SessionStore {
+    inner: Mutex<SessionFile>,
+    path: Option<PathBuf>,
+    max_sessions: usize,
+    /// How long a session may go unused before it expires; `None` never expires
+    idle_ttl: Option<Duration>,
+}
+
+impl SessionStore {
+    /// Load sessions from `path`, if given
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let file = path
+            .as_ref()
+            .and_then(|p| std::fs::read(p).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        Self {
+            inner: Mutex::new(file),
+            path,
+            max_sessions: MAX_SESSIONS,
+            idle_ttl: None,
+        }
+    }
+
+    /// Keep at most `max_sessions` sessions (at least one), and expire those unused
+    /// for longer than `idle_ttl`
+    pub fn with_limits(mut self, max_sessions: usize, idle_ttl: Option<Duration>) -> Self {
+        self.max_sessions = max_sessions.max(1);
+        self.idle_ttl = idle_ttl;
+        self
+    }
+
+    /// Registry saved under `GEMINI_STATE_DIR`, when set, with limits from
+    /// `GEMINI_MAX_SESSIONS` and `GEMINI_SESSION_TTL_SECS`
+    pub fn from_env() -> Self {
+        let number = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+        let max_sessions = number(ENV_MAX_SESSIONS)
+            .filter(|&max| max > 0)
+            .map_or(MAX_SESSIONS, |max| max as usize);
+        let idle_ttl = number(ENV_SESSION_TTL)
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs);
+        Self::new(state_dir().map(|d| d.join(SESSIONS_FILE))).with_limits(max_sessions, idle_ttl)
+    }
+
+    pub fn max_sessions(&self) -> usize {
+        self.max_sessions
+    }
+
+    pub fn idle_ttl(&self) -> Option<Duration> {
+        self.idle_ttl
+    }
+
+    /// Note a call that ran in `session_id`, registering the session if it's new.
+    /// Returns the sessions dropped to stay within the session limit
+    pub fn touch(
+        &self,
+        session_id: &str,
+        tool: &str,
+        model: Option<&str>,
+        prompt: &str,
+        attribution: &Attribution,
+        at: SystemTime,
+    ) -> Vec<SessionRecord> {
+        let at = format_timestamp(at);
+        let summary = summarize_prompt(prompt);
+        let mut file = self.lock();
+        match file
+            .sessions
+            .iter_mut()
+            .find(|s| s.session_id == session_id)
+        {
+            Some(session) => {
+                session.last_used_at = at;
+                session.last_prompt_summary = summary;
+                session.calls += 1;
+                if model.is_some() {
+                    session.model = model.map(String::from);
+                }
+                session.attribution.merge(attribution);
+            }
+            None => file.sessions.push(SessionRecord {
+                session_id: session_id.to_string(),
+                created_at: at.clone(),
+                last_used_at: at,
+                tool: tool.to_string(),
+                model: model.map(String::from),
+                prompt_summary: summary.clone(),
+                last_prompt_summary: summary,
+                calls: 1,
+                attribution: attribution.clone(),
+            }),
+        }
+        let evicted = evict_least_recent(&mut file.sessions, self.max_sessions);
+        self.save(&file);
+        evicted
+    }
+
+    /// Remove sessions unused for longer than the idle TTL as of `now`, and any over
+    /// the session limit. Returns the removed sessions
+    pub fn expire(&self, now: SystemTime) -> Vec<SessionRecord> {
+        let mut file = self.lock();
+        let mut expired = Vec::new();
+        if let Some(cutoff) = self.idle_ttl.and_then(|ttl| now.checked_sub(ttl)) {
+            // RFC 3339 UTC timestamps compare correctly as strings
+            let cutoff = format_timestamp(cutoff);
+            let (stale, fresh) = std::mem::take(&mut file.sessions)
+                .into_iter()
+                .partition(|s| s.last_used_at < cutoff);
+            file.sessions = fresh;
+            expired = stale;
+        }
+        expired.extend(evict_least_recent(&mut file.sessions, self.max_sessions));
+        if !expired.is_empty() {
+            self.save(&file);
+        }
+        expired
+    }
+
+    /// All sessions, most recently used first
+    pub fn list(&self) -> Vec<SessionRecord> {
+        let mut sessions = self.lock().sessions.clone();
+        sessions.sort_by(|a, b| b.last_used_at.cmp(&a.last_used_at));
+        sessions
+    }
+
+    pub fn get(&self, session_id: &str) -> Option<SessionRecord> {
+        self.lock()
+            .sessions
+            .iter()
+            .find(|s| s.session_id == session_id)
+            .cloned()
+    }
+
+    /// Forget a session, returning it if it was registered
+    pub fn remove(&self, session_id: &str) -> Option<SessionRecord> {
+        let mut file = self.lock();
+        let index = file
+            .sessions
+            .iter()
+            .position(|s| s.session_id == session_id)?;
+        let removed = file.sessions.remove(index);
+        self.save(&file);
+        Some(removed)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SessionFile> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn save(&self, file: &SessionFile) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_vec_pretty(file)
+            .map_err(std::io::Error::from)
+            .and_then(|bytes| write_atomic(path, &bytes));
+        if let Err(e) = result {
+            tracing::warn!("Failed to save sessions to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Drop the least recently used sessions beyond `max`, returning them
+fn evict_least_recent(sessions: &mut Vec<SessionRecord>, max: usize) -> Vec<SessionRecord> {
+    if sessions.len() <= max {
+        return Vec::new();
+    }
+    // RFC 3339 UTC timestamps sort lexicographically
+    sessions.sort_by(|a, b| b.last_used_at.cmp(&a.last_used_at));
+    sessions.split_off(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_touch_registers_and_updates_sessions() {
+        let store = SessionStore::new(None);
+        store.touch(
+            "s1",
+            "gemini",
+            None,
+            "first prompt",
+            &Attribution::default(),
+            at(10),
+        );
+        store.touch(
+            "s2",
+            "gemini_agent",
+            Some("gemini-2.5-pro"),
+            "other",
+            &Attribution::default(),
+            at(20),
+        );
+        store.touch(
+            "s1",
+            "gemini",
+            Some("gemini-2.5-flash"),
+            "follow-up",
+            &Attribution::default(),
+            at(30),
+        );
+
+        let sessions = store.list();
+        let ids: Vec<&str> = sessions.iter().map(|s| s.session_id.as_str()).collect();
+        assert_eq!(ids, ["s1", "s2"]);
+        let s1 = &sessions[0];
+        assert_eq!(s1.calls, 2);
+        assert_eq!(s1.prompt_summary, "first prompt");
+        assert_eq!(s1.last_prompt_summary, "follow-up");
+        assert_eq!(s1.model.as_deref(), Some("gemini-2.5-flash"));
+        assert_eq!(s1.created_at, "1970-01-01T00:00:10Z");
+        assert_eq!(s1.last_used_at, "1970-01-01T00:00:30Z");
+    }
+
+    #[test]
+    fn test_touch_keeps_latest_label_and_every_tag() {
+        let store = SessionStore::new(None);
+        let planner = Attribution {
+            label: Some("planner".to_string()),
+            tags: vec!["stage:plan".to_string(), "run:42".to_string()],
+        };
+        let untagged = Attribution::default();
+        let reviewer = Attribution {
+            label: Some("reviewer".to_string()),
+            tags: vec!["run:42".to_string(), "stage:review".to_string()],
+        };
+        store.touch("s1", "gemini", None, "plan", &planner, at(10));
+        store.touch("s1", "gemini", None, "more", &untagged, at(20));
+        let after_untagged = store.get("s1").unwrap().attribution;
+        store.touch("s1", "gemini", None, "review", &reviewer, at(30));
+
+        assert_eq!(after_untagged, planner);
+        let session = store.get("s1").unwrap();
+        assert_eq!(session.attribution.label.as_deref(), Some("reviewer"));
+        assert_eq!(
+            session.attribution.tags,
+            ["stage:plan", "run:42", "stage:review"]
+        );
+        let json = serde_json::to_value(&session).unwrap();
+        assert_eq!(json["label"], "reviewer");
+        assert_eq!(json["tags"][2], "stage:review");
+    }
+
+    #[test]
+    fn test_limits_evict_and_expire_sessions() {
+        let store = SessionStore::new(None).with_limits(2, Some(Duration::from_secs(100)));
+        assert!(store
+            .touch("s1", "gemini", None, "a", &Attribution::default(), at(10))
+            .is_empty());
+        store.touch("s2", "gemini", None, "b", &Attribution::default(), at(20));
+        let evicted = store.touch("s3", "gemini", None, "c", &Attribution::default(), at(200));
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].session_id, "s1");
+
+        let expired = store.expire(at(250));
+        let ids: Vec<&str> = expired.iter().map(|s| s.session_id.as_str()).collect();
+        assert_eq!(ids, ["s2"]);
+        assert_eq!(store.list().len(), 1);
+        assert!(store.expire(at(250)).is_empty());
+    }
+
+    #[test]
+    fn test_remove_and_persistence() {
+        let dir = std::env::temp_dir().join(format!("gemini-mcp-sessions-{}", std::process::id()));
+        let path = dir.join(SESSIONS_FILE);
+        let store = SessionStore::new(Some(path.clone()));
+        store.touch(
+            "s1",
+            "gemini",
+            None,
+            "hello",
+            &Attribution::default(),
+            at(10),
+        );
+        store.touch("s2", "gemini", None, "bye", &Attribution::default(), at(20));
+        assert_eq!(store.remove("s2").map(|s| s.session_id), Some("s2".into()));
+        assert!(store.remove("s2").is_none());
+
+        let reloaded = SessionStore::new(Some(path));
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(reloaded.get("s1").is_some());
+        assert!(reloaded.get("s2").is_none());
+    }
+}
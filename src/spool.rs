@@ -0,0 +1,233 @@
+//! Event traces spooled to disk, for calls with `spool_messages`.
+//!
+//! A long agent run can emit more events than fit in a response, or than the 10,000
+//! kept in memory for `all_messages`. A spooled call appends every event it selects to
+//! `<dir>/<id>.jsonl` as the CLI emits it, one JSON value per line, and returns a
+//! [`TraceSummary`] (the file's path and counts per event type) instead of the events.
+//! The server also serves each trace as the resource `gemini://traces/<id>`.
+//!
+//! The directory is `GEMINI_SPOOL_DIR`, else `gemini-mcp-rs/traces` in the system temp
+//! directory. Traces older than `GEMINI_SPOOL_RETENTION_HOURS` (default 24, `0` to keep
+//! them forever) are removed whenever a new one is started.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const ENV_SPOOL_DIR: &str = "GEMINI_SPOOL_DIR";
+pub const ENV_SPOOL_RETENTION_HOURS: &str = "GEMINI_SPOOL_RETENTION_HOURS";
+const DEFAULT_RETENTION_HOURS: u64 = 24;
+const TRACE_EXTENSION: &str = "jsonl";
+
+/// Distinguishes traces started in the same millisecond
+static NEXT_TRACE: AtomicU64 = AtomicU64::new(1);
+
+/// Directory traces are written to
+pub fn spool_dir() -> PathBuf {
+    std::env::var_os(ENV_SPOOL_DIR)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("gemini-mcp-rs").join("traces"))
+}
+
+/// How long traces are kept, `None` for forever
+fn retention() -> Option<Duration> {
+    let hours = std::env::var(ENV_SPOOL_RETENTION_HOURS)
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_RETENTION_HOURS);
+    (hours > 0).then(|| Duration::from_secs(hours * 3600))
+}
+
+/// What a spooled call wrote
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TraceSummary {
+    /// Name of the trace, as used in its resource URI
+    pub id: String,
+    pub path: PathBuf,
+    /// Events written
+    pub events: usize,
+    /// Size of the file
+    pub bytes: u64,
+    /// Events written per `type`
+    pub event_types: BTreeMap<String, usize>,
+    /// Why the trace is missing or incomplete; the call itself is unaffected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Appends a call's events to its trace file
+#[derive(Debug)]
+pub struct TraceWriter {
+    summary: TraceSummary,
+    file: Option<BufWriter<std::fs::File>>,
+}
+
+impl TraceWriter {
+    /// Start a new trace in `dir`, first removing traces past their retention. A trace
+    /// that can't be created is reported in its summary rather than failing the call
+    pub fn create(dir: &Path) -> Self {
+        if let Some(max_age) = retention() {
+            sweep(dir, max_age, SystemTime::now());
+        }
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let id = format!(
+            "{}-{}-{}",
+            millis,
+            std::process::id(),
+            NEXT_TRACE.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = dir.join(format!("{}.{}", id, TRACE_EXTENSION));
+        let opened = std::fs::create_dir_all(dir).and_then(|_| std::fs::File::create(&path));
+        let (file, error) = match opened {
+            Ok(file) => (Some(BufWriter::new(file)), None),
+            Err(e) => {
+                tracing::warn!(path = %path.display(), "Failed to create trace file: {}", e);
+                (None, Some(e.to_string()))
+            }
+        };
+        Self {
+            summary: TraceSummary {
+                id,
+                path,
+                error,
+                ..Default::default()
+            },
+            file,
+        }
+    }
+
+    /// Append one event of type `type_name`. After a write error the rest of the events
+    /// are dropped
+    pub fn write(&mut self, type_name: &str, event: &Value) {
+        let Some(file) = self.file.as_mut() else {
+            return;
+        };
+        let written = serde_json::to_vec(event)
+            .map_err(io::Error::from)
+            .and_then(|mut line| {
+                line.push(b'\n');
+                file.write_all(&line).map(|_| line.len())
+            });
+        match written {
+            Ok(len) => {
+                self.summary.events += 1;
+                self.summary.bytes += len as u64;
+                *self
+                    .summary
+                    .event_types
+                    .entry(type_name.to_string())
+                    .or_default() += 1;
+            }
+            Err(e) => self.fail(e),
+        }
+    }
+
+    /// Flush the file and summarize what was written
+    pub fn finish(mut self) -> TraceSummary {
+        if let Some(Err(e)) = self.file.as_mut().map(|file| file.flush()) {
+            self.fail(e);
+        }
+        self.summary
+    }
+
+    fn fail(&mut self, e: io::Error) {
+        tracing::warn!(path = %self.summary.path.display(), "Failed to write trace: {}", e);
+        self.file = None;
+        self.summary.error = Some(e.to_string());
+    }
+}
+
+/// Path of the trace called `id` in `dir`; `None` for ids that couldn't name a trace
+pub fn trace_path(dir: &Path, id: &str) -> Option<PathBuf> {
+    let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_digit() || c == '-');
+    valid.then(|| dir.join(format!("{}.{}", id, TRACE_EXTENSION)))
+}
+
+/// The traces in `dir` with their sizes, newest first
+pub fn list(dir: &Path) -> Vec<(String, u64)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut traces: Vec<(String, u64, SystemTime)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(TRACE_EXTENSION) {
+                return None;
+            }
+            let id = path.file_stem()?.to_str()?.to_string();
+            let meta = entry.metadata().ok()?;
+            Some((id, meta.len(), meta.modified().unwrap_or(UNIX_EPOCH)))
+        })
+        .collect();
+    traces.sort_by(|a, b| b.2.cmp(&a.2));
+    traces.into_iter().map(|(id, len, _)| (id, len)).collect()
+}
+
+/// Remove traces in `dir` not written to for longer than `max_age`
+fn sweep(dir: &Path, max_age: Duration, now: SystemTime) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(TRACE_EXTENSION) {
+            continue;
+        }
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > max_age);
+        if expired {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_trace_writer_appends_events_and_counts_types() {
+        let dir = std::env::temp_dir().join(format!("gemini-mcp-spool-{}", std::process::id()));
+        let mut writer = TraceWriter::create(&dir);
+        writer.write("tool_use", &json!({"type": "tool_use", "tool_name": "ls"}));
+        writer.write("message", &json!({"type": "message", "content": "a"}));
+        writer.write("message", &json!({"type": "message", "content": "b"}));
+        let summary = writer.finish();
+
+        let contents = std::fs::read_to_string(&summary.path).unwrap();
+        let listed = list(&dir);
+        let found = trace_path(&dir, &summary.id);
+        sweep(
+            &dir,
+            Duration::ZERO,
+            SystemTime::now() + Duration::from_secs(1),
+        );
+        let swept = list(&dir);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(summary.error, None);
+        assert_eq!(summary.events, 3);
+        assert_eq!(summary.bytes, contents.len() as u64);
+        assert_eq!(summary.event_types.get("message"), Some(&2));
+        assert_eq!(summary.event_types.get("tool_use"), Some(&1));
+        assert_eq!(contents.lines().count(), 3);
+        assert_eq!(listed, vec![(summary.id.clone(), summary.bytes)]);
+        assert_eq!(found, Some(summary.path));
+        assert!(swept.is_empty());
+        assert_eq!(trace_path(&dir, "../secrets"), None);
+    }
+}
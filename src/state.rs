@@ -0,0 +1,52 @@
+//! On-disk state shared across server restarts.
+//!
+//! Persistence is opt-in: state is only written when `GEMINI_STATE_DIR` is set,
+//! so several servers started by different MCP clients never overwrite each
+//! other's files by accident. Give each server its own directory.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const ENV_STATE_DIR: &str = "GEMINI_STATE_DIR";
+
+/// Directory configured through `GEMINI_STATE_DIR`, if any
+pub fn state_dir() -> Option<PathBuf> {
+    std::env::var_os(ENV_STATE_DIR)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Replace `path` with `contents` by writing a sibling temp file and renaming it,
+/// so a crash mid-write never leaves a truncated file behind
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let dir = std::env::temp_dir().join(format!("gemini-mcp-state-{}", std::process::id()));
+        let path = dir.join("state.json");
+
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let leftovers = std::fs::read_dir(&dir).unwrap().count();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(contents, "second");
+        assert_eq!(leftovers, 1);
+    }
+}
@@ -0,0 +1,302 @@
+//! Tolerant parser for the Gemini CLI's stream-json stdout.
+//!
+//! The CLI normally writes one JSON object per line, but in practice stdout also
+//! carries JSON arrays, several objects concatenated on one line, pretty-printed
+//! objects spanning multiple lines, and plain-text noise before or between events.
+//! [`StreamParser`] takes stdout line by line and splits it into JSON values and
+//! text fragments, buffering incomplete JSON until it closes.
+//!
+//! Output is read as raw bytes and decoded with [`decode_line`], so a UTF-8 BOM,
+//! CRLF line endings or bytes that aren't valid UTF-8 (e.g. a Windows console
+//! code page leaking through a wrapper script) never abort parsing.
+
+use serde_json::Value;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// Decode one output line: strip a trailing CR and any BOM, replace invalid UTF-8
+pub fn decode_line(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+    let text = String::from_utf8_lossy(bytes);
+    match text.strip_prefix('\u{feff}') {
+        Some(rest) => rest.to_string(),
+        None => text.into_owned(),
+    }
+}
+
+/// Decode a whole text file: honor UTF-8/UTF-16 BOMs, fall back to lossy UTF-8,
+/// and normalize CRLF line endings to LF
+pub fn decode_text(bytes: &[u8]) -> String {
+    let text = if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+        String::from_utf8_lossy(rest).into_owned()
+    } else if let Some(rest) = bytes.strip_prefix(UTF16LE_BOM) {
+        decode_utf16(rest, u16::from_le_bytes)
+    } else if let Some(rest) = bytes.strip_prefix(UTF16BE_BOM) {
+        decode_utf16(rest, u16::from_be_bytes)
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    };
+    text.replace("\r\n", "\n")
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+const MAX_PENDING_BYTES: usize = 1024 * 1024; // Maximum size of an unterminated JSON value (1MB)
+const MAX_PENDING_LINES: usize = 10_000; // Maximum lines an unterminated JSON value may span
+
+/// One item recovered from the output stream
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamItem {
+    /// A parsed JSON event
+    Json(Value),
+    /// Text that isn't part of any JSON value
+    Text(String),
+}
+
+/// Incremental line-oriented parser; see the module docs
+#[derive(Debug, Default)]
+pub struct StreamParser {
+    pending: String,
+    pending_lines: usize,
+}
+
+impl StreamParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one line (without its terminator) and return the items it completes
+    pub fn push_line(&mut self, line: &str) -> Vec<StreamItem> {
+        let mut items = Vec::new();
+
+        if self.pending.is_empty() {
+            self.parse_fragment(line, &mut items);
+            return items;
+        }
+
+        let mut buffer = std::mem::take(&mut self.pending);
+        buffer.push('\n');
+        buffer.push_str(line);
+        self.pending_lines += 1;
+
+        if buffer.len() > MAX_PENDING_BYTES || self.pending_lines > MAX_PENDING_LINES {
+            // Never closed: give up on it and surface the raw lines as text
+            self.pending_lines = 0;
+            push_text(&buffer, &mut items);
+            return items;
+        }
+
+        self.parse_fragment(&buffer, &mut items);
+        items
+    }
+
+    /// Flush whatever is still buffered once the stream has ended
+    pub fn finish(&mut self) -> Vec<StreamItem> {
+        self.pending_lines = 0;
+        let mut items = Vec::new();
+        push_text(&std::mem::take(&mut self.pending), &mut items);
+        items
+    }
+
+    fn parse_fragment(&mut self, fragment: &str, items: &mut Vec<StreamItem>) {
+        let mut rest = fragment.trim();
+
+        while !rest.is_empty() {
+            let Some(start) = rest.find(['{', '[']) else {
+                push_text(rest, items);
+                break;
+            };
+            if start > 0 {
+                push_text(&rest[..start], items);
+                rest = &rest[start..];
+            }
+
+            let mut values = serde_json::Deserializer::from_str(rest).into_iter::<Value>();
+            match values.next() {
+                Some(Ok(value)) => {
+                    let consumed = values.byte_offset();
+                    push_value(value, items);
+                    rest = rest[consumed..].trim_start();
+                }
+                Some(Err(e)) if e.is_eof() => {
+                    // Looks like the start of a multi-line value; wait for more lines
+                    if self.pending_lines == 0 {
+                        self.pending_lines = 1;
+                    }
+                    self.pending = rest.to_string();
+                    return;
+                }
+                _ => {
+                    // Not JSON after all: keep it as text up to the next candidate start
+                    let next = rest[1..]
+                        .find(['{', '['])
+                        .map(|i| i + 1)
+                        .unwrap_or(rest.len());
+                    push_text(&rest[..next], items);
+                    rest = &rest[next..];
+                }
+            }
+        }
+
+        self.pending_lines = 0;
+    }
+}
+
+/// Emit text one trimmed, non-empty line at a time
+fn push_text(text: &str, items: &mut Vec<StreamItem>) {
+    items.extend(
+        text.lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(|l| StreamItem::Text(l.to_string())),
+    );
+}
+
+/// Emit a parsed value, unpacking arrays of event objects into individual events
+fn push_value(value: Value, items: &mut Vec<StreamItem>) {
+    match value {
+        Value::Array(elements) if !elements.is_empty() && elements.iter().all(Value::is_object) => {
+            items.extend(elements.into_iter().map(StreamItem::Json));
+        }
+        other => items.push(StreamItem::Json(other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse_all(lines: &[&str]) -> Vec<StreamItem> {
+        let mut parser = StreamParser::new();
+        let mut items: Vec<StreamItem> = lines.iter().flat_map(|l| parser.push_line(l)).collect();
+        items.extend(parser.finish());
+        items
+    }
+
+    #[test]
+    fn test_decode_line_strips_bom_and_cr() {
+        let mut bytes = UTF8_BOM.to_vec();
+        bytes.extend_from_slice(b"{\"type\":\"init\"}\r");
+        assert_eq!(decode_line(&bytes), r#"{"type":"init"}"#);
+    }
+
+    #[test]
+    fn test_decode_line_replaces_invalid_utf8() {
+        // "caf\xe9" is Latin-1/CP1252, not UTF-8
+        assert_eq!(decode_line(b"caf\xe9"), "caf\u{fffd}");
+    }
+
+    #[test]
+    fn test_decode_text_handles_boms_and_crlf() {
+        let mut utf8 = UTF8_BOM.to_vec();
+        utf8.extend_from_slice(b"line one\r\nline two\r\n");
+        assert_eq!(decode_text(&utf8), "line one\nline two\n");
+
+        let mut utf16le = UTF16LE_BOM.to_vec();
+        for unit in "hi\r\n".encode_utf16() {
+            utf16le.extend_from_slice(&unit.to_le_bytes());
+        }
+        assert_eq!(decode_text(&utf16le), "hi\n");
+
+        let mut utf16be = UTF16BE_BOM.to_vec();
+        for unit in "hi".encode_utf16() {
+            utf16be.extend_from_slice(&unit.to_be_bytes());
+        }
+        assert_eq!(decode_text(&utf16be), "hi");
+    }
+
+    #[test]
+    fn test_single_line_object() {
+        let items = parse_all(&[r#"{"type":"init","session_id":"s1"}"#]);
+        assert_eq!(
+            items,
+            vec![StreamItem::Json(json!({"type":"init","session_id":"s1"}))]
+        );
+    }
+
+    #[test]
+    fn test_array_of_events_on_one_line() {
+        let items = parse_all(&[r#"[{"type":"a"},{"type":"b"}]"#]);
+        assert_eq!(
+            items,
+            vec![
+                StreamItem::Json(json!({"type":"a"})),
+                StreamItem::Json(json!({"type":"b"})),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_concatenated_objects() {
+        let items = parse_all(&[r#"{"type":"a"}{"type":"b"} {"type":"c"}"#]);
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[2], StreamItem::Json(json!({"type":"c"})));
+    }
+
+    #[test]
+    fn test_pretty_printed_multiline_object() {
+        let items = parse_all(&[
+            "{",
+            r#"  "type": "message","#,
+            r#"  "role": "assistant""#,
+            "}",
+        ]);
+        assert_eq!(
+            items,
+            vec![StreamItem::Json(
+                json!({"type":"message","role":"assistant"})
+            )]
+        );
+    }
+
+    #[test]
+    fn test_interleaved_plain_text() {
+        let items = parse_all(&[
+            "Loading extensions...",
+            r#"Warning: slow network {"type":"init","session_id":"s1"} done"#,
+        ]);
+        assert_eq!(
+            items,
+            vec![
+                StreamItem::Text("Loading extensions...".to_string()),
+                StreamItem::Text("Warning: slow network".to_string()),
+                StreamItem::Json(json!({"type":"init","session_id":"s1"})),
+                StreamItem::Text("done".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bracketed_text_is_not_json() {
+        let items = parse_all(&["[INFO] starting {not json}"]);
+        assert_eq!(
+            items,
+            vec![
+                StreamItem::Text("[INFO] starting".to_string()),
+                StreamItem::Text("{not json}".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_object_flushed_as_text() {
+        let items = parse_all(&[r#"{"type": "message","#, "oops"]);
+        assert_eq!(
+            items,
+            vec![
+                StreamItem::Text(r#"{"type": "message","#.to_string()),
+                StreamItem::Text("oops".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Files and prompts for `gemini_summarize_files`.
+//!
+//! [`collect`] reads the text files in the working directory matching the requested
+//! glob patterns, skipping the ones git ignores. The summary is then built map-reduce
+//! style: each file is split into pieces that fit a prompt and every piece is
+//! summarized ([`piece_prompt`]), the pieces of a file are merged into its digest
+//! ([`file_prompt`]), and the digests are merged into one summary ([`merge_prompt`]).
+
+use crate::messages::{catalog, render};
+use crate::scratch;
+use crate::stream::decode_text;
+use regex::Regex;
+use std::path::Path;
+
+/// Most files one call may summarize
+pub const MAX_FILES: usize = 100;
+/// Most bytes of file content one call may summarize
+pub const MAX_TOTAL_BYTES: u64 = 4 * 1024 * 1024;
+/// Largest piece of text sent in one call
+pub const CHUNK_BYTES: usize = 64 * 1024;
+/// Most glob patterns one call may give
+pub const MAX_PATTERNS: usize = 20;
+
+/// A file to summarize
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    /// Relative to the working directory, with `/` separators
+    pub path: String,
+    pub content: String,
+}
+
+/// The text files under `dir` matching any of `patterns`, sorted by path. Patterns are
+/// relative to `dir`: `*` and `?` match within a path segment, `**` across segments,
+/// and `[...]` matches a character class
+pub fn collect(dir: &Path, patterns: &[String]) -> Result<Vec<SourceFile>, String> {
+    let patterns: Vec<&str> = patterns
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .collect();
+    if patterns.is_empty() || patterns.len() > MAX_PATTERNS {
+        return Err(render(
+            catalog().summarize_patterns_invalid,
+            &[&MAX_PATTERNS],
+        ));
+    }
+    let matchers = patterns
+        .iter()
+        .map(|p| glob_regex(p).ok_or_else(|| render(catalog().summarize_glob_invalid, &[p])))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let listed = scratch::list_files(dir)
+        .map_err(|e| render(catalog().execution_failed, &[&format!("{:#}", e)]))?;
+    let mut paths: Vec<String> = listed
+        .iter()
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .filter(|p| matchers.iter().any(|m| m.is_match(p)))
+        .collect();
+    paths.sort();
+    paths.dedup();
+    if paths.is_empty() {
+        return Err(render(
+            catalog().summarize_no_files,
+            &[&patterns.join(", ")],
+        ));
+    }
+    if paths.len() > MAX_FILES {
+        return Err(render(
+            catalog().summarize_too_many_files,
+            &[&paths.len(), &MAX_FILES],
+        ));
+    }
+
+    let mut files = Vec::with_capacity(paths.len());
+    let mut total = 0u64;
+    for path in paths {
+        let Ok(bytes) = std::fs::read(dir.join(&path)) else {
+            continue;
+        };
+        // Binary files have nothing to summarize
+        if bytes.contains(&0) {
+            continue;
+        }
+        total += bytes.len() as u64;
+        if total > MAX_TOTAL_BYTES {
+            return Err(render(catalog().summarize_too_large, &[&MAX_TOTAL_BYTES]));
+        }
+        let content = decode_text(&bytes);
+        if !content.trim().is_empty() {
+            files.push(SourceFile { path, content });
+        }
+    }
+    if files.is_empty() {
+        return Err(render(
+            catalog().summarize_no_files,
+            &[&patterns.join(", ")],
+        ));
+    }
+    Ok(files)
+}
+
+/// Prompt summarizing one piece of a file, or the whole file when `parts` is 1
+pub fn piece_prompt(
+    path: &str,
+    content: &str,
+    part: usize,
+    parts: usize,
+    focus: Option<&str>,
+) -> String {
+    let what = if parts > 1 {
+        format!("part {} of {} of the file `{}`", part, parts, path)
+    } else {
+        format!("the file `{}`", path)
+    };
+    format!(
+        "Summarize {}: its purpose, the main items it defines and how they fit together, \
+         and anything notable. Be concise and factual; do not speculate about code you \
+         cannot see.{}\n\n```\n{}\n```",
+        what,
+        focus_clause(focus),
+        content.trim_end()
+    )
+}
+
+/// Prompt merging the summaries of a file's pieces into the file's digest
+pub fn file_prompt(path: &str, summaries: &[String], focus: Option<&str>) -> String {
+    let mut prompt = format!(
+        "These are summaries of consecutive parts of the file `{}`. Merge them into one \
+         concise summary of the whole file, without repeating yourself.{}",
+        path,
+        focus_clause(focus)
+    );
+    for (index, summary) in summaries.iter().enumerate() {
+        prompt.push_str(&format!("\n\n## Part {}\n{}", index + 1, summary.trim()));
+    }
+    prompt
+}
+
+/// Prompt merging file digests, given as `### path` sections, into one summary
+pub fn merge_prompt(sections: &[String], focus: Option<&str>) -> String {
+    format!(
+        "These are summaries of files from one codebase. Write one overview of them \
+         together: what they do as a whole, how they relate, and the most important \
+         details, referring to files by path.{}\n\n{}",
+        focus_clause(focus),
+        sections.join("\n\n")
+    )
+}
+
+/// A file's digest as a section of a merge prompt
+pub fn section(path: &str, digest: &str) -> String {
+    format!("### {}\n{}", path, digest.trim())
+}
+
+fn focus_clause(focus: Option<&str>) -> String {
+    match focus.map(str::trim).filter(|f| !f.is_empty()) {
+        Some(focus) => format!(" Focus especially on: {}", focus),
+        None => String::new(),
+    }
+}
+
+/// Regular expression matching the relative paths `pattern` matches, or `None` for
+/// patterns leaving the working directory or that aren't valid
+fn glob_regex(pattern: &str) -> Option<Regex> {
+    let pattern = pattern.replace('\\', "/");
+    let pattern = pattern.strip_prefix("./").unwrap_or(&pattern);
+    if pattern.starts_with('/') || pattern.split('/').any(|segment| segment == "..") {
+        return None;
+    }
+    let mut source = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    source.push_str("(?:.*/)?");
+                } else {
+                    source.push_str(".*");
+                }
+            }
+            '*' => source.push_str("[^/]*"),
+            '?' => source.push_str("[^/]"),
+            '[' => {
+                let mut class = String::new();
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    class.push(c);
+                }
+                let class = class
+                    .strip_prefix('!')
+                    .map(|rest| format!("^{}", rest))
+                    .unwrap_or(class);
+                source.push_str(&format!("[{}]", class));
+            }
+            c => source.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    source.push('$');
+    Regex::new(&source).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_regex() {
+        let matches = |pattern: &str, path: &str| glob_regex(pattern).unwrap().is_match(path);
+        assert!(matches("src/*.rs", "src/lib.rs"));
+        assert!(!matches("src/*.rs", "src/bin/main.rs"));
+        assert!(matches("src/**/*.rs", "src/lib.rs"));
+        assert!(matches("src/**/*.rs", "src/bin/main.rs"));
+        assert!(matches("**/README.md", "README.md"));
+        assert!(matches("./docs/?.md", "docs/a.md"));
+        assert!(matches("src/[!m]*.rs", "src/lib.rs"));
+        assert!(!matches("src/[!m]*.rs", "src/main.rs"));
+        assert!(!matches("*.rs", "build.rs.bak"));
+        assert!(glob_regex("../secrets/*").is_none());
+        assert!(glob_regex("/etc/*").is_none());
+    }
+
+    #[test]
+    fn test_collect_reads_matching_text_files() {
+        let dir =
+            std::env::temp_dir().join(format!("gemini-mcp-summarize-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/b.rs"), "fn b() {}\r\n").unwrap();
+        std::fs::write(dir.join("src/a.rs"), "fn a() {}\n").unwrap();
+        std::fs::write(dir.join("src/blob.rs"), b"\x00\x01").unwrap();
+        std::fs::write(dir.join("notes.txt"), "notes").unwrap();
+
+        let collected = collect(&dir, &["src/*.rs".to_string(), "src/a.rs".to_string()]);
+        let missing = collect(&dir, &["*.md".to_string()]);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let paths: Vec<String> = collected.unwrap().into_iter().map(|f| f.path).collect();
+        assert_eq!(paths, vec!["src/a.rs", "src/b.rs"]);
+        assert!(missing.is_err());
+    }
+}
@@ -0,0 +1,256 @@
+//! Per-session transcripts kept on disk, behind the `get_transcript` tool.
+//!
+//! Every call that runs in a session appends one JSON line (timestamp, tool, model,
+//! prompt, reply or error) to `<dir>/<SESSION_ID>.jsonl`, so conversations stay
+//! readable after the in-memory history has moved on or the server restarted. The
+//! directory is `GEMINI_TRANSCRIPT_DIR`, else `transcripts` under `GEMINI_STATE_DIR`,
+//! else `gemini-mcp-rs/transcripts` in the user's data directory
+//! (`~/.local/share` on Linux). Set `GEMINI_TRANSCRIPT_DIR=off` to keep none.
+//!
+//! Transcripts not written to for `GEMINI_TRANSCRIPT_RETENTION_DAYS` (default 30,
+//! `0` to keep them forever) are removed by a periodic sweep.
+
+use crate::state::state_dir;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+pub const ENV_TRANSCRIPT_DIR: &str = "GEMINI_TRANSCRIPT_DIR";
+pub const ENV_TRANSCRIPT_RETENTION_DAYS: &str = "GEMINI_TRANSCRIPT_RETENTION_DAYS";
+const DEFAULT_RETENTION_DAYS: u64 = 30;
+const TRANSCRIPT_EXTENSION: &str = "jsonl";
+
+/// One call in a session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    /// RFC 3339 UTC timestamp of when the call started
+    pub timestamp: String,
+    pub tool: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    pub prompt: String,
+    #[serde(default)]
+    pub response: Option<String>,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Transcript files in one directory
+#[derive(Debug)]
+pub struct TranscriptStore {
+    dir: Option<PathBuf>,
+    retention: Option<Duration>,
+    /// Serializes appends from concurrent calls in the same process
+    write_lock: Mutex<()>,
+}
+
+impl TranscriptStore {
+    /// Store writing to `dir` (`None` keeps nothing), removing transcripts idle for
+    /// longer than `retention` (`None` keeps them forever)
+    pub fn new(dir: Option<PathBuf>, retention: Option<Duration>) -> Self {
+        Self {
+            dir,
+            retention,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Store configured through `GEMINI_TRANSCRIPT_DIR` and
+    /// `GEMINI_TRANSCRIPT_RETENTION_DAYS`
+    pub fn from_env() -> Self {
+        let dir = match std::env::var(ENV_TRANSCRIPT_DIR) {
+            Ok(v) if matches!(v.trim().to_ascii_lowercase().as_str(), "off" | "none" | "0") => None,
+            Ok(v) if !v.trim().is_empty() => Some(PathBuf::from(v.trim())),
+            _ => state_dir()
+                .map(|d| d.join("transcripts"))
+                .or_else(|| data_dir().map(|d| d.join("gemini-mcp-rs").join("transcripts"))),
+        };
+        let days = std::env::var(ENV_TRANSCRIPT_RETENTION_DAYS)
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_RETENTION_DAYS);
+        let retention = (days > 0).then(|| Duration::from_secs(days * 86_400));
+        Self::new(dir, retention)
+    }
+
+    /// Directory transcripts are written to, `None` when disabled
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Append a call to the session's transcript
+    pub fn append(&self, session_id: &str, entry: &TranscriptEntry) -> io::Result<()> {
+        let Some(path) = self.path(session_id) else {
+            return Ok(());
+        };
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?
+            .write_all(&line)
+    }
+
+    /// The session's calls, oldest first; `None` when no transcript exists.
+    /// Lines that can't be parsed (e.g. cut short by a crash) are skipped
+    pub fn read(&self, session_id: &str) -> Option<Vec<TranscriptEntry>> {
+        let file = std::fs::File::open(self.path(session_id)?).ok()?;
+        Some(
+            io::BufReader::new(file)
+                .lines()
+                .map_while(Result::ok)
+                .filter_map(|line| serde_json::from_str(&line).ok())
+                .collect(),
+        )
+    }
+
+    /// Delete a session's transcript, returning whether one existed
+    pub fn remove(&self, session_id: &str) -> io::Result<bool> {
+        let Some(path) = self.path(session_id) else {
+            return Ok(false);
+        };
+        match std::fs::remove_file(path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Remove transcripts last written more than the retention period before `now`.
+    /// Returns how many were removed
+    pub fn collect_garbage(&self, now: SystemTime) -> usize {
+        let (Some(dir), Some(retention)) = (&self.dir, self.retention) else {
+            return 0;
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return 0;
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().and_then(|e| e.to_str()) == Some(TRANSCRIPT_EXTENSION))
+            .filter(|path| {
+                std::fs::metadata(path)
+                    .and_then(|m| m.modified())
+                    .is_ok_and(|modified| {
+                        now.duration_since(modified).unwrap_or_default() > retention
+                    })
+            })
+            .filter(|path| std::fs::remove_file(path).is_ok())
+            .count()
+    }
+
+    fn path(&self, session_id: &str) -> Option<PathBuf> {
+        let name = file_name(session_id)?;
+        Some(self.dir.as_ref()?.join(name))
+    }
+}
+
+/// File name for a session's transcript. Session ids are UUIDs; anything that
+/// could escape the directory is rejected
+fn file_name(session_id: &str) -> Option<String> {
+    let valid = !session_id.is_empty()
+        && session_id.len() <= 128
+        && session_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then(|| format!("{}.{}", session_id, TRANSCRIPT_EXTENSION))
+}
+
+/// The user's data directory: `$XDG_DATA_HOME` or `~/.local/share`, and
+/// `%LOCALAPPDATA%` on Windows
+fn data_dir() -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty());
+    if cfg!(windows) {
+        return var("LOCALAPPDATA").map(PathBuf::from);
+    }
+    var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| var("HOME").map(|home| Path::new(&home).join(".local").join("share")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(prompt: &str) -> TranscriptEntry {
+        TranscriptEntry {
+            timestamp: "2024-01-01T00:00:00Z".to_string(),
+            tool: "gemini".to_string(),
+            model: Some("gemini-2.5-pro".to_string()),
+            prompt: prompt.to_string(),
+            response: Some("ok".to_string()),
+            success: true,
+            error: None,
+            duration_ms: 10,
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "gemini-mcp-transcripts-{}-{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_append_and_read_back() {
+        let dir = temp_dir("append");
+        let store = TranscriptStore::new(Some(dir.clone()), None);
+        store.append("s-1", &entry("first")).unwrap();
+        store.append("s-1", &entry("second")).unwrap();
+        store.append("../escape", &entry("nope")).unwrap();
+        std::fs::write(dir.join("s-2.jsonl"), "{\"broken\n").unwrap();
+
+        let turns = store.read("s-1");
+        let broken = store.read("s-2");
+        let files = std::fs::read_dir(&dir).unwrap().count();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let prompts: Vec<_> = turns.unwrap().into_iter().map(|e| e.prompt).collect();
+        assert_eq!(prompts, ["first", "second"]);
+        assert_eq!(broken, Some(Vec::new()));
+        assert!(store.read("missing").is_none());
+        assert_eq!(files, 2);
+    }
+
+    #[test]
+    fn test_collect_garbage_removes_idle_transcripts() {
+        let dir = temp_dir("gc");
+        let store = TranscriptStore::new(Some(dir.clone()), Some(Duration::from_secs(3600)));
+        store.append("old", &entry("p")).unwrap();
+
+        let kept = store.collect_garbage(SystemTime::now());
+        let removed = store.collect_garbage(SystemTime::now() + Duration::from_secs(7200));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!((kept, removed), (0, 1));
+    }
+
+    #[test]
+    fn test_remove_deletes_transcript() {
+        let dir = temp_dir("remove");
+        let store = TranscriptStore::new(Some(dir.clone()), None);
+        store.append("s-1", &entry("p")).unwrap();
+        let removed = (store.remove("s-1").unwrap(), store.remove("s-1").unwrap());
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(removed, (true, false));
+    }
+
+    #[test]
+    fn test_disabled_store_keeps_nothing() {
+        let store = TranscriptStore::new(None, None);
+        store.append("s-1", &entry("p")).unwrap();
+        assert!(store.read("s-1").is_none());
+    }
+}
@@ -179,7 +179,7 @@ impl<T: DeserializeOwned> Decoder for AdaptiveCodec<T> {
             match Self::detect_format(buf) {
                 Some(fmt) => {
                     self.detected_format = Some(fmt);
-                    eprintln!("[transport] Detected framing format: {:?}", fmt);
+                    tracing::debug!("Detected framing format: {:?}", fmt);
                     // Store in shared state if available
                     if let Some(ref shared) = self.shared_format {
                         // Use try_write to avoid blocking - if we can't get the lock,
@@ -385,7 +385,7 @@ where
             next.await.and_then(|result| {
                 result
                     .inspect_err(|e| {
-                        eprintln!("[transport] Error reading message: {}", e);
+                        tracing::warn!("Error reading message: {}", e);
                     })
                     .ok()
             })
@@ -436,6 +436,30 @@ where
     }
 }
 
+/// A bidirectional byte stream, such as a socket connection, with adaptive framing
+pub struct AdaptiveStream<S> {
+    stream: S,
+}
+
+impl<S> AdaptiveStream<S> {
+    pub fn new(stream: S) -> Self {
+        Self { stream }
+    }
+}
+
+impl<Role, S> IntoTransport<Role, std::io::Error, AdaptiveTransportAdapter> for AdaptiveStream<S>
+where
+    Role: ServiceRole,
+    S: AsyncRead + AsyncWrite + Send + 'static,
+    RxJsonRpcMessage<Role>: DeserializeOwned,
+    TxJsonRpcMessage<Role>: Serialize,
+{
+    fn into_transport(self) -> impl Transport<Role, Error = std::io::Error> + 'static {
+        let (read, write) = tokio::io::split(self.stream);
+        AdaptiveTransport::<_, _, TxJsonRpcMessage<Role>, RxJsonRpcMessage<Role>>::new(read, write)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
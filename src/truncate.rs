@@ -0,0 +1,115 @@
+//! Response size limit behind `max_response_bytes`.
+//!
+//! A long agentic session can log thousands of events, and returning them all with
+//! `return_all_messages` produces responses some MCP clients can't handle. A limit
+//! (the parameter, or `GEMINI_MAX_RESPONSE_BYTES` when a call sets none) caps the
+//! bytes spent on `agent_messages` plus `all_messages`, measured as compact JSON.
+//! Truncation is deterministic: the reply keeps its first bytes, then events are
+//! kept in order while they fit and the rest are dropped. Unset means no limit.
+
+use crate::gemini::GeminiResult;
+use serde::Serialize;
+
+pub const ENV_MAX_RESPONSE_BYTES: &str = "GEMINI_MAX_RESPONSE_BYTES";
+/// Smallest accepted limit, so a truncated reply still says something
+pub const MIN_RESPONSE_BYTES: usize = 1024;
+
+/// The server-wide limit from `GEMINI_MAX_RESPONSE_BYTES`; values under
+/// [`MIN_RESPONSE_BYTES`] are ignored
+pub fn default_limit() -> Option<usize> {
+    std::env::var(ENV_MAX_RESPONSE_BYTES)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|limit| *limit >= MIN_RESPONSE_BYTES)
+}
+
+/// What [`apply`] removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Truncation {
+    /// Bytes cut from the end of `agent_messages`
+    pub agent_messages_bytes_dropped: usize,
+    /// Events dropped from the end of `all_messages`
+    pub events_dropped: usize,
+}
+
+impl Truncation {
+    pub fn is_truncated(&self) -> bool {
+        self.agent_messages_bytes_dropped > 0 || self.events_dropped > 0
+    }
+}
+
+/// Shrink `result` so its reply and events fit in `limit` bytes
+pub fn apply(result: &mut GeminiResult, limit: usize) -> Truncation {
+    let mut truncation = Truncation::default();
+    let reply = &mut result.agent_messages;
+    if reply.len() > limit {
+        let mut cut = limit;
+        while !reply.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        truncation.agent_messages_bytes_dropped = reply.len() - cut;
+        reply.truncate(cut);
+    }
+
+    let mut budget = limit - reply.len();
+    let kept = result
+        .all_messages
+        .iter()
+        .take_while(|event| {
+            // One extra byte for the separating comma
+            let size = serde_json::to_string(event).map_or(0, |s| s.len()) + 1;
+            match budget.checked_sub(size) {
+                Some(rest) => {
+                    budget = rest;
+                    true
+                }
+                None => false,
+            }
+        })
+        .count();
+    truncation.events_dropped = result.all_messages.len() - kept;
+    result.all_messages.truncate(kept);
+    truncation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_within_limit_is_untouched() {
+        let mut result = GeminiResult {
+            agent_messages: "short".to_string(),
+            all_messages: vec![json!({"type": "message"})],
+            ..Default::default()
+        };
+        let truncation = apply(&mut result, MIN_RESPONSE_BYTES);
+        assert!(!truncation.is_truncated());
+        assert_eq!(result.all_messages.len(), 1);
+    }
+
+    #[test]
+    fn test_drops_trailing_events_then_reply_bytes() {
+        let event = json!({"type": "message", "content": "x".repeat(90)});
+        let mut result = GeminiResult {
+            agent_messages: "reply".to_string(),
+            all_messages: vec![event; 20],
+            ..Default::default()
+        };
+        let truncation = apply(&mut result, 1024);
+        assert_eq!(result.agent_messages, "reply");
+        assert_eq!(result.all_messages.len(), 20 - truncation.events_dropped);
+        assert!(truncation.events_dropped > 0);
+
+        let mut result = GeminiResult {
+            agent_messages: "é".repeat(600),
+            all_messages: vec![json!({"type": "message"})],
+            ..Default::default()
+        };
+        let truncation = apply(&mut result, 1025);
+        assert_eq!(result.agent_messages.len(), 1024);
+        assert_eq!(truncation.agent_messages_bytes_dropped, 176);
+        assert_eq!(truncation.events_dropped, 1);
+    }
+}
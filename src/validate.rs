@@ -0,0 +1,246 @@
+//! Reply validation for `gemini_validated` and the `response_schema` parameter of `gemini`.
+//!
+//! A reply can be checked against a regular expression and/or a JSON Schema. Schemas
+//! are compiled with the `jsonschema` crate, so every keyword of the draft they declare
+//! (2020-12 by default) is enforced, `format` included. `$ref`s must point inside the
+//! schema, since remote documents are never fetched. JSON replies may be wrapped in a
+//! Markdown code fence.
+
+use regex::Regex;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+
+/// Most problems listed in the feedback for one reply
+const MAX_REPORTED_ERRORS: usize = 10;
+
+/// Checks a reply must pass
+#[derive(Clone)]
+pub struct Validator {
+    schema: Option<Arc<jsonschema::Validator>>,
+    pattern: Option<Regex>,
+}
+
+impl fmt::Debug for Validator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Validator")
+            .field("schema", &self.schema.is_some())
+            .field("pattern", &self.pattern.as_ref().map(Regex::as_str))
+            .finish()
+    }
+}
+
+impl Validator {
+    /// Build a validator from a JSON Schema, a regular expression, or both. A schema
+    /// that doesn't compile is rejected rather than letting any reply through
+    pub fn new(schema: Option<Value>, pattern: Option<&str>) -> Result<Self, String> {
+        let schema = match schema {
+            Some(schema) if !schema.is_object() => {
+                return Err("schema must be a JSON object".to_string())
+            }
+            Some(schema) => Some(Arc::new(
+                jsonschema::options()
+                    .should_validate_formats(true)
+                    .build(&schema)
+                    .map_err(|e| e.to_string())?,
+            )),
+            None => None,
+        };
+        let pattern = pattern
+            .filter(|p| !p.is_empty())
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        Ok(Self { schema, pattern })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schema.is_none() && self.pattern.is_none()
+    }
+
+    /// Problems with a reply, empty when it is valid
+    pub fn check(&self, reply: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(reply) {
+                errors.push(format!(
+                    "reply does not match the pattern {}",
+                    pattern.as_str()
+                ));
+            }
+        }
+        if let Some(schema) = &self.schema {
+            match serde_json::from_str::<Value>(extract_json(reply)) {
+                Ok(value) => errors.extend(schema.iter_errors(&value).map(|e| {
+                    let path = e.instance_path.to_string();
+                    let path = if path.is_empty() {
+                        "(root)"
+                    } else {
+                        path.as_str()
+                    };
+                    format!("{}: {}", path, e)
+                })),
+                Err(e) => errors.push(format!("reply is not valid JSON: {}", e)),
+            }
+        }
+        errors.truncate(MAX_REPORTED_ERRORS);
+        errors
+    }
+
+    /// The JSON document of a reply, when the validator has a schema. Fails when the
+    /// reply isn't JSON
+    pub fn parse(&self, reply: &str) -> Result<Option<Value>, String> {
+        if self.schema.is_none() {
+            return Ok(None);
+        }
+        serde_json::from_str(extract_json(reply))
+            .map(Some)
+            .map_err(|e| format!("reply is not valid JSON: {}", e))
+    }
+}
+
+/// The JSON document in a reply, without a surrounding Markdown code fence
+pub fn extract_json(reply: &str) -> &str {
+    let trimmed = reply.trim();
+    let Some(rest) = trimmed.strip_prefix("```") else {
+        return trimmed;
+    };
+    // Skip the info string (e.g. `json`) on the opening line
+    let body = rest.split_once('\n').map_or("", |(_, body)| body);
+    body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+}
+
+/// Output instructions appended to a prompt whose reply must satisfy `schema`
+pub fn schema_instructions(schema: &Value) -> String {
+    format!(
+        "Reply with a single JSON document that satisfies the following JSON Schema and nothing \
+         else: no explanations, no commentary and no Markdown code fence.\n\n{}",
+        serde_json::to_string_pretty(schema).unwrap_or_default()
+    )
+}
+
+/// Feedback prompt asking for a corrected reply
+pub fn retry_prompt(errors: &[String]) -> String {
+    let mut prompt = String::from("Your previous reply failed validation and must be corrected:\n");
+    for error in errors {
+        prompt.push_str(&format!("- {}\n", error));
+    }
+    prompt.push_str(
+        "Reply again with the complete corrected answer only, without explanations or commentary.",
+    );
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn person_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["name", "age"],
+            "additionalProperties": false,
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "age": {"type": "integer", "minimum": 0},
+                "tags": {"type": "array", "items": {"enum": ["a", "b"]}}
+            }
+        })
+    }
+
+    #[test]
+    fn test_extract_json_strips_code_fence() {
+        assert_eq!(extract_json("```json\n{\"a\": 1}\n```"), "{\"a\": 1}");
+        assert_eq!(extract_json("```\n[1]\n```\n"), "[1]");
+        assert_eq!(extract_json("  {\"a\": 1} "), "{\"a\": 1}");
+    }
+
+    #[test]
+    fn test_schema_instructions_embed_schema() {
+        let instructions = schema_instructions(&json!({"type": "array"}));
+        assert!(instructions.starts_with("Reply with a single JSON document"));
+        assert!(instructions.ends_with("{\n  \"type\": \"array\"\n}"));
+    }
+
+    #[test]
+    fn test_schema_accepts_valid_reply() {
+        let validator = Validator::new(Some(person_schema()), None).unwrap();
+        let reply = "```json\n{\"name\": \"Ada\", \"age\": 36, \"tags\": [\"a\"]}\n```";
+        assert!(validator.check(reply).is_empty());
+    }
+
+    #[test]
+    fn test_schema_reports_each_problem() {
+        let validator = Validator::new(Some(person_schema()), None).unwrap();
+        let errors = validator.check(r#"{"name": "", "age": 1.5, "tags": ["c"], "x": 1}"#);
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        for location in ["/age: ", "/name: ", "/tags/0: ", "(root): "] {
+            assert!(
+                errors.iter().any(|e| e.starts_with(location)),
+                "{} in {:?}",
+                location,
+                errors
+            );
+        }
+
+        let errors = validator.check("not json");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("reply is not valid JSON"));
+        let errors = validator.check("{}");
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors.iter().all(|e| e.starts_with("(root): ")));
+    }
+
+    #[test]
+    fn test_schema_enforces_composition_and_references() {
+        let schema = json!({
+            "$defs": {"id": {"type": "integer", "minimum": 1}},
+            "type": "object",
+            "properties": {
+                "id": {"$ref": "#/$defs/id"},
+                "value": {"oneOf": [{"type": "string"}, {"type": "number"}]},
+                "day": {"type": "string", "format": "date"}
+            }
+        });
+        let validator = Validator::new(Some(schema), None).unwrap();
+        assert!(validator.check(r#"{"id": 3, "value": "x"}"#).is_empty());
+        assert_eq!(validator.check(r#"{"id": 0}"#).len(), 1);
+        assert_eq!(validator.check(r#"{"value": true}"#).len(), 1);
+        assert_eq!(validator.check(r#"{"day": "2024-13-45"}"#).len(), 1);
+    }
+
+    #[test]
+    fn test_uncompilable_schemas_are_rejected() {
+        assert!(Validator::new(Some(json!({"type": "no-such-type"})), None).is_err());
+        assert!(Validator::new(Some(json!({"$ref": "https://example.com/s.json"})), None).is_err());
+        assert!(Validator::new(Some(json!({"pattern": "("})), None).is_err());
+    }
+
+    #[test]
+    fn test_parse_needs_a_schema_and_json() {
+        let validator = Validator::new(Some(json!({"type": "array"})), None).unwrap();
+        assert_eq!(validator.parse("```json\n[1]\n```"), Ok(Some(json!([1]))));
+        assert!(validator.parse("not json").is_err());
+
+        let pattern_only = Validator::new(None, Some("^ok$")).unwrap();
+        assert_eq!(pattern_only.parse("ok"), Ok(None));
+    }
+
+    #[test]
+    fn test_pattern_validation() {
+        let validator = Validator::new(None, Some(r"^\d{4}-\d{2}-\d{2}$")).unwrap();
+        assert!(validator.check("2024-03-01").is_empty());
+        assert_eq!(validator.check("March 1st").len(), 1);
+
+        assert!(Validator::new(None, Some("(")).is_err());
+        assert!(Validator::new(Some(json!("object")), None).is_err());
+        assert!(Validator::new(None, Some("")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_retry_prompt_lists_errors() {
+        let prompt = retry_prompt(&["$: missing required property 'name'".to_string()]);
+        assert!(prompt.contains("- $: missing required property 'name'\n"));
+    }
+}
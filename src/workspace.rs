@@ -0,0 +1,170 @@
+//! Extra workspace directories for the `include_directories` parameter.
+//!
+//! The Gemini CLI only sees its working directory unless `--include-directories`
+//! adds more. Callers may name directories (relative to the working directory unless
+//! absolute), but each must resolve, after following symlinks, to a directory inside
+//! the allowlisted root: `GEMINI_INCLUDE_ROOT`, or the working directory when unset.
+//! An agent can thus narrow what Gemini sees within a monorepo without reaching
+//! anything outside it.
+//!
+//! The working directory itself is the first workspace root the MCP client reports
+//! (see [`root_from_uri`]) when it has any, and the server's own otherwise.
+
+use crate::messages::{catalog, render};
+use std::path::{Path, PathBuf};
+
+pub const ENV_INCLUDE_ROOT: &str = "GEMINI_INCLUDE_ROOT";
+/// Most directories one call may add, the CLI's own limit
+pub const MAX_INCLUDE_DIRECTORIES: usize = 5;
+
+/// The directory included directories must be inside, from `GEMINI_INCLUDE_ROOT` or `cwd`
+pub fn include_root(cwd: &Path) -> PathBuf {
+    std::env::var_os(ENV_INCLUDE_ROOT)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| cwd.to_path_buf())
+}
+
+/// The local directory a client root URI (`file:///path`, percent-encoded) names, if it
+/// exists
+pub fn root_from_uri(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    // Only local roots: `file:///path` or `file://localhost/path`
+    let path = match rest.strip_prefix("localhost") {
+        Some(path) => path,
+        None => rest,
+    };
+    if !path.starts_with('/') {
+        return None;
+    }
+    let path = percent_decode(path)?;
+    // `file:///C:/src` names `C:/src` on Windows
+    #[cfg(windows)]
+    let path = match path.strip_prefix('/') {
+        Some(rest) if rest.as_bytes().get(1) == Some(&b':') => rest.to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path)).filter(|p| p.is_dir())
+}
+
+/// `text` with `%XX` escapes decoded, `None` when they don't form UTF-8
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Check the directories named in `dirs`, relative to `base` unless absolute, and
+/// return their canonical paths
+pub fn resolve(dirs: &[String], base: &Path, root: &Path) -> Result<Vec<PathBuf>, String> {
+    let dirs: Vec<&str> = dirs
+        .iter()
+        .map(|d| d.trim())
+        .filter(|d| !d.is_empty())
+        .collect();
+    if dirs.len() > MAX_INCLUDE_DIRECTORIES {
+        return Err(render(
+            catalog().too_many_include_directories,
+            &[&MAX_INCLUDE_DIRECTORIES],
+        ));
+    }
+    let root = root
+        .canonicalize()
+        .map_err(|_| render(catalog().include_directory_not_found, &[&root.display()]))?;
+
+    let mut resolved: Vec<PathBuf> = Vec::with_capacity(dirs.len());
+    for name in dirs {
+        let path = base
+            .join(name)
+            .canonicalize()
+            .ok()
+            .filter(|p| p.is_dir())
+            .ok_or_else(|| render(catalog().include_directory_not_found, &[&name]))?;
+        if !path.starts_with(&root) {
+            return Err(render(
+                catalog().include_directory_outside_root,
+                &[&name, &root.display()],
+            ));
+        }
+        if !resolved.contains(&path) {
+            resolved.push(path);
+        }
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_stays_inside_root() {
+        let root = std::env::temp_dir().join(format!("gemini-mcp-include-{}", std::process::id()));
+        let repo = root.join("repo");
+        std::fs::create_dir_all(repo.join("services/api")).unwrap();
+        std::fs::create_dir_all(repo.join("libs/core")).unwrap();
+        std::fs::write(repo.join("README.md"), "readme").unwrap();
+        let dirs =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|n| n.to_string()).collect() };
+
+        let ok = resolve(
+            &dirs(&["services/api", " ", "libs/core", "libs/core/"]),
+            &repo,
+            &repo,
+        );
+        let outside = resolve(&dirs(&[".."]), &repo, &repo);
+        let escaping = resolve(&dirs(&["services/../../repo/libs"]), &repo, &repo);
+        let missing = resolve(&dirs(&["services/web"]), &repo, &repo);
+        let file = resolve(&dirs(&["README.md"]), &repo, &repo);
+        let too_many = resolve(&dirs(&["a"; MAX_INCLUDE_DIRECTORIES + 1]), &repo, &repo);
+        let wider_root = resolve(&dirs(&[".."]), &repo, &root);
+        let _ = std::fs::remove_dir_all(&root);
+
+        let ok = ok.unwrap();
+        assert_eq!(ok.len(), 2);
+        assert!(ok[0].ends_with("services/api"));
+        assert!(outside.is_err());
+        assert_eq!(escaping.unwrap().len(), 1);
+        assert!(missing.is_err());
+        assert!(file.is_err());
+        assert!(too_many.is_err());
+        assert_eq!(wider_root.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_root_from_uri() {
+        let dir = std::env::temp_dir().join(format!("gemini-mcp root {}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let encoded = dir.display().to_string().replace(' ', "%20");
+        let root = root_from_uri(&format!("file://{}", encoded));
+        let missing = root_from_uri(&format!("file://{}/missing", encoded));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(cfg!(not(unix)) || root == Some(dir));
+        assert!(missing.is_none());
+        assert!(root_from_uri("https://example.com/repo").is_none());
+        assert!(root_from_uri("file://server/share").is_none());
+        assert_eq!(
+            percent_decode("a%2Fb%zz%+1%"),
+            Some("a/b%zz%+1%".to_string())
+        );
+    }
+}
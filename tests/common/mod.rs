@@ -1,58 +1,71 @@
-// Common test utilities and helpers
-
-/// Get a temporary directory for testing
-pub fn get_temp_dir() -> std::path::PathBuf {
-    std::env::temp_dir()
-}
-
-/// Create a test options with default values
-pub fn create_test_options(prompt: &str) -> gemini_mcp_rs::gemini::Options {
-    gemini_mcp_rs::gemini::Options {
-        prompt: prompt.to_string(),
-        sandbox: false,
-        session_id: None,
-        return_all_messages: false,
-        model: None,
-    }
-}
-
-/// Mock session ID generator
-pub fn generate_mock_session_id() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    format!("test-session-{}", timestamp)
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_get_temp_dir() {
-        let temp = get_temp_dir();
-        assert!(temp.exists());
-        assert!(temp.is_dir());
-    }
-
-    #[test]
-    fn test_create_test_options() {
-        let opts = create_test_options("test prompt");
-        assert_eq!(opts.prompt, "test prompt");
-        assert!(!opts.sandbox);
-    }
-
-    #[test]
-    fn test_generate_mock_session_id() {
-        let id1 = generate_mock_session_id();
-        std::thread::sleep(std::time::Duration::from_millis(10));
-        let id2 = generate_mock_session_id();
-
-        assert!(id1.starts_with("test-session-"));
-        assert!(id2.starts_with("test-session-"));
-        assert_ne!(id1, id2);
-    }
-}
-
+// Common test utilities and helpers
+#![allow(dead_code)]
+
+/// Get a temporary directory for testing
+pub fn get_temp_dir() -> std::path::PathBuf {
+    std::env::temp_dir()
+}
+
+/// Create a test options with default values
+pub fn create_test_options(prompt: &str) -> gemini_mcp_rs::gemini::Options {
+    gemini_mcp_rs::gemini::Options {
+        prompt: prompt.to_string(),
+        sandbox: false,
+        session_id: None,
+        return_all_messages: false,
+        model: None,
+        ..Default::default()
+    }
+}
+
+/// Write a mock-gemini script to a unique temp file and return a prompt that selects it
+pub fn mock_script_prompt(name: &str, script: &str) -> String {
+    let path = get_temp_dir().join(format!(
+        "gemini-mcp-rs-{}-{}-{}.jsonl",
+        name,
+        std::process::id(),
+        generate_mock_session_id()
+    ));
+    std::fs::write(&path, script).expect("failed to write mock script");
+    format!("MOCK_SCRIPT={}", path.display())
+}
+
+/// Mock session ID generator
+pub fn generate_mock_session_id() -> String {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    format!("test-session-{}", timestamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_temp_dir() {
+        let temp = get_temp_dir();
+        assert!(temp.exists());
+        assert!(temp.is_dir());
+    }
+
+    #[test]
+    fn test_create_test_options() {
+        let opts = create_test_options("test prompt");
+        assert_eq!(opts.prompt, "test prompt");
+        assert!(!opts.sandbox);
+    }
+
+    #[test]
+    fn test_generate_mock_session_id() {
+        let id1 = generate_mock_session_id();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let id2 = generate_mock_session_id();
+
+        assert!(id1.starts_with("test-session-"));
+        assert!(id2.starts_with("test-session-"));
+        assert_ne!(id1, id2);
+    }
+}
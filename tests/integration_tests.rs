@@ -15,6 +15,7 @@ mod tests {
             return_all_messages: false,
             model: None,
             timeout_secs: None,
+            ..Default::default()
         };
 
         // This test requires a real Gemini CLI installation
@@ -32,6 +33,7 @@ mod tests {
             return_all_messages: true,
             model: Some("gemini-pro".to_string()),
             timeout_secs: Some(300),
+            ..Default::default()
         };
 
         assert_eq!(opts.prompt, "test");
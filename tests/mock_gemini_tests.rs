@@ -0,0 +1,327 @@
+// Integration tests driving gemini::run against the bundled mock CLI.
+// Run with: cargo test --features mock-gemini --test mock_gemini_tests
+#![cfg(feature = "mock-gemini")]
+
+mod common;
+
+use common::{create_test_options, mock_script_prompt};
+use futures::StreamExt;
+use gemini_mcp_rs::gemini::{self, FailureKind, GeminiClient, GeminiError, GeminiEvent, Options};
+use gemini_mcp_rs::health;
+
+fn mock_options(name: &str, script: &str) -> Options {
+    // Every test points GEMINI_BIN at the same binary, so the shared env var is race-free
+    std::env::set_var("GEMINI_BIN", env!("CARGO_BIN_EXE_mock-gemini"));
+    create_test_options(&mock_script_prompt(name, script))
+}
+
+#[tokio::test]
+async fn test_mock_successful_session() {
+    let opts = mock_options(
+        "success",
+        r#"{"type":"init","session_id":"mock-session-1","model":"gemini-2.5-pro"}
+{"type":"message","role":"user","content":"hi"}
+{"type":"message","role":"assistant","content":"Hello"}
+{"type":"message","role":"assistant","content":"from mock"}
+{"type":"result","status":"success"}
+"#,
+    );
+
+    let result = gemini::run(opts).await.unwrap();
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.session_id, "mock-session-1");
+    assert_eq!(result.agent_messages, "Hello\nfrom mock");
+}
+
+#[tokio::test]
+async fn test_mock_streams_partial_messages() {
+    let mut opts = mock_options(
+        "partial",
+        r#"{"type":"init","session_id":"mock-session-4"}
+{"type":"message","role":"assistant","content":"first"}
+{"type":"tool_use","tool_name":"read_file"}
+{"type":"message","role":"assistant","content":"second"}
+{"type":"result","status":"success"}
+"#,
+    );
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    opts.partial_messages = Some(tx);
+
+    let result = gemini::run(opts).await.unwrap();
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    let mut streamed = Vec::new();
+    while let Some(text) = rx.recv().await {
+        streamed.push(text);
+    }
+    assert_eq!(streamed, ["first", "second"]);
+}
+
+#[tokio::test]
+async fn test_mock_spools_events_to_disk() {
+    let mut opts = mock_options(
+        "spool",
+        r#"{"type":"init","session_id":"mock-session-spool"}
+{"type":"tool_use","tool_name":"read_file"}
+{"type":"tool_result","status":"success"}
+{"type":"message","role":"assistant","content":"done"}
+{"type":"result","status":"success"}
+"#,
+    );
+    let dir = std::env::temp_dir().join(format!("gemini-mcp-spool-mock-{}", std::process::id()));
+    opts.return_all_messages = true;
+    opts.message_filter = vec!["tool_use".to_string(), "tool_result".to_string()];
+    opts.spool_dir = Some(dir.clone());
+
+    let result = gemini::run(opts).await.unwrap();
+    let trace = result.trace.clone().unwrap();
+    let contents = std::fs::read_to_string(&trace.path).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.agent_messages, "done");
+    assert!(result.all_messages.is_empty());
+    assert_eq!(trace.events, 2);
+    assert_eq!(trace.event_types.get("tool_use"), Some(&1));
+    assert_eq!(contents.lines().count(), 2);
+    assert!(contents.contains("read_file"));
+}
+
+#[tokio::test]
+async fn test_mock_large_prompt_is_sent_on_stdin() {
+    let mut opts = mock_options(
+        "stdin",
+        r#"{"type":"init","session_id":"mock-session-5"}
+#echo-args
+{"type":"result","status":"success"}
+"#,
+    );
+    opts.prompt
+        .push_str(&format!("\n{}", "x".repeat(64 * 1024)));
+
+    let result = gemini::run(opts).await.unwrap();
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert!(
+        !result.agent_messages.contains("--prompt"),
+        "args were: {}",
+        result.agent_messages
+    );
+}
+
+#[tokio::test]
+async fn test_mock_nonzero_exit_includes_stderr() {
+    let opts = mock_options(
+        "exit",
+        r#"{"type":"init","session_id":"mock-session-2"}
+#stderr quota exceeded
+#exit 3
+"#,
+    );
+
+    let result = gemini::run(opts).await.unwrap();
+    assert!(!result.success);
+    assert_eq!(result.failure_kind(), Some(FailureKind::ExitCode));
+    let error = result.error.unwrap();
+    assert!(error.contains("Some(3)"), "error was: {}", error);
+    assert!(error.contains("quota exceeded"), "error was: {}", error);
+}
+
+#[tokio::test]
+async fn test_mock_expired_login_is_auth_failure() {
+    let opts = mock_options(
+        "auth",
+        r#"#stderr Error: invalid_grant: Token has been expired or revoked.
+#exit 1
+"#,
+    );
+
+    let result = gemini::run(opts).await.unwrap();
+    assert!(!result.success);
+    assert_eq!(result.failure_kind(), Some(FailureKind::Auth));
+    let error = result.error.unwrap();
+    assert!(error.contains("Some(1)"), "error was: {}", error);
+    assert!(error.contains("To fix: "), "error was: {}", error);
+}
+
+#[tokio::test]
+async fn test_mock_error_event_marks_failure() {
+    let opts = mock_options(
+        "error-event",
+        r#"{"type":"init","session_id":"mock-session-3"}
+{"type":"error","message":"model overloaded"}
+"#,
+    );
+
+    let result = gemini::run(opts).await.unwrap();
+    assert!(!result.success);
+    assert_eq!(result.failure_kind(), Some(FailureKind::CliError));
+    assert!(result.error.unwrap().contains("model overloaded"));
+}
+
+#[tokio::test]
+async fn test_mock_non_json_output() {
+    let opts = mock_options("non-json", "not json at all\n");
+
+    let result = gemini::run(opts).await.unwrap();
+    assert!(!result.success);
+    assert_eq!(result.failure_kind(), Some(FailureKind::NoJsonOutput));
+    assert!(result.error.unwrap().contains("not json at all"));
+}
+
+#[tokio::test]
+async fn test_mock_timeout_kills_child() {
+    let mut opts = mock_options(
+        "timeout",
+        r#"{"type":"init","session_id":"mock-session-4"}
+#sleep 10000
+{"type":"message","role":"assistant","content":"too late"}
+"#,
+    );
+    opts.timeout_secs = Some(1);
+
+    let started = std::time::Instant::now();
+    let result = gemini::run(opts).await.unwrap();
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    // What was read before the timeout comes back with the failure
+    assert!(!result.success);
+    assert!(result.timed_out);
+    assert_eq!(result.session_id, "mock-session-4");
+    assert_eq!(result.failure_kind(), Some(FailureKind::Timeout));
+    let error = result.error.unwrap();
+    assert!(error.contains("timed out"), "error was: {}", error);
+}
+
+#[tokio::test]
+async fn test_mock_idle_timeout_keeps_partial_reply() {
+    let mut opts = mock_options(
+        "idle-timeout",
+        r#"{"type":"init","session_id":"mock-session-idle"}
+{"type":"message","role":"assistant","content":"first half"}
+#sleep 10000
+{"type":"message","role":"assistant","content":"too late"}
+"#,
+    );
+    opts.idle_timeout_secs = Some(1);
+
+    let started = std::time::Instant::now();
+    let result = gemini::run(opts).await.unwrap();
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    assert!(result.timed_out);
+    assert_eq!(result.session_id, "mock-session-idle");
+    assert_eq!(result.agent_messages, "first half");
+    assert!(matches!(
+        result.failure,
+        Some(GeminiError::IdleTimeout { secs: 1 })
+    ));
+}
+
+#[tokio::test]
+async fn test_mock_receives_cli_flags() {
+    let mut opts = mock_options(
+        "args",
+        r#"{"type":"init","session_id":"mock-session-5"}
+#echo-args
+"#,
+    );
+    opts.model = Some("gemini-2.0-flash".to_string());
+    opts.sandbox = true;
+    opts.extra_args = vec!["--telemetry".to_string(), "false".to_string()];
+
+    let result = gemini::run(opts).await.unwrap();
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert!(result
+        .agent_messages
+        .contains("\"--model\",\"gemini-2.0-flash\""));
+    assert!(result.agent_messages.contains("\"--sandbox\""));
+    assert!(result.agent_messages.contains("\"stream-json\""));
+    assert!(result.agent_messages.contains("\"--telemetry\",\"false\""));
+}
+
+#[tokio::test]
+async fn test_mock_irregular_json_framing() {
+    let opts = mock_options(
+        "framing",
+        r#"Loaded cached credentials.
+{
+  "type": "init",
+  "session_id": "mock-session-6"
+}
+[{"type":"message","role":"assistant","content":"one"},{"type":"message","role":"assistant","content":"two"}]
+{"type":"message","role":"assistant","content":"three"}{"type":"result","status":"success"}
+"#,
+    );
+
+    let result = gemini::run(opts).await.unwrap();
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.session_id, "mock-session-6");
+    assert_eq!(result.agent_messages, "one\ntwo\nthree");
+}
+
+#[tokio::test]
+async fn test_mock_health_reports_version() {
+    let bin = env!("CARGO_BIN_EXE_mock-gemini");
+    let report = health::check(Some(bin), health::VERSION_TIMEOUT).await;
+
+    assert!(report.healthy, "unexpected error: {:?}", report.error);
+    assert_eq!(report.version.as_deref(), Some("0.0.0-mock"));
+    assert_eq!(report.path.as_deref(), Some(bin));
+}
+
+#[tokio::test]
+async fn test_mock_client_streams_and_logs() {
+    let prompt = mock_script_prompt(
+        "client",
+        r#"{"type":"init","session_id":"mock-session-9"}
+{"type":"message","role":"assistant","content":"streamed"}
+{"type":"result","status":"success"}
+"#,
+    );
+    let logged = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = std::sync::Arc::clone(&logged);
+    let client = GeminiClient::builder()
+        .binary(env!("CARGO_BIN_EXE_mock-gemini"))
+        .default_timeout_secs(30)
+        .log_hook(move |_, message| sink.lock().unwrap().push(message.to_string()))
+        .build();
+
+    let mut call = client.run_streaming(client.options(prompt));
+    assert_eq!(call.messages.recv().await.as_deref(), Some("streamed"));
+    let result = call.result().await.unwrap();
+    assert!(result.success, "unexpected error: {:?}", result.error);
+    assert_eq!(result.session_id, "mock-session-9");
+    assert!(logged
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|m| m.starts_with("session mock-session-9 finished")));
+}
+
+#[tokio::test]
+async fn test_mock_run_streaming_reports_events() {
+    let opts = mock_options(
+        "events",
+        r#"{"type":"init","session_id":"mock-session-10"}
+{"type":"tool_use","tool_name":"read_file","tool_id":"t1","parameters":{}}
+{"type":"message","role":"assistant","content":"done reading"}
+{"type":"result","status":"success","stats":{"input_tokens":7,"output_tokens":3}}
+"#,
+    );
+
+    let events: Vec<GeminiEvent> = gemini::run_streaming(opts).collect().await;
+    let names: Vec<&str> = events
+        .iter()
+        .map(|e| match e {
+            GeminiEvent::SessionStarted { .. } => "session",
+            GeminiEvent::AssistantDelta(_) => "delta",
+            GeminiEvent::ToolUse { .. } => "tool",
+            GeminiEvent::Usage(_) => "usage",
+            GeminiEvent::Error(_) => "error",
+            GeminiEvent::Done(_) => "done",
+        })
+        .collect();
+    assert_eq!(names, ["session", "tool", "delta", "usage", "done"]);
+    let Some(GeminiEvent::Done(Ok(result))) = events.last() else {
+        panic!("run failed: {:?}", events.last());
+    };
+    assert_eq!(result.session_id, "mock-session-10");
+    assert_eq!(result.agent_messages, "done reading");
+}
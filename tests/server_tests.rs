@@ -14,9 +14,20 @@ fn test_server_info() {
 
     assert_eq!(info.protocol_version, ProtocolVersion::V_2024_11_05);
     assert!(info.capabilities.tools.is_some());
+    assert!(info.capabilities.resources.is_some());
     assert!(info.instructions.is_some());
 }
 
+#[test]
+fn test_instructions_reflect_tools_and_transport() {
+    let server = GeminiServer::new().with_transport("streamable HTTP at /mcp");
+    let instructions = server.get_info().instructions.unwrap();
+
+    assert!(instructions.contains("gemini_ask"));
+    assert!(instructions.contains("gemini_job_status"));
+    assert!(instructions.contains("Transport: streamable HTTP at /mcp."));
+}
+
 #[test]
 fn test_default_implementation() {
     let server1 = GeminiServer::new();
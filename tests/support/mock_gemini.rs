@@ -0,0 +1,77 @@
+//! Mock Gemini CLI used by the integration tests via `GEMINI_BIN`.
+//!
+//! The script to replay is named by a `MOCK_SCRIPT=<path>` line anywhere in the
+//! `--prompt` argument (or stdin, when there is none), so concurrently running
+//! tests never share state through environment variables. Each script line is written to stdout verbatim, except
+//! for these directives:
+//!
+//! - `#sleep <ms>`: flush stdout and pause
+//! - `#stderr <text>`: write a line to stderr
+//! - `#exit <code>`: flush stdout and exit with the given code
+//! - `#echo-args`: emit an assistant message whose content is the argv as JSON
+//!
+//! `--version` prints a fixed version and exits without a script.
+//!
+//! Build with `cargo build --features mock-gemini --bin mock-gemini`.
+
+use std::io::{Read, Write};
+use std::process::exit;
+use std::time::Duration;
+
+const SCRIPT_MARKER: &str = "MOCK_SCRIPT=";
+const MOCK_VERSION: &str = "0.0.0-mock";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|a| a == "--version") {
+        println!("{}", MOCK_VERSION);
+        return;
+    }
+    let prompt = match args.windows(2).find(|w| w[0] == "--prompt" || w[0] == "-p") {
+        Some(w) => w[1].clone(),
+        None => {
+            let mut input = String::new();
+            let _ = std::io::stdin().read_to_string(&mut input);
+            input
+        }
+    };
+
+    let Some(path) = prompt
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(SCRIPT_MARKER))
+    else {
+        eprintln!("mock-gemini: no {} line in prompt", SCRIPT_MARKER);
+        exit(2);
+    };
+
+    let script = match std::fs::read_to_string(path.trim()) {
+        Ok(script) => script,
+        Err(e) => {
+            eprintln!("mock-gemini: failed to read script {}: {}", path, e);
+            exit(2);
+        }
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    for line in script.lines() {
+        if let Some(ms) = line.strip_prefix("#sleep ") {
+            let _ = stdout.flush();
+            std::thread::sleep(Duration::from_millis(ms.trim().parse().unwrap_or(0)));
+        } else if let Some(text) = line.strip_prefix("#stderr ") {
+            eprintln!("{}", text);
+        } else if let Some(code) = line.strip_prefix("#exit ") {
+            let _ = stdout.flush();
+            exit(code.trim().parse().unwrap_or(1));
+        } else if line.trim() == "#echo-args" {
+            let event = serde_json::json!({
+                "type": "message",
+                "role": "assistant",
+                "content": serde_json::to_string(&args).unwrap_or_default(),
+            });
+            let _ = writeln!(stdout, "{}", event);
+        } else {
+            let _ = writeln!(stdout, "{}", line);
+        }
+    }
+    let _ = stdout.flush();
+}